@@ -1,3 +1,6 @@
+from midi_tracker.param_list import draw_param_rows
+
+
 class InstsTab:
     def __init__(self, state, pg_state) -> None:
         # self.screen = pg_state.screen
@@ -10,10 +13,12 @@ class InstsTab:
     def draw(self):
         right_most = (self.screen_width * self.pg_state.config.ui.tab.width)
         height = (self.screen_height * self.pg_state.config.ui.tab.row_height)
+        col_width = right_most * self.pg_state.config.ui.tab.row_elm_width
 
         self.draw_tab_lable(right_most, height)
 
-        self.tmp_view(right_most, self.screen_height - height)
+        draw_param_rows(self.pg_state, self.state,
+                        self.state.screen._0.rows(), height, col_width)
 
     def draw_tab_lable(self, right_most: float, height: float):
         middle_x = right_most * 0.5
@@ -28,16 +33,3 @@ class InstsTab:
         textRect.center = (middle_x, middle_y)
 
         self.pg_state.screen.blit(display, textRect)
-
-    def tmp_view(self, right_most: float, height: float):
-        middle_x = right_most * 0.5
-        middle_y = height * 0.5
-        color = self.pg_state.config.colors.text
-
-        display = self.pg_state.fonts[0].render(
-            "Under Construction", True, color)
-        textRect = display.get_rect()
-
-        textRect.center = (middle_x, middle_y)
-
-        self.pg_state.screen.blit(display, textRect)
@@ -0,0 +1,68 @@
+from midi_tracker.param_list import N_VISIBLE_ROWS
+
+
+class MappingsTab:
+    def __init__(self, state, pg_state) -> None:
+        self.state = state
+        self.log = pg_state.log
+        (self.screen_width, self.screen_height) = pg_state.screen_size
+        self.pg_state = pg_state
+
+    def draw(self):
+        right_most = (self.screen_width * self.pg_state.config.ui.tab.width)
+        height = (self.screen_height * self.pg_state.config.ui.tab.row_height)
+        col_width = right_most * self.pg_state.config.ui.tab.row_elm_width
+        rows = self.state.screen._0
+
+        self.draw_tab_lable(right_most, height)
+
+        if not rows:
+            # mappings are learned on the instrument, mixer, and effects screens.
+            self.draw_text("HOLD A ON A PARAMETER", right_most * 0.5, height * 3.5)
+            self.draw_text("AND MOVE A KNOB", right_most * 0.5, height * 4.5)
+            return
+
+        self.draw_rows(rows, height, col_width)
+
+    def draw_rows(self, rows: list[tuple[str, str, str]], height: float, col_width: float):
+        """draws a scrolling list of (controller, parameter, scope) rows, with the cursor on the
+        whole row"""
+        cursor = self.state.display_cursor
+        first = max(0, min(cursor.row - N_VISIBLE_ROWS // 2,
+                    len(rows) - N_VISIBLE_ROWS))
+        cols = [col_width * 0.75, col_width * 2.5, col_width * 4.25]
+
+        for i, row in enumerate(rows[first:first + N_VISIBLE_ROWS]):
+            row_i = first + i
+            bottom = (height * 3.0) + height * i
+            middle_y = bottom - height * 0.5
+
+            if row_i == cursor.row:
+                middle_x = (cols[0] + cols[-1]) * 0.5
+                width = cols[-1] - cols[0] + col_width
+                self.pg_state.draw_rect(
+                    (middle_x, middle_y), (width, height), self.pg_state.config.colors.cursor)
+                self.pg_state.draw_rect(
+                    (middle_x, middle_y), (width - 5, height - 5), self.pg_state.config.colors.back_ground)
+
+            for middle_x, text in zip(cols, row):
+                self.draw_text(text, middle_x, middle_y)
+
+    def draw_text(self, text: str, middle_x: float, middle_y: float):
+        color = self.pg_state.config.colors.text
+        display = self.pg_state.fonts[1].render(text, True, color)
+        textRect = display.get_rect()
+        textRect.center = (middle_x, middle_y)
+        self.pg_state.screen.blit(display, textRect)
+
+    def draw_tab_lable(self, right_most: float, height: float):
+        middle_x = right_most * 0.5
+        middle_y = height * 0.5
+        color = self.pg_state.config.colors.text
+
+        display = self.pg_state.fonts[0].render("MIDI Mappings", True, color)
+        textRect = display.get_rect()
+
+        textRect.center = (middle_x, middle_y)
+
+        self.pg_state.screen.blit(display, textRect)
@@ -0,0 +1,94 @@
+COLUMNS = ["LD-1", "LD-2", "BASS", "PERC", "MSTR"]
+# the hold line turns this color once a meter has gone over full scale.
+CLIP_COLOR = (243, 139, 168)
+
+
+class MixerTab:
+    def __init__(self, state, pg_state) -> None:
+        self.state = state
+        self.log = pg_state.log
+        (self.screen_width, self.screen_height) = pg_state.screen_size
+        self.pg_state = pg_state
+
+    def draw(self):
+        right_most = (self.screen_width * self.pg_state.config.ui.tab.width)
+        height = (self.screen_height * self.pg_state.config.ui.tab.row_height)
+        col_width = right_most / len(COLUMNS)
+        mixer = self.state.screen._0
+        levels = self.state.screen._1
+
+        self.draw_tab_lable(right_most, height)
+
+        for col_i, lable in enumerate(COLUMNS):
+            middle_x = col_width * (col_i + 0.5)
+            level = levels.channels[col_i] if col_i < len(
+                levels.channels) else levels.master
+
+            self.draw_text(lable, middle_x, height * 1.5)
+            self.draw_meter(level, middle_x, height, col_width)
+            self.draw_rows(mixer.rows(col_i), col_i,
+                           middle_x, height, col_width)
+
+        # the master bus compressor and limiter, under the master column.
+        self.draw_text(f"GR {self.state.gain_reduction:.1f}",
+                       col_width * (len(COLUMNS) - 0.5), height * 11.5)
+
+    def draw_text(self, text: str, middle_x: float, middle_y: float):
+        color = self.pg_state.config.colors.text
+        display = self.pg_state.fonts[1].render(text, True, color)
+        textRect = display.get_rect()
+        textRect.center = (middle_x, middle_y)
+        self.pg_state.screen.blit(display, textRect)
+
+    def draw_tab_lable(self, right_most: float, height: float):
+        middle_x = right_most * 0.5
+        middle_y = height * 0.5
+        color = self.pg_state.config.colors.text
+
+        display = self.pg_state.fonts[0].render("Mixer", True, color)
+        textRect = display.get_rect()
+        textRect.center = (middle_x, middle_y)
+        self.pg_state.screen.blit(display, textRect)
+
+    def draw_meter(self, level, middle_x: float, height: float, col_width: float):
+        """draws a peak meter growing up from the bottom of its box, with the RMS level inside
+        it and a line at the held peak"""
+        colors = self.pg_state.config.colors
+        top = height * 2.5
+        bottom = height * 7.5
+        full_height = bottom - top
+        bar_height = max(min(level.peak, 1.0) * full_height, 1.0)
+        rms_height = max(min(level.rms, 1.0) * full_height, 1.0)
+        hold_y = bottom - min(level.hold, 1.0) * (full_height - 4)
+        hold_color = CLIP_COLOR if level.hold >= 1.0 else colors.text
+        width = col_width * 0.3
+
+        self.pg_state.draw_rect(
+            (middle_x, (top + bottom) * 0.5), (width, full_height), colors.cursor)
+        self.pg_state.draw_rect(
+            (middle_x, (top + bottom) * 0.5), (width - 4, full_height - 4), colors.back_ground)
+        self.pg_state.draw_rect(
+            (middle_x, bottom - bar_height * 0.5), (width - 4, bar_height), colors.text)
+        self.pg_state.draw_rect(
+            (middle_x, bottom - rms_height * 0.5), (width * 0.5, rms_height), colors.cursor)
+        self.pg_state.draw_rect(
+            (middle_x, hold_y), (width - 4, 2), hold_color)
+
+    def draw_rows(self, rows: list[tuple[str, str]], col_i: int, middle_x: float, height: float, col_width: float):
+        colors = self.pg_state.config.colors
+        cursor = self.state.display_cursor
+
+        for row_i, (name, value) in enumerate(rows):
+            middle_y = height * (8.5 + row_i * 2.0)
+
+            self.draw_text(name, middle_x, middle_y)
+
+            if row_i == cursor.row and col_i == cursor.col:
+                self.pg_state.draw_rect(
+                    (middle_x, middle_y + height), (col_width * 0.9, height), colors.cursor)
+
+                if not cursor.selected:
+                    self.pg_state.draw_rect(
+                        (middle_x, middle_y + height), (col_width * 0.9 - 5, height - 5), colors.back_ground)
+
+            self.draw_text(value, middle_x, middle_y + height)
@@ -0,0 +1,31 @@
+N_VISIBLE_ROWS = 16
+
+
+def draw_param_rows(pg_state, state, rows: list[tuple[str, str]], height: float, col_width: float):
+    """draws a scrolling list of (name, value) parameter rows, with the cursor on the value"""
+    color = pg_state.config.colors.text
+    cursor = state.display_cursor
+    first = max(0, min(cursor.row - N_VISIBLE_ROWS // 2,
+                len(rows) - N_VISIBLE_ROWS))
+
+    for i, (name, value) in enumerate(rows[first:first + N_VISIBLE_ROWS]):
+        row_i = first + i
+        bottom = (height * 3.0) + height * i
+        middle_y = bottom - height * 0.5
+
+        for col_i, (middle_x, text) in enumerate([(col_width * 1.5, name), (col_width * 3.5, value)]):
+            display = pg_state.fonts[1].render(text, True, color)
+            textRect = display.get_rect()
+
+            textRect.center = (middle_x, middle_y)
+
+            if row_i == cursor.row and col_i == 1 and cursor.selected:
+                pg_state.draw_rect(
+                    (middle_x, middle_y), (col_width * 2.0, height), pg_state.config.colors.cursor)
+            elif row_i == cursor.row and col_i == 1:
+                pg_state.draw_rect(
+                    (middle_x, middle_y), (col_width * 2.0, height), pg_state.config.colors.cursor)
+                pg_state.draw_rect(
+                    (middle_x, middle_y), (col_width * 2.0 - 5, height - 5), pg_state.config.colors.back_ground)
+
+            pg_state.screen.blit(display, textRect)
@@ -1,4 +1,4 @@
-from tracker_backend import TrackerCommand, PhraseRow
+from tracker_backend import TrackerCommand, PhraseRow, ChordShape
 
 
 class PhrasesTab:
@@ -97,3 +97,30 @@ class PhrasesTab:
             case TrackerCommand.Volume(arg):
                 arg = int(arg * 15)
                 return f"V-{arg:X}"
+            case TrackerCommand.Chord(shape, inversion):
+                names = {
+                    ChordShape.Major: "MAJ",
+                    ChordShape.Minor: "MIN",
+                    ChordShape.Sus2: "SU2",
+                    ChordShape.Sus4: "SU4",
+                    ChordShape.Major7: "MA7",
+                    ChordShape.Minor7: "MI7",
+                    ChordShape.Dom7: "DO7",
+                }
+                return f"{names[shape]}{inversion}"
+            case TrackerCommand.NoteOff():
+                return "OFF"
+            case TrackerCommand.Cutoff(arg):
+                return f"C{arg:02X}"
+            case TrackerCommand.DelaySend(arg):
+                arg = int(arg * 15)
+                return f"D-{arg:X}"
+            case TrackerCommand.ReverbSend(arg):
+                arg = int(arg * 15)
+                return f"R-{arg:X}"
+            case TrackerCommand.MidiCc(slot, value):
+                return f"{'ABCD'[slot]}={value:02X}"
+            case TrackerCommand.MidiCcRamp(slot, value):
+                return f"{'ABCD'[slot]}/{value:02X}"
+            case TrackerCommand.PitchBend(arg):
+                return f"P{arg:02X}"
@@ -0,0 +1,43 @@
+from midi_tracker.param_list import draw_param_rows
+
+
+class SettingsTab:
+    def __init__(self, state, pg_state) -> None:
+        self.state = state
+        self.log = pg_state.log
+        (self.screen_width, self.screen_height) = pg_state.screen_size
+        self.pg_state = pg_state
+
+    def draw(self):
+        right_most = (self.screen_width * self.pg_state.config.ui.tab.width)
+        height = (self.screen_height * self.pg_state.config.ui.tab.row_height)
+        col_width = right_most * self.pg_state.config.ui.tab.row_elm_width
+        rows = self.state.screen._0.rows() + self.state.screen._1.rows()
+
+        self.draw_tab_lable(right_most, height)
+
+        draw_param_rows(self.pg_state, self.state, rows, height, col_width)
+
+        # how often the audio output has run dry, a bigger buffer helps if this keeps going up.
+        self.draw_text(f"UNDERRUNS {self.state.underruns}",
+                       col_width * 2.5, height * (len(rows) + 3.5))
+        self.draw_text("B: EXPORT SONG", col_width * 2.5, height * (len(rows) + 4.5))
+
+    def draw_text(self, text: str, middle_x: float, middle_y: float):
+        color = self.pg_state.config.colors.text
+        display = self.pg_state.fonts[1].render(text, True, color)
+        textRect = display.get_rect()
+        textRect.center = (middle_x, middle_y)
+        self.pg_state.screen.blit(display, textRect)
+
+    def draw_tab_lable(self, right_most: float, height: float):
+        middle_x = right_most * 0.5
+        middle_y = height * 0.5
+        color = self.pg_state.config.colors.text
+
+        display = self.pg_state.fonts[0].render("Settings", True, color)
+        textRect = display.get_rect()
+
+        textRect.center = (middle_x, middle_y)
+
+        self.pg_state.screen.blit(display, textRect)
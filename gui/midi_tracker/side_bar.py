@@ -1,7 +1,15 @@
+import pygame
+from midi_tracker.mixer_tab import CLIP_COLOR
+
+# the spectrum is drawn from this many dB below full scale up to full scale.
+SPECTRUM_RANGE = 72.0
+
+
 class SideBar:
-    def __init__(self, state, pg_state) -> None:
+    def __init__(self, state, pg_state, telemetry=None) -> None:
         # self.screen = pg_state.screen
         self.state = state
+        self.telemetry = telemetry
         self.log = pg_state.log
         # self.config = config
         (self.screen_width, self.screen_height) = pg_state.screen_size
@@ -25,8 +33,13 @@ class SideBar:
         middle_y = bottom * 0.5
         color = self.pg_state.config.colors.text
 
-        display = self.pg_state.fonts[0].render(
-            f"TEMPO: {self.state.tempo}", True, color)
+        # the estimated tempo of the MIDI clock being followed, if there is one.
+        if self.state.clock_tempo is not None:
+            tempo = f"EXT: {self.state.clock_tempo:.1f}"
+        else:
+            tempo = f"TEMPO: {self.state.tempo}"
+
+        display = self.pg_state.fonts[0].render(tempo, True, color)
         textRect = display.get_rect()
 
         textRect.center = (middle_x, middle_y)
@@ -60,11 +73,84 @@ class SideBar:
         """draws osciloscope to the screen"""
         self.log.info("drawing osciloscope")
         bottom = self.screen_height * self.pg_state.config.ui.menu.osciloscope
+        colors = self.pg_state.config.colors
+        width = self.screen_width - left_most
+        row_height = (bottom - top) / 3.0
+
+        # the scope is redrawn on its own, so it clears its own box first.
+        self.pg_state.draw_rect(
+            ((left_most + self.screen_width) * 0.5, (top + bottom) * 0.5), (width, bottom - top), colors.back_ground)
+
+        if self.telemetry is None:
+            return bottom
 
-        self.log.debug("osciloscope not implemented")
+        scope = self.telemetry.scope
+
+        # the master across the top, the channels side by side under it, then the spectrum and
+        # the meters.
+        self.draw_wave(scope.master, left_most, top, width, row_height)
+
+        channel_width = width / len(scope.channels)
+
+        for i, points in enumerate(scope.channels):
+            self.draw_wave(points, left_most + channel_width * i,
+                           top + row_height, channel_width, row_height)
+
+        self.draw_spectrum(self.telemetry.spectrum, left_most,
+                           top + row_height * 2.0, width * 0.6, row_height)
+        self.draw_levels(self.telemetry.levels, left_most + width * 0.6,
+                         top + row_height * 2.0, width * 0.4, row_height)
 
         return bottom
 
+    def draw_wave(self, points: list[float], left: float, top: float, width: float, height: float):
+        """draws one waveform as a line, filling the box with a full scale signal"""
+        color = self.pg_state.config.colors.text
+        middle_y = top + height * 0.5
+        # a little room is left so the boxes don't run into each other.
+        scale = height * 0.45
+        step = width / max(len(points) - 1, 1)
+        line = [(left + step * i, middle_y - max(min(point, 1.0), -1.0) * scale)
+                for i, point in enumerate(points)]
+
+        if len(line) > 1:
+            pygame.draw.lines(self.pg_state.screen, color, False, line)
+
+    def draw_spectrum(self, bands: list[float], left: float, top: float, width: float, height: float):
+        """draws the master spectrum as bars, lowest band on the left"""
+        color = self.pg_state.config.colors.cursor
+        bottom = top + height
+        band_width = width / max(len(bands), 1)
+
+        for i, db in enumerate(bands):
+            bar_height = max(min(1.0 + db / SPECTRUM_RANGE, 1.0), 0.0) * height * 0.9
+
+            if bar_height >= 1.0:
+                self.pg_state.draw_rect(
+                    (left + band_width * (i + 0.5), bottom - bar_height * 0.5), (band_width - 1, bar_height), color)
+
+    def draw_levels(self, levels, left: float, top: float, width: float, height: float):
+        """draws a small meter for each channel and the master, with the RMS level beside the
+        peak and a line at the held peak"""
+        colors = self.pg_state.config.colors
+        meters = list(levels.channels) + [levels.master]
+        meter_width = width / len(meters)
+        bottom = top + height * 0.95
+
+        for i, level in enumerate(meters):
+            middle_x = left + meter_width * (i + 0.5)
+            peak_height = min(level.peak, 1.0) * height * 0.9
+            rms_height = min(level.rms, 1.0) * height * 0.9
+            hold_y = bottom - min(level.hold, 1.0) * height * 0.9
+            hold_color = CLIP_COLOR if level.hold >= 1.0 else colors.text
+
+            self.pg_state.draw_rect(
+                (middle_x - meter_width * 0.15, bottom - peak_height * 0.5), (meter_width * 0.3, peak_height), colors.text)
+            self.pg_state.draw_rect(
+                (middle_x + meter_width * 0.15, bottom - rms_height * 0.5), (meter_width * 0.3, rms_height), colors.cursor)
+            self.pg_state.draw_rect(
+                (middle_x, hold_y), (meter_width * 0.7, 2), hold_color)
+
     def draw_menu_map(self, i, left_most: float, top: float):
         """draws menu map to the screen"""
         self.log.info("drawing menu map")
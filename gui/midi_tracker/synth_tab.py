@@ -0,0 +1,41 @@
+class SynthTab:
+    def __init__(self, state, pg_state) -> None:
+        self.state = state
+        self.log = pg_state.log
+        (self.screen_width, self.screen_height) = pg_state.screen_size
+        self.pg_state = pg_state
+
+    def draw(self):
+        right_most = (self.screen_width * self.pg_state.config.ui.tab.width)
+        height = (self.screen_height * self.pg_state.config.ui.tab.row_height)
+        middle_x = right_most * 0.5
+        instrument = self.state.screen._0
+        channel = self.state.screen._1
+
+        self.draw_tab_lable(right_most, height)
+
+        # notes come from the MIDI input, so there is nothing to edit here.
+        self.draw_text(f"INSTRUMENT {instrument:02X}", middle_x, height * 2.5)
+        self.draw_text(f"CHANNEL {channel + 1}", middle_x, height * 3.5)
+        self.draw_text(
+            f"NOTE {self.pg_state.display_note(self.state.playing[channel])}",
+            middle_x, height * 4.5)
+
+    def draw_text(self, text: str, middle_x: float, middle_y: float):
+        color = self.pg_state.config.colors.text
+        display = self.pg_state.fonts[1].render(text, True, color)
+        textRect = display.get_rect()
+        textRect.center = (middle_x, middle_y)
+        self.pg_state.screen.blit(display, textRect)
+
+    def draw_tab_lable(self, right_most: float, height: float):
+        middle_x = right_most * 0.5
+        middle_y = height * 0.5
+        color = self.pg_state.config.colors.text
+
+        display = self.pg_state.fonts[0].render("Synth", True, color)
+        textRect = display.get_rect()
+
+        textRect.center = (middle_x, middle_y)
+
+        self.pg_state.screen.blit(display, textRect)
@@ -0,0 +1,69 @@
+from os.path import basename
+
+
+N_PREVIEW_POINTS = 128
+
+
+class WaveTab:
+    def __init__(self, state, pg_state) -> None:
+        self.state = state
+        self.log = pg_state.log
+        (self.screen_width, self.screen_height) = pg_state.screen_size
+        self.pg_state = pg_state
+
+    def draw(self):
+        right_most = (self.screen_width * self.pg_state.config.ui.tab.width)
+        height = (self.screen_height * self.pg_state.config.ui.tab.row_height)
+        wavetable = self.state.screen._0
+
+        self.draw_tab_lable(wavetable, right_most, height)
+        self.draw_frame(wavetable, right_most, height)
+
+    def draw_tab_lable(self, wavetable, right_most: float, height: float):
+        middle_x = right_most * 0.5
+        middle_y = height * 0.5
+        color = self.pg_state.config.colors.text
+        cursor = self.state.display_cursor
+
+        display = self.pg_state.fonts[0].render(
+            f"Wave {wavetable.name:02X}", True, color)
+        textRect = display.get_rect()
+        textRect.center = (middle_x, middle_y)
+        self.pg_state.screen.blit(display, textRect)
+
+        source = basename(str(wavetable.file)) if wavetable.file is not None else "DRAWN"
+        frames = len(wavetable.frames)
+        display = self.pg_state.fonts[2].render(
+            f"FRAME {cursor.row + 1:02X}/{frames:02X}  {source}", True, color)
+        textRect = display.get_rect()
+        textRect.center = (middle_x, height * 1.5)
+        self.pg_state.screen.blit(display, textRect)
+
+    def draw_frame(self, wavetable, right_most: float, height: float):
+        """draws the frame under the cursor as bars from the center line"""
+        cursor = self.state.display_cursor
+        colors = self.pg_state.config.colors
+        points = wavetable.preview(cursor.row, N_PREVIEW_POINTS)
+
+        if not points:
+            return
+
+        top = height * 2.5
+        bottom = self.screen_height - height * 0.5
+        center_y = (top + bottom) * 0.5
+        half_height = (bottom - top) * 0.5
+        left = right_most * 0.05
+        bar_width = (right_most * 0.9) / len(points)
+
+        for i, point in enumerate(points):
+            middle_x = left + bar_width * (i + 0.5)
+            bar_height = max(abs(point) * half_height, 2.0)
+            middle_y = center_y - point * half_height * 0.5
+
+            if i == cursor.col and wavetable.file is None:
+                color = colors.cursor
+            else:
+                color = colors.text
+
+            self.pg_state.draw_rect(
+                (middle_x, middle_y), (max(bar_width - 2.0, 1.0), bar_height), color)
@@ -0,0 +1,224 @@
+use crate::params::{Choice, Param};
+use anyhow::Result;
+use bevy::prelude::Resource;
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// where the audio engine sends what it renders.
+#[pyclass(module = "tracker_backend", eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum AudioBackend {
+    /// an ALSA device. PipeWire and PulseAudio are reached through their ALSA plugins.
+    #[default]
+    Alsa,
+    /// throws the audio away, at the pace a sound card would take it.
+    Null,
+    /// writes the audio to `AudioConfig::wav_path`, at the pace a sound card would take it.
+    Wav,
+}
+
+impl Choice for AudioBackend {
+    const ALL: &'static [Self] = &[Self::Alsa, Self::Null, Self::Wav];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Alsa => "ALSA",
+            Self::Null => "NULL",
+            Self::Wav => "WAV",
+        }
+    }
+}
+
+/// the number of frames rendered at a time. smaller buffers cut latency but are more likely to
+/// run dry.
+#[pyclass(module = "tracker_backend", eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum BufferSize {
+    Frames64,
+    Frames128,
+    #[default]
+    Frames256,
+    Frames512,
+    Frames1024,
+    Frames2048,
+}
+
+impl Choice for BufferSize {
+    const ALL: &'static [Self] = &[
+        Self::Frames64,
+        Self::Frames128,
+        Self::Frames256,
+        Self::Frames512,
+        Self::Frames1024,
+        Self::Frames2048,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Frames64 => "64",
+            Self::Frames128 => "128",
+            Self::Frames256 => "256",
+            Self::Frames512 => "512",
+            Self::Frames1024 => "1024",
+            Self::Frames2048 => "2048",
+        }
+    }
+}
+
+impl BufferSize {
+    pub fn frames(&self) -> usize {
+        64 << (*self as usize)
+    }
+}
+
+#[pyclass(module = "tracker_backend", eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum SampleRate {
+    Hz44100,
+    #[default]
+    Hz48000,
+    Hz96000,
+}
+
+impl Choice for SampleRate {
+    const ALL: &'static [Self] = &[Self::Hz44100, Self::Hz48000, Self::Hz96000];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Hz44100 => "44.1K",
+            Self::Hz48000 => "48K",
+            Self::Hz96000 => "96K",
+        }
+    }
+}
+
+impl SampleRate {
+    pub fn hz(&self) -> u32 {
+        match self {
+            Self::Hz44100 => 44_100,
+            Self::Hz48000 => 48_000,
+            Self::Hz96000 => 96_000,
+        }
+    }
+}
+
+/// the name of an ALSA device, like `default` or `hw:1,0`.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioDevice {
+    pub name: String,
+}
+
+impl Default for AudioDevice {
+    fn default() -> Self {
+        Self {
+            name: "default".to_string(),
+        }
+    }
+}
+
+/// how audio is played. picked at startup from the config and changed from the settings screen.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize, Resource)]
+#[serde(default)]
+pub struct AudioConfig {
+    pub backend: AudioBackend,
+    /// the device played through by the ALSA backend.
+    pub device: AudioDevice,
+    pub buffer_size: BufferSize,
+    /// the rate asked for, the device may run at a different one.
+    pub sample_rate: SampleRate,
+    /// the file written by the WAV backend. it is overwritten each time the backend starts.
+    pub wav_path: PathBuf,
+    /// the file the song is exported to from the settings screen. overwritten by each export.
+    pub export_path: PathBuf,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            backend: AudioBackend::Alsa,
+            device: AudioDevice::default(),
+            buffer_size: BufferSize::default(),
+            sample_rate: SampleRate::default(),
+            wav_path: PathBuf::from("midi-tracker.wav"),
+            export_path: PathBuf::from("midi-tracker-export.wav"),
+        }
+    }
+}
+
+impl AudioConfig {
+    pub fn params(&mut self) -> Vec<Param<'_>> {
+        vec![
+            Param::choice("OUTPUT", &mut self.backend),
+            Param::choice("DEVICE", &mut self.device),
+            Param::choice("BUFFER", &mut self.buffer_size),
+            Param::choice("RATE", &mut self.sample_rate),
+        ]
+    }
+
+    pub fn n_params(&self) -> usize {
+        self.clone().params().len()
+    }
+
+    /// the file the config is kept in between runs.
+    pub fn path() -> PathBuf {
+        std::env::var_os("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_default()
+            .join(".config/midi-tracker/audio.ron")
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        super::load(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        super::save(self, path)
+    }
+}
+
+#[pymethods]
+impl AudioConfig {
+    /// the name and value of each parameter, for display on the settings screen.
+    fn rows(&self) -> Vec<(String, String)> {
+        self.clone().params().iter().map(Param::row).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn the_backend_is_loaded_as_it_was_saved() {
+        let config = AudioConfig {
+            backend: AudioBackend::Wav,
+            buffer_size: BufferSize::Frames1024,
+            sample_rate: SampleRate::Hz48000,
+            wav_path: PathBuf::from("/tmp/take-1.wav"),
+            ..AudioConfig::default()
+        };
+
+        let path =
+            std::env::temp_dir().join(format!("midi-tracker-audio-{}.ron", std::process::id()));
+        config.save(&path).unwrap();
+        let loaded = AudioConfig::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.unwrap(), config);
+
+        // settings the file doesn't have are left at their defaults.
+        let loaded: AudioConfig = ron::from_str("(backend: Null)").unwrap();
+        assert_eq!(
+            loaded,
+            AudioConfig {
+                backend: AudioBackend::Null,
+                ..AudioConfig::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use crate::{
+    midi::learn::{MappingScope, MidiMapping},
+    params::Param,
+};
+use anyhow::Result;
+use bevy::prelude::Resource;
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// how a MIDI port is reached.
+#[pyclass(module = "tracker_backend", eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum MidiPortKind {
+    /// no port, MIDI isn't sent.
+    #[default]
+    None,
+    /// a port of another ALSA sequencer client, like a USB interface or a soft synth.
+    Seq,
+    /// an ALSA raw MIDI device, like `hw:1,0,0`.
+    Raw,
+}
+
+/// a MIDI port. sequencer ports are named `client:port` by their names rather than their
+/// numbers, as the numbers change when devices are plugged in.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MidiPort {
+    pub kind: MidiPortKind,
+    pub name: String,
+}
+
+/// a port MIDI is read from.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MidiInPort {
+    pub port: MidiPort,
+}
+
+/// where MIDI is sent and read from. changed from the settings screen.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd, Serialize, Deserialize, Resource)]
+#[serde(default)]
+pub struct MidiConfig {
+    /// the port notes of `InstrumentOutput::UsbMidi` instruments are sent to.
+    pub output: MidiPort,
+    /// true to send MIDI clock, start, stop, and song position to the output port.
+    pub clock_out: bool,
+    /// the port notes are played from on the synth screen.
+    pub input: MidiInPort,
+    /// true to play in time with the MIDI clock, start, stop, and continue read from the input
+    /// port.
+    pub clock_in: bool,
+    /// where controllers are mapped to when learned, with the song or here.
+    pub learn_to: MappingScope,
+    /// the mappings kept for every song. saved with the rest of the config in `MidiConfig::path`.
+    pub mappings: Vec<MidiMapping>,
+}
+
+impl MidiConfig {
+    pub fn params(&mut self) -> Vec<Param<'_>> {
+        vec![
+            Param::choice("MIDI OUT", &mut self.output),
+            Param::choice("CLOCK OUT", &mut self.clock_out),
+            Param::choice("MIDI IN", &mut self.input),
+            Param::choice("CLOCK IN", &mut self.clock_in),
+            Param::choice("LEARN TO", &mut self.learn_to),
+        ]
+    }
+
+    pub fn n_params(&self) -> usize {
+        self.clone().params().len()
+    }
+
+    /// the file the config is kept in between runs.
+    pub fn path() -> PathBuf {
+        std::env::var_os("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_default()
+            .join(".config/midi-tracker/midi.ron")
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        super::load(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        super::save(self, path)
+    }
+}
+
+#[pymethods]
+impl MidiConfig {
+    /// the name and value of each parameter, for display on the settings screen.
+    fn rows(&self) -> Vec<(String, String)> {
+        self.clone().params().iter().map(Param::row).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{midi::learn::ParamTarget, params::ParamName};
+    use std::fs;
+
+    #[test]
+    fn global_mappings_are_loaded_as_they_were_saved() {
+        let config = MidiConfig {
+            output: MidiPort {
+                kind: MidiPortKind::Seq,
+                name: "USB MIDI:0".into(),
+            },
+            learn_to: MappingScope::Global,
+            mappings: vec![MidiMapping {
+                channel: 0,
+                control: 7,
+                target: ParamTarget::Mixer(4, ParamName::new("VOLUME").unwrap()),
+            }],
+            ..MidiConfig::default()
+        };
+
+        let path =
+            std::env::temp_dir().join(format!("midi-tracker-midi-{}.ron", std::process::id()));
+        config.save(&path).unwrap();
+        let loaded = MidiConfig::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.unwrap(), config);
+    }
+}
@@ -1 +1,47 @@
+pub mod audio;
+pub mod midi;
 pub mod ui;
+
+use anyhow::Result;
+use ron::ser::PrettyConfig;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// reads a value kept in a RON file.
+pub fn load<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    Ok(ron::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// writes `value` to a RON file, making the folder it goes in if it isn't there.
+pub fn save<T: Serialize>(value: &T, path: &Path) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(
+        path,
+        ron::ser::to_string_pretty(value, PrettyConfig::default())?,
+    )?;
+
+    Ok(())
+}
+
+/// moves a file that failed to load out of the way, so it isn't saved over, and returns where it
+/// went. `project.ron` goes to `project.ron.bak`, or `project.ron.1.bak` if that is taken, etc.
+pub fn move_aside(path: &Path) -> Result<PathBuf> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let moved = (0..)
+        .map(|i| match i {
+            0 => path.with_file_name(format!("{name}.bak")),
+            i => path.with_file_name(format!("{name}.{i}.bak")),
+        })
+        .find(|moved| !moved.exists())
+        .unwrap_or_default();
+
+    fs::rename(path, &moved)?;
+
+    Ok(moved)
+}
@@ -1,6 +1,9 @@
+use super::{audio::AudioConfig, midi::MidiConfig, move_aside};
+use anyhow::Result;
+use bevy::log::*;
 use pyo3::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub type Color = [u8; 3];
 pub type Bpm = u8;
@@ -11,6 +14,8 @@ pub struct TrackerConfig {
     pub font: FontConfig,
     pub colors: ColorsConfig,
     pub ui: UiConfig,
+    pub audio: AudioConfig,
+    pub midi: MidiConfig,
 }
 
 #[pyclass(module = "tracker_backend", get_all)]
@@ -80,5 +85,38 @@ pub fn get_config() -> TrackerConfig {
     config.ui.tab.row_elm_width = 1.0 / 5.0;
     config.ui.tab.row_height = 1.0 / 18.0;
 
+    if let Some(audio) = load_saved(&AudioConfig::path(), AudioConfig::load) {
+        config.audio = audio;
+    }
+
+    if let Some(midi) = load_saved(&MidiConfig::path(), MidiConfig::load) {
+        config.midi = midi;
+    }
+
     config
 }
+
+/// loads a config saved by an earlier run. `None` if there isn't one, or it failed to load, in
+/// which case it is moved aside so it isn't saved over.
+fn load_saved<T>(path: &Path, load: impl FnOnce(&Path) -> Result<T>) -> Option<T> {
+    if !path.exists() {
+        return None;
+    }
+
+    match load(path) {
+        Ok(config) => Some(config),
+        Err(e) => {
+            error!("failed to load the config from {}: {e}", path.display());
+
+            match move_aside(path) {
+                Ok(moved) => warn!(
+                    "moved the config that failed to load to {}",
+                    moved.display()
+                ),
+                Err(e) => error!("failed to move {} aside: {e}", path.display()),
+            }
+
+            None
+        }
+    }
+}
@@ -30,9 +30,12 @@ impl Plugin for ControlsPlugin {
 
 #[derive(Debug, Resource, Default)]
 pub struct LastViewed {
+    /// the song column the last chain was opened from.
+    pub channel: usize,
     pub chain: Index,
     pub phrase: Index,
     pub instrument: Index,
+    pub wavetable: Index,
 }
 
 #[derive(Debug, Resource, Default)]
@@ -100,10 +103,9 @@ fn update_state(
 ) -> Option<(Screen, ScreenState)> {
     // if let Some(n_s) = screen_will_be.0 {
     match (screen.clone(), n_s) {
-        // edit_song -> settings
-        (Screen::Song(), ScreenState::Settings) => {
-            // *screen = Screen::Settings();
-            Some((Screen::Settings(), ScreenState::Settings))
+        // edit_song -> mappings
+        (Screen::Song(), ScreenState::Mappings) => {
+            Some((Screen::Mappings(), ScreenState::Mappings))
         }
         // edit_song -> edit_chain
         (Screen::Song(), ScreenState::EditChain) => {
@@ -111,6 +113,8 @@ fn update_state(
 
             // warn!("{display_cursor:?} => {chain:?}");
 
+            last_viewed.channel = display_cursor.col;
+
             if let Some(chain_i) = chain {
                 // *screen = Screen::EditChain(chain);
                 Some((Screen::EditChain(chain_i), ScreenState::EditChain))
@@ -190,16 +194,21 @@ fn update_state(
                 None
             }
         }
-        // edit_instrument -> play_synth
-        (Screen::Instrument(inst_i), ScreenState::PlaySynth) => {
-            // *screen = Screen::PlaySynth();
+        // edit_instrument -> edit_wavetable
+        (Screen::Instrument(inst_i), ScreenState::EditWavetable) => {
             last_viewed.instrument = inst_i;
-            Some((Screen::PlaySynth(), ScreenState::PlaySynth))
+
+            let wavetable = instruments.0[inst_i]
+                .as_ref()
+                .map(|inst| inst.synth.wavetable as Index)
+                .unwrap_or(last_viewed.wavetable);
+
+            Some((Screen::Wavetable(wavetable), ScreenState::EditWavetable))
         }
-        // play_synth -> edit_instrument
-        (Screen::PlaySynth(), ScreenState::EditInsts) => {
-            // *screen = Screen::Instrument(last_viewed.instrument);
-            if instruments.0.get(last_viewed.instrument).is_some() {
+        // edit_wavetable -> edit_instrument
+        (Screen::Wavetable(wavetable_i), ScreenState::EditInsts) => {
+            if let Some(Some(_)) = instruments.0.get(last_viewed.instrument) {
+                last_viewed.wavetable = wavetable_i;
                 Some((
                     Screen::Instrument(last_viewed.instrument),
                     ScreenState::EditInsts,
@@ -208,18 +217,50 @@ fn update_state(
                 None
             }
         }
-        // play_synth -> settings
-        (Screen::PlaySynth(), ScreenState::Settings) => {
-            // *screen = Screen::Settings();
-            Some((Screen::Settings(), ScreenState::Settings))
+        // edit_wavetable -> play_synth
+        (Screen::Wavetable(wavetable_i), ScreenState::PlaySynth) => {
+            last_viewed.wavetable = wavetable_i;
+            Some((Screen::PlaySynth(), ScreenState::PlaySynth))
+        }
+        // play_synth -> edit_wavetable
+        (Screen::PlaySynth(), ScreenState::EditWavetable) => Some((
+            Screen::Wavetable(last_viewed.wavetable),
+            ScreenState::EditWavetable,
+        )),
+        // play_synth -> mixer
+        (Screen::PlaySynth(), ScreenState::EditMixer) => {
+            Some((Screen::Mixer(), ScreenState::EditMixer))
         }
-        // settings -> play_synth
-        (Screen::Settings(), ScreenState::PlaySynth) => {
-            // *screen = Screen::PlaySynth();
+        // mixer -> play_synth
+        (Screen::Mixer(), ScreenState::PlaySynth) => {
             Some((Screen::PlaySynth(), ScreenState::PlaySynth))
         }
-        // settings -> edit_song
-        (Screen::Settings(), ScreenState::EditSong) => {
+        // mixer -> effects
+        (Screen::Mixer(), ScreenState::EditEffects) => {
+            Some((Screen::Effects(), ScreenState::EditEffects))
+        }
+        // effects -> mixer
+        (Screen::Effects(), ScreenState::EditMixer) => {
+            Some((Screen::Mixer(), ScreenState::EditMixer))
+        }
+        // effects -> settings
+        (Screen::Effects(), ScreenState::Settings) => {
+            Some((Screen::Settings(), ScreenState::Settings))
+        }
+        // settings -> effects
+        (Screen::Settings(), ScreenState::EditEffects) => {
+            Some((Screen::Effects(), ScreenState::EditEffects))
+        }
+        // settings -> mappings
+        (Screen::Settings(), ScreenState::Mappings) => {
+            Some((Screen::Mappings(), ScreenState::Mappings))
+        }
+        // mappings -> settings
+        (Screen::Mappings(), ScreenState::Settings) => {
+            Some((Screen::Settings(), ScreenState::Settings))
+        }
+        // mappings -> edit_song
+        (Screen::Mappings(), ScreenState::EditSong) => {
             // *screen = Screen::Song();
             Some((Screen::Song(), ScreenState::EditSong))
         }
@@ -227,8 +268,12 @@ fn update_state(
         | (Screen::EditChain(_), ScreenState::EditChain)
         | (Screen::EditPhrase(_), ScreenState::EditPhrase)
         | (Screen::Instrument(_), ScreenState::EditInsts)
+        | (Screen::Wavetable(_), ScreenState::EditWavetable)
         | (Screen::PlaySynth(), ScreenState::PlaySynth)
-        | (Screen::Settings(), ScreenState::Settings) => None,
+        | (Screen::Mixer(), ScreenState::EditMixer)
+        | (Screen::Effects(), ScreenState::EditEffects)
+        | (Screen::Settings(), ScreenState::Settings)
+        | (Screen::Mappings(), ScreenState::Mappings) => None,
         (from, to) => {
             error!("transisioning from tab: {from:?} to tab: {to:?}, is illegal");
             None
@@ -292,8 +337,12 @@ fn screen_change(
         ScreenState::EditChain,
         ScreenState::EditPhrase,
         ScreenState::EditInsts,
+        ScreenState::EditWavetable,
         ScreenState::PlaySynth,
+        ScreenState::EditMixer,
+        ScreenState::EditEffects,
         ScreenState::Settings,
+        ScreenState::Mappings,
     ];
 
     let Some(screen_i) = screens.into_iter().position(|s| s == **state) else {
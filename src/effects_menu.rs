@@ -0,0 +1,200 @@
+use crate::{
+    controls::MyGamepad,
+    pygame_coms::{DisplayCursor, Song},
+    tracker_state::StateUpdated,
+    ExitMenuState, ScreenState,
+};
+use bevy::{log::*, prelude::*};
+
+/// the cursor of the screen the effects screen was entered from.
+#[derive(Debug, Clone, Default, Resource)]
+struct ReturnCursor(DisplayCursor);
+
+pub struct EffectsMenuPlugin;
+
+impl Plugin for EffectsMenuPlugin {
+    fn build(&self, app: &mut App) {
+        debug!("tracker_backend::effects_menu::EffectsMenuPlugin loaded");
+
+        app.init_resource::<ReturnCursor>()
+            .add_event::<EditParam>()
+            .add_systems(
+                Update,
+                movement
+                    .run_if(in_state(ScreenState::EditEffects))
+                    .run_if(not(in_state(ExitMenuState::Opened))),
+            )
+            .add_systems(
+                Update,
+                set_select
+                    .run_if(in_state(ScreenState::EditEffects))
+                    .run_if(not(in_state(ExitMenuState::Opened))),
+            )
+            .add_systems(
+                Update,
+                change_param
+                    .run_if(in_state(ScreenState::EditEffects))
+                    .run_if(not(in_state(ExitMenuState::Opened))),
+            )
+            .add_systems(
+                Update,
+                edit_param
+                    .run_if(in_state(ScreenState::EditEffects))
+                    .run_if(not(in_state(ExitMenuState::Opened))),
+            )
+            .add_systems(OnEnter(ScreenState::EditEffects), set_selected)
+            .add_systems(
+                OnEnter(ScreenState::EditEffects),
+                (save_cursor, set_cursor).chain(),
+            )
+            .add_systems(OnExit(ScreenState::EditEffects), restore_cursor);
+    }
+}
+
+#[derive(Event, Debug, Default)]
+struct EditParam {
+    /// how many steps to move the parameter under the cursor by.
+    delta: i32,
+}
+
+fn set_selected(mut display_cursor: ResMut<DisplayCursor>) {
+    display_cursor.selected = false;
+}
+
+fn save_cursor(display_cursor: Res<DisplayCursor>, mut return_cursor: ResMut<ReturnCursor>) {
+    return_cursor.0 = display_cursor.clone();
+}
+
+fn restore_cursor(mut display_cursor: ResMut<DisplayCursor>, return_cursor: Res<ReturnCursor>) {
+    display_cursor.row = return_cursor.0.row;
+    display_cursor.col = return_cursor.0.col;
+}
+
+fn set_cursor(mut display_cursor: ResMut<DisplayCursor>) {
+    display_cursor.row = 0;
+    display_cursor.col = 0;
+}
+
+fn set_select(
+    mut display_cursor: ResMut<DisplayCursor>,
+    mut state_updated: EventWriter<StateUpdated>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    my_gamepad: Option<Res<MyGamepad>>,
+) {
+    let Some(&MyGamepad(gamepad)) = my_gamepad.as_deref() else {
+        // no gamepad is connected
+        return;
+    };
+
+    let a_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::East,
+    };
+
+    if buttons.pressed(a_button) != display_cursor.selected {
+        display_cursor.selected = buttons.pressed(a_button);
+        state_updated.send_default();
+    }
+}
+
+fn change_param(
+    display_cursor: Res<DisplayCursor>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    my_gamepad: Option<Res<MyGamepad>>,
+    mut edit_param_event: EventWriter<EditParam>,
+) {
+    let Some(&MyGamepad(gamepad)) = my_gamepad.as_deref() else {
+        // no gamepad is connected
+        return;
+    };
+
+    let button = |button_type| GamepadButton {
+        gamepad,
+        button_type,
+    };
+
+    if !buttons.pressed(button(GamepadButtonType::East)) || !display_cursor.selected {
+        return;
+    }
+
+    for (button_type, delta) in [
+        (GamepadButtonType::DPadUp, 1),
+        (GamepadButtonType::DPadDown, -1),
+        (GamepadButtonType::DPadRight, 10),
+        (GamepadButtonType::DPadLeft, -10),
+    ] {
+        if buttons.just_released(button(button_type)) {
+            edit_param_event.send(EditParam { delta });
+        }
+    }
+}
+
+fn edit_param(
+    mut song: ResMut<Song>,
+    display_cursor: Res<DisplayCursor>,
+    mut events: EventReader<EditParam>,
+    mut state_updated: EventWriter<StateUpdated>,
+) {
+    for ev in events.read() {
+        if let Some(param) = song.effects_params().get_mut(display_cursor.row) {
+            param.shift(ev.delta);
+            state_updated.send_default();
+        } else {
+            error!(
+                "row {} is past the last effects parameter",
+                display_cursor.row
+            );
+        }
+    }
+}
+
+fn movement(
+    buttons: Res<ButtonInput<GamepadButton>>,
+    my_gamepad: Option<Res<MyGamepad>>,
+    mut display_cursor: ResMut<DisplayCursor>,
+    mut state_updated: EventWriter<StateUpdated>,
+    gamepads: Res<Gamepads>,
+    song: Res<Song>,
+) {
+    if display_cursor.selected {
+        return;
+    }
+
+    let Some(&MyGamepad(gamepad)) = my_gamepad.as_deref() else {
+        // no gamepad is connected
+        return;
+    };
+
+    let button = |button_type| GamepadButton {
+        gamepad,
+        button_type,
+    };
+
+    let start_button = if let Some(name) = gamepads.name(gamepad)
+        && name.starts_with("PS5")
+    {
+        button(GamepadButtonType::Start)
+    } else {
+        button(GamepadButtonType::Select)
+    };
+
+    if buttons.pressed(start_button) {
+        return;
+    }
+
+    let n_rows = song.n_effects_params();
+    let mut row = display_cursor.row;
+
+    if buttons.just_released(button(GamepadButtonType::DPadUp)) {
+        row = (row + n_rows - 1) % n_rows;
+    }
+
+    if buttons.just_released(button(GamepadButtonType::DPadDown)) {
+        row = (row + 1) % n_rows;
+    }
+
+    if row != display_cursor.row {
+        display_cursor.row = row;
+        state_updated.send_default();
+    }
+}
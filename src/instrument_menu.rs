@@ -0,0 +1,260 @@
+use crate::{
+    controls::MyGamepad,
+    pygame_coms::{DisplayCursor, Index, Screen},
+    tracker_state::{AllInstruments, StateUpdated},
+    ExitMenuState, ScreenState,
+};
+use bevy::{log::*, prelude::*};
+
+#[derive(Debug, Clone, Default, Resource)]
+struct InstrumentIndex(Index);
+
+/// the cursor of the screen the instrument screen was entered from.
+#[derive(Debug, Clone, Default, Resource)]
+struct ReturnCursor(DisplayCursor);
+
+pub struct InstrumentMenuPlugin;
+
+impl Plugin for InstrumentMenuPlugin {
+    fn build(&self, app: &mut App) {
+        debug!("tracker_backend::controls::Instrument Menu Plugin loaded");
+
+        app.init_resource::<InstrumentIndex>()
+            .init_resource::<ReturnCursor>()
+            .add_event::<EditParam>()
+            .add_systems(
+                Update,
+                movement
+                    .run_if(in_state(ScreenState::EditInsts))
+                    .run_if(not(in_state(ExitMenuState::Opened))),
+            )
+            .add_systems(
+                Update,
+                set_select
+                    .run_if(in_state(ScreenState::EditInsts))
+                    .run_if(not(in_state(ExitMenuState::Opened))),
+            )
+            .add_systems(
+                Update,
+                change_param
+                    .run_if(in_state(ScreenState::EditInsts))
+                    .run_if(not(in_state(ExitMenuState::Opened))),
+            )
+            .add_systems(
+                Update,
+                edit_param
+                    .run_if(in_state(ScreenState::EditInsts))
+                    .run_if(not(in_state(ExitMenuState::Opened))),
+            )
+            .add_systems(OnEnter(ScreenState::EditInsts), set_instrument_index)
+            .add_systems(OnEnter(ScreenState::EditInsts), set_selected)
+            .add_systems(
+                OnEnter(ScreenState::EditInsts),
+                (save_cursor, set_cursor).chain(),
+            )
+            .add_systems(OnExit(ScreenState::EditInsts), restore_cursor);
+    }
+}
+
+#[derive(Event, Debug, Default)]
+struct EditParam {
+    /// how many steps to move the parameter under the cursor by.
+    delta: i32,
+}
+
+fn set_instrument_index(mut instrument_index: ResMut<InstrumentIndex>, screen: Res<Screen>) {
+    if let Screen::Instrument(instrument_i) = *screen {
+        instrument_index.0 = instrument_i;
+    }
+}
+
+fn set_selected(mut display_cursor: ResMut<DisplayCursor>) {
+    display_cursor.selected = false;
+}
+
+fn save_cursor(display_cursor: Res<DisplayCursor>, mut return_cursor: ResMut<ReturnCursor>) {
+    return_cursor.0 = display_cursor.clone();
+}
+
+fn restore_cursor(mut display_cursor: ResMut<DisplayCursor>, return_cursor: Res<ReturnCursor>) {
+    display_cursor.row = return_cursor.0.row;
+    display_cursor.col = return_cursor.0.col;
+}
+
+fn set_cursor(mut display_cursor: ResMut<DisplayCursor>) {
+    display_cursor.row = 0;
+    display_cursor.col = 0;
+}
+
+fn set_select(
+    mut display_cursor: ResMut<DisplayCursor>,
+    mut state_updated: EventWriter<StateUpdated>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    my_gamepad: Option<Res<MyGamepad>>,
+) {
+    let Some(&MyGamepad(gamepad)) = my_gamepad.as_deref() else {
+        // no gamepad is connected
+        return;
+    };
+
+    let a_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::East,
+    };
+
+    if (!buttons.pressed(a_button) && display_cursor.selected)
+        || (buttons.pressed(a_button) && !display_cursor.selected)
+    {
+        state_updated.send_default();
+        display_cursor.selected = !(!buttons.pressed(a_button) && display_cursor.selected)
+            || (buttons.pressed(a_button) && !display_cursor.selected);
+    }
+}
+
+fn change_param(
+    display_cursor: Res<DisplayCursor>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    my_gamepad: Option<Res<MyGamepad>>,
+    mut edit_param_event: EventWriter<EditParam>,
+) {
+    let Some(&MyGamepad(gamepad)) = my_gamepad.as_deref() else {
+        // no gamepad is connected
+        return;
+    };
+
+    let up_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::DPadUp,
+    };
+    let down_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::DPadDown,
+    };
+    let left_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::DPadLeft,
+    };
+    let right_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::DPadRight,
+    };
+    let a_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::East,
+    };
+
+    if !buttons.pressed(a_button) || !display_cursor.selected {
+        return;
+    }
+
+    if buttons.just_released(up_button) {
+        edit_param_event.send(EditParam { delta: 1 });
+    }
+
+    if buttons.just_released(down_button) {
+        edit_param_event.send(EditParam { delta: -1 });
+    }
+
+    if buttons.just_released(right_button) {
+        edit_param_event.send(EditParam { delta: 10 });
+    }
+
+    if buttons.just_released(left_button) {
+        edit_param_event.send(EditParam { delta: -10 });
+    }
+}
+
+fn edit_param(
+    mut instruments: ResMut<AllInstruments>,
+    display_cursor: Res<DisplayCursor>,
+    mut events: EventReader<EditParam>,
+    mut state_updated: EventWriter<StateUpdated>,
+    instrument_index: Res<InstrumentIndex>,
+) {
+    let instrument_i = instrument_index.0;
+
+    for ev in events.read() {
+        if let Some(Some(instrument)) = instruments.0.get_mut(instrument_i) {
+            if let Some(param) = instrument.params().get_mut(display_cursor.row) {
+                param.shift(ev.delta);
+                state_updated.send_default();
+            } else {
+                error!(
+                    "row {} is past the last parameter of instrument {instrument_i}",
+                    display_cursor.row
+                );
+            }
+        } else {
+            error!("attempting to edit instrument {instrument_i}, which does not exist.");
+        }
+    }
+}
+
+fn movement(
+    buttons: Res<ButtonInput<GamepadButton>>,
+    my_gamepad: Option<Res<MyGamepad>>,
+    mut display_cursor: ResMut<DisplayCursor>,
+    mut state_updated: EventWriter<StateUpdated>,
+    gamepads: Res<Gamepads>,
+    instruments: Res<AllInstruments>,
+    instrument_index: Res<InstrumentIndex>,
+) {
+    if display_cursor.selected {
+        return;
+    }
+
+    let Some(&MyGamepad(gamepad)) = my_gamepad.as_deref() else {
+        // no gamepad is connected
+        return;
+    };
+
+    let up_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::DPadUp,
+    };
+    let down_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::DPadDown,
+    };
+
+    let start_button = if let Some(name) = gamepads.name(gamepad)
+        && name.starts_with("PS5")
+    {
+        GamepadButton {
+            gamepad,
+            button_type: GamepadButtonType::Start,
+        }
+    } else {
+        GamepadButton {
+            gamepad,
+            button_type: GamepadButtonType::Select,
+        }
+    };
+
+    let Some(Some(instrument)) = instruments.0.get(instrument_index.0) else {
+        return;
+    };
+    let n_rows = instrument.n_params();
+
+    if buttons.just_released(up_button) && !buttons.pressed(start_button) {
+        let new_row = if display_cursor.row == 0 {
+            n_rows - 1
+        } else {
+            display_cursor.row - 1
+        };
+
+        display_cursor.row = new_row;
+        state_updated.send_default();
+    }
+
+    if buttons.just_released(down_button) && !buttons.pressed(start_button) {
+        let new_row = if display_cursor.row + 1 >= n_rows {
+            0
+        } else {
+            display_cursor.row + 1
+        };
+
+        display_cursor.row = new_row;
+        state_updated.send_default();
+    }
+}
@@ -1,4 +1,4 @@
-use crate::pygame_coms::{InputCMD, State};
+use crate::pygame_coms::{InputCMD, State, Telemetry};
 use anyhow::Result;
 use bevy::{log::*, prelude::Resource};
 use crossbeam::channel::{unbounded, Receiver, Sender};
@@ -9,12 +9,14 @@ use pyo3::prelude::*;
 pub struct TrackerIPC {
     pub rx: Receiver<State>,
     pub tx: Sender<InputCMD>,
+    pub telemetry_rx: Receiver<Telemetry>,
 }
 
 #[derive(Debug, Clone, Resource)]
 pub struct RustIPC {
     pub rx: Receiver<InputCMD>,
     pub tx: Sender<State>,
+    pub telemetry_tx: Sender<Telemetry>,
 }
 
 // #[derive(Debug, Clone)]
@@ -46,6 +48,11 @@ impl TrackerIPC {
         v
     }
 
+    /// the newest telemetry, any older ones waiting are dropped.
+    fn recv_telemetry(&self) -> Option<Telemetry> {
+        self.telemetry_rx.try_iter().last()
+    }
+
     fn send(&self, cmd: InputCMD) {
         match self.tx.send(cmd) {
             Ok(_) => {}
@@ -66,6 +73,15 @@ impl RustIPC {
     pub fn send_msg(&self, state: State) -> Result<()> {
         Ok(self.tx.send(state)?)
     }
+
+    /// sends the telemetry, unless the frontend has yet to pick up the last one.
+    pub fn send_telemetry(&self, telemetry: Telemetry) -> Result<()> {
+        if self.telemetry_tx.is_empty() {
+            self.telemetry_tx.send(telemetry)?;
+        }
+
+        Ok(())
+    }
 }
 
 // impl<RX, TX> RTX<RX, TX> {
@@ -84,6 +100,18 @@ impl RustIPC {
 pub fn gen_ipc() -> (RustIPC, TrackerIPC) {
     let (tx, rx) = unbounded();
     let (tx_2, rx_2) = unbounded();
+    let (telemetry_tx, telemetry_rx) = unbounded();
 
-    (RustIPC { rx, tx: tx_2 }, TrackerIPC { rx: rx_2, tx })
+    (
+        RustIPC {
+            rx,
+            tx: tx_2,
+            telemetry_tx,
+        },
+        TrackerIPC {
+            rx: rx_2,
+            tx,
+            telemetry_rx,
+        },
+    )
 }
@@ -1,19 +1,56 @@
 #![feature(let_chains)]
+#![cfg_attr(test, feature(test))]
 use crate::config::ui::{get_config, TrackerConfig};
 use bevy::{a11y::AccessibilityPlugin, log::LogPlugin, prelude::*};
 use chain_menu::ChainMenuPlugin;
-use config::ui::{ColorsConfig, FontConfig, MenuUiConf, TabUiConf, UiConfig};
+use config::{
+    audio::{AudioBackend, AudioConfig, AudioDevice, BufferSize, SampleRate},
+    midi::{MidiConfig, MidiInPort, MidiPort, MidiPortKind},
+    ui::{ColorsConfig, FontConfig, MenuUiConf, TabUiConf, UiConfig},
+};
 use controls::ControlsPlugin;
+use effects_menu::EffectsMenuPlugin;
+use instrument_menu::InstrumentMenuPlugin;
 use ipc::{gen_ipc, RustIPC, TrackerIPC};
+use mappings_menu::MappingsMenuPlugin;
+use midi::{
+    learn::{MappingScope, MidiMapping, ParamTarget},
+    MidiParams, MidiPlugin,
+};
+use mixer_menu::MixerMenuPlugin;
 use phrase_menu::PhraseMenuPlugin;
+use project::ProjectPlugin;
 use pygame_coms::{
-    Button, Chain, ChainRow, InputCMD, Instrument, Phrase, PhraseRow, PlaybackCursor, Screen,
-    ScreenData, Song, SongRow, State, TrackerCommand,
+    Button, Chain, ChainRow, ChordShape, InputCMD, Instrument, InstrumentOutput, Phrase, PhraseRow,
+    PlaybackCursor, Screen, ScreenData, Song, SongRow, State, Telemetry, TrackerCommand,
 };
 use pyo3::prelude::*;
+use sequencer::SequencerPlugin;
+use settings_menu::SettingsMenuPlugin;
 use song_menu::SongMenuPlugin;
 use std::thread::spawn;
+use synth::{
+    drums::{DrumKit, DrumParams, DrumVoice},
+    dynamics::{Dynamics, SidechainTarget},
+    effects::{DelaySync, Effects},
+    envelope::{Adsr, ModEnvTarget, ModEnvelope},
+    filter::{FilterMode, FilterParams},
+    fm::{FmAlgorithm, FmOperator, FmParams},
+    gameboy::{GbChannel, GbDuty, GbParams, GbWaveVolume},
+    inserts::{InsertSlot, InsertType, Inserts},
+    lfo::{LfoParams, LfoShape},
+    mixer::{Level, Mixer, MixerChannel, MixerLevels},
+    mod_matrix::{ModDest, ModMatrix, ModSlot, ModSource},
+    osc::OscType,
+    sampler::{SamplerMode, SamplerParams},
+    scope::Scope,
+    voices::{Polyphony, StealPolicy, VoiceMode},
+    wavetable::Wavetable,
+    SynthParams, SynthPlugin,
+};
+use synth_menu::SynthMenuPlugin;
 use tracker_state::TrackerStatePlugin;
+use wavetable_menu::WavetableMenuPlugin;
 
 #[derive(States, Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ScreenState {
@@ -22,8 +59,12 @@ pub enum ScreenState {
     EditChain,
     EditPhrase,
     EditInsts,
+    EditWavetable,
     PlaySynth,
+    EditMixer,
+    EditEffects,
     Settings,
+    Mappings,
 }
 
 #[derive(States, Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -43,11 +84,23 @@ pub enum ExitMenuState {
 pub mod chain_menu;
 pub mod config;
 pub mod controls;
+pub mod effects_menu;
+pub mod instrument_menu;
 pub mod ipc;
+pub mod mappings_menu;
+pub mod midi;
+pub mod mixer_menu;
+pub mod params;
 pub mod phrase_menu;
+pub mod project;
 pub mod pygame_coms;
+pub mod sequencer;
+pub mod settings_menu;
 pub mod song_menu;
+pub mod synth;
+pub mod synth_menu;
 pub mod tracker_state;
+pub mod wavetable_menu;
 
 fn build_runner(io: RustIPC) -> impl FnMut(App) -> AppExit {
     let runner = move |mut app: App| -> AppExit {
@@ -63,6 +116,10 @@ fn build_runner(io: RustIPC) -> impl FnMut(App) -> AppExit {
 
             if py_msg == Some(InputCMD::Exit()) {
                 info!("exiting from runner loop becuase of PyGame Exit.");
+                // one last update, so whatever runs on the way out sees the exit.
+                app.world_mut().send_event(AppExit::Success);
+                app.update();
+
                 return AppExit::Success;
             }
 
@@ -101,9 +158,20 @@ fn start(io: RustIPC) {
         .add_plugins(ControlsPlugin)
         // .add_plugins(base_display::BaseDisplayPlugin)
         .add_plugins(TrackerStatePlugin)
+        .add_plugins(ProjectPlugin)
         .add_plugins(SongMenuPlugin)
         .add_plugins(ChainMenuPlugin)
         .add_plugins(PhraseMenuPlugin)
+        .add_plugins(InstrumentMenuPlugin)
+        .add_plugins(WavetableMenuPlugin)
+        .add_plugins(SynthMenuPlugin)
+        .add_plugins(MixerMenuPlugin)
+        .add_plugins(EffectsMenuPlugin)
+        .add_plugins(SettingsMenuPlugin)
+        .add_plugins(MappingsMenuPlugin)
+        .add_plugins(SequencerPlugin)
+        .add_plugins(SynthPlugin)
+        .add_plugins(MidiPlugin)
         // .insert_state(ScreenData::Song)
         .init_state::<ScreenState>()
         .init_state::<PlayingState>()
@@ -130,9 +198,53 @@ fn tracker_backend(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(get_config, m)?)?;
 
     m.add_class::<TrackerCommand>()?;
+    m.add_class::<ChordShape>()?;
     m.add_class::<Button>()?;
     m.add_class::<InputCMD>()?;
     m.add_class::<Instrument>()?;
+    m.add_class::<InstrumentOutput>()?;
+    m.add_class::<SynthParams>()?;
+    m.add_class::<OscType>()?;
+    m.add_class::<Adsr>()?;
+    m.add_class::<ModEnvelope>()?;
+    m.add_class::<ModEnvTarget>()?;
+    m.add_class::<FilterParams>()?;
+    m.add_class::<FilterMode>()?;
+    m.add_class::<LfoParams>()?;
+    m.add_class::<LfoShape>()?;
+    m.add_class::<ModMatrix>()?;
+    m.add_class::<ModSlot>()?;
+    m.add_class::<ModSource>()?;
+    m.add_class::<ModDest>()?;
+    m.add_class::<Wavetable>()?;
+    m.add_class::<DrumKit>()?;
+    m.add_class::<DrumParams>()?;
+    m.add_class::<DrumVoice>()?;
+    m.add_class::<SamplerParams>()?;
+    m.add_class::<SamplerMode>()?;
+    m.add_class::<FmParams>()?;
+    m.add_class::<FmOperator>()?;
+    m.add_class::<FmAlgorithm>()?;
+    m.add_class::<GbParams>()?;
+    m.add_class::<GbChannel>()?;
+    m.add_class::<GbDuty>()?;
+    m.add_class::<GbWaveVolume>()?;
+    m.add_class::<Inserts>()?;
+    m.add_class::<InsertSlot>()?;
+    m.add_class::<InsertType>()?;
+    m.add_class::<Polyphony>()?;
+    m.add_class::<VoiceMode>()?;
+    m.add_class::<StealPolicy>()?;
+    m.add_class::<MidiParams>()?;
+    m.add_class::<Mixer>()?;
+    m.add_class::<MixerChannel>()?;
+    m.add_class::<MixerLevels>()?;
+    m.add_class::<Level>()?;
+    m.add_class::<Effects>()?;
+    m.add_class::<DelaySync>()?;
+    m.add_class::<Dynamics>()?;
+    m.add_class::<SidechainTarget>()?;
+    m.add_class::<Scope>()?;
     m.add_class::<PhraseRow>()?;
     m.add_class::<Phrase>()?;
     m.add_class::<ChainRow>()?;
@@ -142,6 +254,7 @@ fn tracker_backend(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<Screen>()?;
     m.add_class::<PlaybackCursor>()?;
     m.add_class::<State>()?;
+    m.add_class::<Telemetry>()?;
     m.add_class::<ScreenData>()?;
     m.add_class::<TrackerConfig>()?;
     m.add_class::<TrackerCommand>()?;
@@ -150,6 +263,18 @@ fn tracker_backend(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<UiConfig>()?;
     m.add_class::<MenuUiConf>()?;
     m.add_class::<TabUiConf>()?;
+    m.add_class::<AudioConfig>()?;
+    m.add_class::<AudioBackend>()?;
+    m.add_class::<AudioDevice>()?;
+    m.add_class::<BufferSize>()?;
+    m.add_class::<SampleRate>()?;
+    m.add_class::<MidiConfig>()?;
+    m.add_class::<MidiPort>()?;
+    m.add_class::<MidiInPort>()?;
+    m.add_class::<MidiPortKind>()?;
+    m.add_class::<MidiMapping>()?;
+    m.add_class::<ParamTarget>()?;
+    m.add_class::<MappingScope>()?;
     // m.add_class::<>()?;
     // m.add_class::<>()?;
     // m.add_class::<>()?;
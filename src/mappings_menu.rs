@@ -0,0 +1,155 @@
+use crate::{
+    config::midi::MidiConfig,
+    controls::MyGamepad,
+    midi::learn::{forget, mapped},
+    pygame_coms::{DisplayCursor, Song},
+    tracker_state::StateUpdated,
+    ExitMenuState, ScreenState,
+};
+use bevy::{log::*, prelude::*};
+
+/// the cursor of the screen the mappings screen was entered from.
+#[derive(Debug, Clone, Default, Resource)]
+struct ReturnCursor(DisplayCursor);
+
+pub struct MappingsMenuPlugin;
+
+impl Plugin for MappingsMenuPlugin {
+    fn build(&self, app: &mut App) {
+        debug!("tracker_backend::mappings_menu::MappingsMenuPlugin loaded");
+
+        app.init_resource::<ReturnCursor>()
+            .add_systems(
+                Update,
+                movement
+                    .run_if(in_state(ScreenState::Mappings))
+                    .run_if(not(in_state(ExitMenuState::Opened))),
+            )
+            .add_systems(
+                Update,
+                rm.run_if(in_state(ScreenState::Mappings))
+                    .run_if(not(in_state(ExitMenuState::Opened))),
+            )
+            .add_systems(OnEnter(ScreenState::Mappings), set_selected)
+            .add_systems(
+                OnEnter(ScreenState::Mappings),
+                (save_cursor, set_cursor).chain(),
+            )
+            .add_systems(OnExit(ScreenState::Mappings), restore_cursor);
+    }
+}
+
+fn set_selected(mut display_cursor: ResMut<DisplayCursor>) {
+    display_cursor.selected = false;
+}
+
+fn save_cursor(display_cursor: Res<DisplayCursor>, mut return_cursor: ResMut<ReturnCursor>) {
+    return_cursor.0 = display_cursor.clone();
+}
+
+fn restore_cursor(mut display_cursor: ResMut<DisplayCursor>, return_cursor: Res<ReturnCursor>) {
+    display_cursor.row = return_cursor.0.row;
+    display_cursor.col = return_cursor.0.col;
+}
+
+fn set_cursor(mut display_cursor: ResMut<DisplayCursor>) {
+    display_cursor.row = 0;
+    display_cursor.col = 0;
+}
+
+/// deletes the mapping under the cursor when A and B are pressed together.
+fn rm(
+    buttons: Res<ButtonInput<GamepadButton>>,
+    my_gamepad: Option<Res<MyGamepad>>,
+    mut display_cursor: ResMut<DisplayCursor>,
+    mut state_updated: EventWriter<StateUpdated>,
+    mut song: ResMut<Song>,
+    mut config: ResMut<MidiConfig>,
+) {
+    let Some(&MyGamepad(gamepad)) = my_gamepad.as_deref() else {
+        // no gamepad is connected
+        return;
+    };
+
+    let b_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::South,
+    };
+    let a_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::East,
+    };
+
+    if !((buttons.just_released(a_button) && buttons.pressed(b_button))
+        || (buttons.just_released(b_button) && buttons.pressed(a_button))
+        || (buttons.just_released(a_button) && buttons.just_released(b_button))
+        || (buttons.just_pressed(a_button) && buttons.just_pressed(b_button)))
+    {
+        return;
+    }
+
+    let n_rows = mapped(&song.mappings, &config.mappings).count();
+
+    if display_cursor.row >= n_rows {
+        return;
+    }
+
+    forget(display_cursor.row, &mut song.mappings, &mut config.mappings);
+
+    // the cursor stays on the last row when it is deleted.
+    display_cursor.row = display_cursor.row.min(n_rows.saturating_sub(2));
+    state_updated.send_default();
+}
+
+fn movement(
+    buttons: Res<ButtonInput<GamepadButton>>,
+    my_gamepad: Option<Res<MyGamepad>>,
+    mut display_cursor: ResMut<DisplayCursor>,
+    mut state_updated: EventWriter<StateUpdated>,
+    gamepads: Res<Gamepads>,
+    song: Res<Song>,
+    config: Res<MidiConfig>,
+) {
+    let Some(&MyGamepad(gamepad)) = my_gamepad.as_deref() else {
+        // no gamepad is connected
+        return;
+    };
+
+    let button = |button_type| GamepadButton {
+        gamepad,
+        button_type,
+    };
+
+    let start_button = if let Some(name) = gamepads.name(gamepad)
+        && name.starts_with("PS5")
+    {
+        button(GamepadButtonType::Start)
+    } else {
+        button(GamepadButtonType::Select)
+    };
+
+    if buttons.pressed(start_button) {
+        return;
+    }
+
+    let n_rows = mapped(&song.mappings, &config.mappings).count();
+
+    if n_rows == 0 {
+        return;
+    }
+
+    let mut row = display_cursor.row.min(n_rows - 1);
+
+    if buttons.just_released(button(GamepadButtonType::DPadUp)) {
+        row = (row + n_rows - 1) % n_rows;
+    }
+
+    if buttons.just_released(button(GamepadButtonType::DPadDown)) {
+        row = (row + 1) % n_rows;
+    }
+
+    if row != display_cursor.row {
+        display_cursor.row = row;
+        state_updated.send_default();
+    }
+}
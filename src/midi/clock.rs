@@ -0,0 +1,325 @@
+use super::MidiMessage;
+use crate::{
+    config::ui::Bpm,
+    sequencer::{ClockEvent, ROWS_PER_BEAT},
+    synth::AudioClock,
+};
+use std::{collections::VecDeque, time::Instant};
+
+/// MIDI clock pulses per quarter note.
+pub const PPQN: u32 = 24;
+/// MIDI clock pulses per phrase row.
+const PULSES_PER_ROW: u32 = PPQN / ROWS_PER_BEAT as u32;
+/// how much of the difference between when a pulse came and when it was expected moves the beat.
+const PHASE_SMOOTHING: f64 = 0.1;
+/// the clock is lost after this many pulses fail to arrive, a beat.
+const LOST_AFTER: f64 = PPQN as f64;
+/// the furthest the smoothed clock runs on past the frames the audio thread has rendered, the
+/// largest buffer it renders at once.
+const MAX_AHEAD: f64 = 2048.0;
+
+/// the frames between MIDI clock pulses at `tempo`.
+pub fn pulse_len(tempo: Bpm, sample_rate: f64) -> f64 {
+    60.0 / (tempo.max(1) as f64 * PPQN as f64) * sample_rate
+}
+
+/// when the MIDI clock pulses sent while the song plays are due.
+#[derive(Debug, Clone, Default)]
+pub struct ClockOut {
+    pub tempo: Bpm,
+    /// the frame the next pulse is due on, `None` while stopped.
+    next: Option<f64>,
+}
+
+impl ClockOut {
+    pub fn new(tempo: Bpm) -> Self {
+        Self { tempo, next: None }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.next.is_some()
+    }
+
+    /// starts pulsing, the first pulse lands on frame `at`.
+    pub fn start(&mut self, at: f64) {
+        self.next = Some(at);
+    }
+
+    pub fn stop(&mut self) {
+        self.next = None;
+    }
+
+    /// the frame the next pulse is due on.
+    pub fn next_pulse(&self) -> Option<f64> {
+        self.next
+    }
+
+    /// the number of pulses that have come due by frame `now`.
+    pub fn pulses(&mut self, now: f64, sample_rate: f64) -> usize {
+        let len = pulse_len(self.tempo, sample_rate);
+        let mut pulses = 0;
+
+        while let Some(next) = self.next.as_mut()
+            && *next <= now
+        {
+            *next += len;
+            pulses += 1;
+        }
+
+        pulses
+    }
+}
+
+/// follows the MIDI clock of another device, turning its pulses into rows. the tempo is averaged
+/// over a beat and the beat is smoothed, so jitter in when pulses arrive doesn't make it into the
+/// rows.
+#[derive(Debug, Clone)]
+pub struct ClockIn {
+    /// the estimated frames between pulses.
+    period: f64,
+    /// the frame the last message came in on, `None` till the clock is found, or once it is lost.
+    last: Option<f64>,
+    /// the frames the pulses of the last beat came in on.
+    arrivals: VecDeque<f64>,
+    /// the smoothed frame of the last pulse.
+    phase: f64,
+    /// pulses since the last start or continue.
+    pulse: u32,
+}
+
+impl ClockIn {
+    /// a clock expected to run at `tempo`, till its pulses show otherwise.
+    pub fn new(tempo: Bpm, sample_rate: f64) -> Self {
+        Self {
+            period: pulse_len(tempo, sample_rate),
+            last: None,
+            arrivals: VecDeque::with_capacity(PPQN as usize + 1),
+            phase: 0.0,
+            pulse: 0,
+        }
+    }
+
+    /// true while the clock is coming in. a start counts, as some devices only send pulses while
+    /// they play.
+    pub fn is_running(&self) -> bool {
+        self.last.is_some()
+    }
+
+    /// the estimated tempo, `None` while the clock isn't coming in.
+    pub fn tempo(&self, sample_rate: f64) -> Option<f32> {
+        self.is_running()
+            .then(|| (60.0 * sample_rate / (self.period * PPQN as f64)) as f32)
+    }
+
+    /// takes in a message that came in on frame `at`, returning what it means for playback.
+    pub fn receive(&mut self, at: f64, message: MidiMessage) -> Option<ClockEvent> {
+        if matches!(message, MidiMessage::Start | MidiMessage::Continue) {
+            self.last = Some(at);
+        }
+
+        match message {
+            MidiMessage::Start => {
+                self.pulse = 0;
+                Some(ClockEvent::Start)
+            }
+            MidiMessage::Continue => {
+                self.pulse = 0;
+                Some(ClockEvent::Continue)
+            }
+            MidiMessage::Stop => Some(ClockEvent::Stop),
+            MidiMessage::Clock => self.pulse(at),
+            _ => None,
+        }
+    }
+
+    /// a row starts every `PULSES_PER_ROW` pulses from a start.
+    fn pulse(&mut self, at: f64) -> Option<ClockEvent> {
+        if !self.arrivals.is_empty() {
+            let expected = self.phase + self.period;
+            self.phase = expected + (at - expected) * PHASE_SMOOTHING;
+        } else {
+            self.phase = at;
+        }
+
+        if self.arrivals.len() > PPQN as usize {
+            self.arrivals.pop_front();
+        }
+
+        self.arrivals.push_back(at);
+        self.last = Some(at);
+
+        if let (Some(first), Some(last)) = (self.arrivals.front(), self.arrivals.back())
+            && last > first
+        {
+            self.period = (last - first) / (self.arrivals.len() - 1) as f64;
+        }
+
+        let row = self.pulse.is_multiple_of(PULSES_PER_ROW);
+        self.pulse = self.pulse.wrapping_add(1);
+
+        row.then_some(ClockEvent::Row {
+            at: self.phase,
+            len: self.period * PULSES_PER_ROW as f64,
+        })
+    }
+
+    /// true, once, when no pulse has come in for a beat by frame `now`.
+    pub fn lost(&mut self, now: f64) -> bool {
+        let lost = self
+            .last
+            .is_some_and(|last| now - last > self.period * LOST_AFTER);
+
+        if lost {
+            self.last = None;
+            self.arrivals.clear();
+        }
+
+        lost
+    }
+}
+
+/// the audio clock, moved on smoothly between the buffers the audio thread renders. the audio
+/// thread counts a whole buffer at a time, which would bunch clock pulses up at its start.
+pub struct SmoothClock {
+    clock: AudioClock,
+    /// the frames rendered when they were last seen to change, and when that was.
+    frames: u64,
+    since: Instant,
+    /// the last time given, so time never goes backwards.
+    last: f64,
+}
+
+impl SmoothClock {
+    pub fn new(clock: AudioClock) -> Self {
+        Self {
+            frames: clock.now(),
+            clock,
+            since: Instant::now(),
+            last: 0.0,
+        }
+    }
+
+    pub fn sample_rate(&self) -> f64 {
+        self.clock.sample_rate() as f64
+    }
+
+    /// the frame the audio thread has reached, fractional between buffers.
+    pub fn now(&mut self) -> f64 {
+        let frames = self.clock.now();
+
+        if frames != self.frames {
+            self.frames = frames;
+            self.since = Instant::now();
+        }
+
+        let ahead = (self.since.elapsed().as_secs_f64() * self.sample_rate()).min(MAX_AHEAD);
+        self.last = self.last.max(frames as f64 + ahead);
+
+        self.last
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pulses_are_evenly_spaced() {
+        // 960 frames a pulse.
+        let mut clock = ClockOut::new(125);
+        assert_eq!(clock.pulses(10_000.0, 48_000.0), 0);
+
+        clock.start(1000.0);
+        assert_eq!(clock.pulses(999.0, 48_000.0), 0);
+        assert_eq!(clock.pulses(1000.0, 48_000.0), 1);
+        // the rest of the first beat.
+        assert_eq!(clock.pulses(1000.0 + 960.0 * 23.5, 48_000.0), 23);
+        assert_eq!(clock.next_pulse(), Some(1000.0 + 960.0 * 24.0));
+
+        clock.tempo = 250;
+        assert_eq!(clock.pulses(1000.0 + 960.0 * 25.0, 48_000.0), 3);
+
+        clock.stop();
+        assert_eq!(clock.pulses(f64::MAX, 48_000.0), 0);
+    }
+
+    /// the rows a clock stream at `tempo` plays, from a start, with each pulse `jitter(i)` frames
+    /// off time.
+    fn follow(tempo: f64, pulses: usize, jitter: impl Fn(usize) -> f64) -> (ClockIn, Vec<f64>) {
+        let period = 60.0 * 48_000.0 / (tempo * PPQN as f64);
+        let mut clock = ClockIn::new(120, 48_000.0);
+        let mut rows = Vec::new();
+
+        assert_eq!(
+            clock.receive(0.0, MidiMessage::Start),
+            Some(ClockEvent::Start)
+        );
+
+        for i in 0..pulses {
+            let at = 1000.0 + i as f64 * period + jitter(i);
+
+            if let Some(ClockEvent::Row { at, .. }) = clock.receive(at, MidiMessage::Clock) {
+                rows.push(at);
+            }
+        }
+
+        (clock, rows)
+    }
+
+    #[test]
+    fn rows_follow_a_steady_clock() {
+        // 1000 frames a pulse.
+        let (clock, rows) = follow(120.0, PPQN as usize * 8, |_| 0.0);
+
+        assert_eq!(rows.len(), 8 * 4);
+        assert_eq!(rows[0], 1000.0);
+        assert!(rows
+            .windows(2)
+            .all(|rows| (rows[1] - rows[0] - 6000.0).abs() < 1.0));
+        assert!((clock.tempo(48_000.0).unwrap() - 120.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn jitter_is_smoothed_out() {
+        // pulses up to a millisecond early or late.
+        let jitter = |i: usize| [48.0, -48.0, 0.0, 24.0, -24.0][i % 5];
+        let (clock, rows) = follow(125.0, PPQN as usize * 32, jitter);
+
+        // 960 frames a pulse, once it has settled from the tracker tempo of 120. the pulses are
+        // as much as 96 frames apart from where they should be.
+        let settled = &rows[16..];
+        assert!(settled
+            .windows(2)
+            .all(|rows| (rows[1] - rows[0] - 5760.0).abs() < 4.0));
+        assert!((clock.tempo(48_000.0).unwrap() - 125.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn follows_a_change_of_tempo() {
+        let mut clock = ClockIn::new(120, 48_000.0);
+        let mut at = 0.0;
+
+        for tempo in [120.0, 90.0] {
+            for _ in 0..PPQN * 16 {
+                at += 60.0 * 48_000.0 / (tempo * PPQN as f64);
+                clock.receive(at, MidiMessage::Clock);
+            }
+
+            assert!((clock.tempo(48_000.0).unwrap() - tempo as f32).abs() < 0.1);
+        }
+    }
+
+    #[test]
+    fn losing_the_clock_stops_following_it() {
+        let (mut clock, _) = follow(120.0, 10, |_| 0.0);
+
+        assert!(!clock.lost(1000.0 + 9.0 * 1000.0 + 1000.0 * 23.0));
+        assert!(clock.lost(1000.0 + 9.0 * 1000.0 + 1000.0 * 25.0));
+        assert!(!clock.lost(f64::MAX));
+        assert_eq!(clock.tempo(48_000.0), None);
+
+        // found again, at the tempo it was lost at.
+        clock.receive(100_000.0, MidiMessage::Clock);
+        assert_eq!(clock.tempo(48_000.0), Some(120.0));
+    }
+}
@@ -0,0 +1,443 @@
+use super::{MidiMessage, MidiReceived};
+use crate::{
+    config::midi::MidiConfig,
+    params::{Choice, Param, ParamName},
+    pygame_coms::{DisplayCursor, Index, Instrument, Screen, Song},
+    tracker_state::{AllInstruments, StateUpdated},
+};
+use bevy::{log::*, prelude::*};
+use pyo3::pyclass;
+use serde::{Deserialize, Serialize};
+
+/// the number of mappings saved with a song.
+pub const N_SONG_MAPPINGS: usize = 32;
+/// the names of the mixer columns, as the mixer screen shows them.
+const MIXER_COLUMNS: [&str; 5] = ["LD-1", "LD-2", "BASS", "PERC", "MSTR"];
+/// how many places through its range a row is set to, to find if it changes the layout. more
+/// than any choice has options.
+const LAYOUT_STEPS: usize = 16;
+
+/// a parameter a controller can be mapped to, by the screen it is on and its name. the name
+/// finds it again when the rows around it change, like when an instruments output does.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ParamTarget {
+    /// a parameter of an instrument.
+    Instrument(Index, ParamName),
+    /// a parameter of a column of the mixer screen.
+    Mixer(usize, ParamName),
+    /// a parameter of the effects screen.
+    Effects(ParamName),
+}
+
+impl ParamTarget {
+    /// the parameter held on `screen`, `None` unless a parameter of the instrument, mixer, or
+    /// effects screens is selected. rows that change which rows an instrument has, like its
+    /// output, can't be mapped, as a knob would shuffle the rows under the cursor.
+    pub fn held(
+        screen: &Screen,
+        display_cursor: &DisplayCursor,
+        song: &mut Song,
+        instruments: &mut AllInstruments,
+    ) -> Option<Self> {
+        if !display_cursor.selected {
+            return None;
+        }
+
+        let row = display_cursor.row;
+        let name = |params: Vec<Param>| ParamName::new(params.get(row)?.name);
+
+        match *screen {
+            Screen::Instrument(i) => {
+                let instrument = instruments.0.get_mut(i)?.as_mut()?;
+                let name = name(instrument.params())?;
+
+                (!changes_layout(instrument, name)).then_some(Self::Instrument(i, name))
+            }
+            Screen::Mixer() => {
+                let col = display_cursor.col;
+
+                Some(Self::Mixer(col, name(song.mixer.params(col))?))
+            }
+            Screen::Effects() => Some(Self::Effects(name(song.effects_params())?)),
+            _ => None,
+        }
+    }
+
+    /// runs `f` on the parameter. `None` if it isn't there, like once its instrument is deleted,
+    /// or while its instrument's output doesn't have it.
+    pub fn with_param<R>(
+        &self,
+        song: &mut Song,
+        instruments: &mut AllInstruments,
+        f: impl FnOnce(&mut Param) -> R,
+    ) -> Option<R> {
+        let find = |params: Vec<Param>, name: ParamName| {
+            params
+                .into_iter()
+                .find(|param| name == param.name)
+                .map(|mut param| f(&mut param))
+        };
+
+        match *self {
+            Self::Instrument(i, name) => find(instruments.0.get_mut(i)?.as_mut()?.params(), name),
+            Self::Mixer(col, name) => find(song.mixer.params(col), name),
+            Self::Effects(name) => find(song.effects_params(), name),
+        }
+    }
+
+    /// where the parameter is and its name, for the mappings screen.
+    pub fn describe(&self) -> String {
+        match *self {
+            Self::Instrument(i, name) => format!("INST {i:02X} {name}"),
+            Self::Mixer(col, name) => {
+                format!("{} {name}", MIXER_COLUMNS.get(col).unwrap_or(&"----"))
+            }
+            Self::Effects(name) => format!("FX {name}"),
+        }
+    }
+}
+
+/// true if setting the parameter `name` can change which parameters `instrument` has.
+fn changes_layout(instrument: &Instrument, name: ParamName) -> bool {
+    let layout = |instrument: &mut Instrument| -> Vec<&'static str> {
+        instrument.params().iter().map(|param| param.name).collect()
+    };
+    let shown = layout(&mut instrument.clone());
+
+    (0..=LAYOUT_STEPS).any(|step| {
+        let mut tried = instrument.clone();
+
+        if let Some(param) = tried.params().iter_mut().find(|param| name == param.name) {
+            param.set_fraction(step as f32 / LAYOUT_STEPS as f32);
+        }
+
+        layout(&mut tried) != shown
+    })
+}
+
+/// a controller of the MIDI input, mapped to set a parameter.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct MidiMapping {
+    /// the MIDI channel, counting from 0.
+    pub channel: u8,
+    pub control: u8,
+    pub target: ParamTarget,
+}
+
+impl MidiMapping {
+    /// true if both can't be kept. a controller sets one parameter, and a parameter is set by one
+    /// controller.
+    fn clashes(&self, other: &Self) -> bool {
+        (self.channel, self.control) == (other.channel, other.control)
+            || self.target == other.target
+    }
+
+    /// the channel, from 1, and the controller, like `01:4A`.
+    pub fn control_name(&self) -> String {
+        format!("{:02}:{:02X}", self.channel + 1, self.control)
+    }
+}
+
+/// where mappings that are learned are kept.
+#[pyclass(module = "tracker_backend", eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum MappingScope {
+    /// with the song, for the synths it uses.
+    #[default]
+    Song,
+    /// in the MIDI config, for a controller that is always plugged in.
+    Global,
+}
+
+impl Choice for MappingScope {
+    const ALL: &'static [Self] = &[Self::Song, Self::Global];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Song => "SONG",
+            Self::Global => "GLOBAL",
+        }
+    }
+}
+
+/// the mappings of the song then the global ones, in the order the mappings screen lists them.
+pub fn mapped<'a>(
+    song: &'a [Option<MidiMapping>],
+    global: &'a [MidiMapping],
+) -> impl Iterator<Item = (MappingScope, MidiMapping)> + 'a {
+    song.iter()
+        .flatten()
+        .map(|mapping| (MappingScope::Song, *mapping))
+        .chain(
+            global
+                .iter()
+                .map(|mapping| (MappingScope::Global, *mapping)),
+        )
+}
+
+/// keeps `mapping` in `scope`, in place of any mapping of its controller or its parameter. false,
+/// changing nothing, if the song has no room for it.
+pub fn learn(
+    mapping: MidiMapping,
+    scope: MappingScope,
+    song: &mut [Option<MidiMapping>],
+    global: &mut Vec<MidiMapping>,
+) -> bool {
+    let has_room = song
+        .iter()
+        .any(|slot| slot.is_none_or(|kept| kept.clashes(&mapping)));
+
+    if scope == MappingScope::Song && !has_room {
+        return false;
+    }
+
+    for slot in song.iter_mut() {
+        if slot.is_some_and(|kept| kept.clashes(&mapping)) {
+            *slot = None;
+        }
+    }
+
+    global.retain(|kept| !kept.clashes(&mapping));
+
+    match scope {
+        MappingScope::Song => {
+            if let Some(slot) = song.iter_mut().find(|slot| slot.is_none()) {
+                *slot = Some(mapping);
+            }
+        }
+        MappingScope::Global => global.push(mapping),
+    }
+
+    true
+}
+
+/// removes the `i`th of the `mapped` mappings.
+pub fn forget(i: usize, song: &mut [Option<MidiMapping>], global: &mut Vec<MidiMapping>) {
+    let n_song = song.iter().flatten().count();
+
+    if i < n_song {
+        if let Some(slot) = song.iter_mut().filter(|slot| slot.is_some()).nth(i) {
+            *slot = None;
+        }
+    } else if i - n_song < global.len() {
+        global.remove(i - n_song);
+    }
+}
+
+/// the controller, parameter, and scope of each mapping, for the mappings screen.
+pub fn rows(song: &Song, config: &MidiConfig) -> Vec<(String, String, String)> {
+    mapped(&song.mappings, &config.mappings)
+        .map(|(scope, mapping)| {
+            (
+                mapping.control_name(),
+                mapping.target.describe(),
+                scope.name().to_string(),
+            )
+        })
+        .collect()
+}
+
+/// maps a controller moved while a parameter is held, on the instrument, mixer, or effects
+/// screens, to that parameter. mapped controllers set their parameters from any screen.
+pub(super) fn control_params(
+    mut received: EventReader<MidiReceived>,
+    screen: Res<Screen>,
+    display_cursor: Res<DisplayCursor>,
+    mut song: ResMut<Song>,
+    mut instruments: ResMut<AllInstruments>,
+    mut config: ResMut<MidiConfig>,
+    mut state_updated: EventWriter<StateUpdated>,
+) {
+    for MidiReceived { message, .. } in received.read() {
+        let MidiMessage::ControlChange {
+            channel,
+            control,
+            value,
+        } = *message
+        else {
+            continue;
+        };
+
+        if let Some(target) = ParamTarget::held(
+            &screen,
+            &display_cursor,
+            song.bypass_change_detection(),
+            instruments.bypass_change_detection(),
+        ) {
+            let mapping = MidiMapping {
+                channel,
+                control,
+                target,
+            };
+            let scope = config.learn_to;
+            let learned =
+                mapped(&song.mappings, &config.mappings).any(|kept| kept == (scope, mapping));
+
+            if !learned {
+                if learn(mapping, scope, &mut song.mappings, &mut config.mappings) {
+                    info!("mapped {} to {target:?}", mapping.control_name());
+                } else {
+                    warn!("the song has no room for more MIDI mappings");
+                }
+            }
+        }
+
+        let Some((_, mapping)) = mapped(&song.mappings, &config.mappings)
+            .find(|(_, mapping)| (mapping.channel, mapping.control) == (channel, control))
+        else {
+            continue;
+        };
+
+        // only what the parameter is a part of is marked as changed, so the synth isn't sent the
+        // rest.
+        let set = mapping.target.with_param(
+            song.bypass_change_detection(),
+            instruments.bypass_change_detection(),
+            |param| param.set_fraction(value as f32 / 127.0),
+        );
+
+        if set.is_some() {
+            match mapping.target {
+                ParamTarget::Instrument(..) => instruments.set_changed(),
+                ParamTarget::Mixer(..) | ParamTarget::Effects(_) => song.set_changed(),
+            }
+
+            state_updated.send_default();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pygame_coms::InstrumentOutput;
+
+    fn name(name: &str) -> ParamName {
+        ParamName::new(name).unwrap()
+    }
+
+    fn mapping(control: u8, target: ParamTarget) -> MidiMapping {
+        MidiMapping {
+            channel: 0,
+            control,
+            target,
+        }
+    }
+
+    #[test]
+    fn a_controller_sets_one_parameter() {
+        let mut song = [None; 2];
+        let mut global = Vec::new();
+        let volume = mapping(1, ParamTarget::Mixer(0, name("VOLUME")));
+        let pan = mapping(2, ParamTarget::Mixer(0, name("PAN")));
+
+        assert!(learn(volume, MappingScope::Song, &mut song, &mut global));
+        assert!(learn(pan, MappingScope::Global, &mut song, &mut global));
+
+        // the first controller moved to the second parameter, which the second controller loses.
+        let moved = mapping(1, ParamTarget::Mixer(0, name("PAN")));
+        assert!(learn(moved, MappingScope::Song, &mut song, &mut global));
+        assert_eq!(song, [Some(moved), None]);
+        assert!(global.is_empty());
+
+        // a full song has no room, unless a mapping is replaced.
+        let reverb = mapping(3, ParamTarget::Effects(name("REV MIX")));
+        assert!(learn(reverb, MappingScope::Song, &mut song, &mut global));
+        let delay = mapping(4, ParamTarget::Effects(name("DLY MIX")));
+        assert!(!learn(delay, MappingScope::Song, &mut song, &mut global));
+        assert!(learn(delay, MappingScope::Global, &mut song, &mut global));
+
+        let listed: Vec<_> = mapped(&song, &global).collect();
+        assert_eq!(
+            listed,
+            [
+                (MappingScope::Song, moved),
+                (MappingScope::Song, reverb),
+                (MappingScope::Global, delay),
+            ]
+        );
+
+        forget(1, &mut song, &mut global);
+        forget(1, &mut song, &mut global);
+        assert_eq!(song, [Some(moved), None]);
+        assert!(global.is_empty());
+    }
+
+    #[test]
+    fn controllers_set_parameters_across_their_range() {
+        let mut song = Song::default();
+        let mut instruments = AllInstruments::default();
+        let set = |song: &mut Song, target: ParamTarget, value: u8| {
+            target.with_param(song, &mut AllInstruments(Vec::new()), |param| {
+                param.set_fraction(value as f32 / 127.0)
+            })
+        };
+
+        // volume, from 0.0 to 1.0 in steps of 0.05.
+        set(&mut song, ParamTarget::Mixer(1, name("VOLUME")), 127);
+        assert_eq!(song.mixer.channels[1].volume, 1.0);
+        set(&mut song, ParamTarget::Mixer(1, name("VOLUME")), 64);
+        assert!((song.mixer.channels[1].volume - 0.5).abs() < 1.0e-6);
+        set(&mut song, ParamTarget::Mixer(4, name("VOLUME")), 0);
+        assert_eq!(song.mixer.master, 0.0);
+
+        // mute, off through the bottom half.
+        set(&mut song, ParamTarget::Mixer(2, name("MUTE")), 63);
+        assert!(!song.mixer.channels[2].mute);
+        set(&mut song, ParamTarget::Mixer(2, name("MUTE")), 64);
+        assert!(song.mixer.channels[2].mute);
+
+        // not in its column, or of an instrument that isn't there.
+        assert_eq!(set(&mut song, ParamTarget::Mixer(4, name("PAN")), 0), None);
+        let target = ParamTarget::Instrument(0x10, name("CUTOFF"));
+        assert_eq!(target.with_param(&mut song, &mut instruments, |_| ()), None);
+        assert_eq!(target.describe(), "INST 10 CUTOFF");
+        assert_eq!(ParamTarget::Mixer(3, name("PAN")).describe(), "PERC PAN");
+    }
+
+    #[test]
+    fn mappings_follow_their_parameter_when_the_output_changes() {
+        let mut song = Song::default();
+        let mut instruments = AllInstruments::default();
+        let screen = Screen::Instrument(0);
+        let mut held = |instruments: &mut AllInstruments, param: &str| {
+            let row = instruments.0[0]
+                .as_mut()
+                .unwrap()
+                .params()
+                .iter()
+                .position(|shown| shown.name == param)
+                .unwrap();
+            let display_cursor = DisplayCursor {
+                row,
+                col: 0,
+                selected: true,
+            };
+
+            ParamTarget::held(&screen, &display_cursor, &mut song, instruments)
+        };
+
+        // rows that change what the rows under them are can't be mapped.
+        assert_eq!(held(&mut instruments, "OUTPUT"), None);
+        assert_eq!(held(&mut instruments, "FX1 TYPE"), None);
+
+        let attack = held(&mut instruments, "ATTACK").unwrap();
+        assert_eq!(attack, ParamTarget::Instrument(0, name("ATTACK")));
+
+        // the sampler has an attack too, on another row.
+        let mut song = Song::default();
+        let mut set = |instruments: &mut AllInstruments, output| {
+            instruments.0[0].as_mut().unwrap().output = output;
+            attack.with_param(&mut song, instruments, |param| param.set_fraction(1.0))
+        };
+
+        assert_eq!(set(&mut instruments, InstrumentOutput::Sampler), Some(()));
+        let time = instruments.0[0].as_ref().unwrap().amp_env.attack;
+        assert_ne!(time, Instrument::new(0).amp_env.attack);
+
+        // an FM instrument has an attack for each operator, but none of its own.
+        assert_eq!(set(&mut instruments, InstrumentOutput::Fm), None);
+    }
+}
@@ -0,0 +1,1259 @@
+use crate::{
+    config::{
+        midi::{MidiConfig, MidiPort},
+        ui::{get_config, Bpm},
+    },
+    params::{Param, Range},
+    pygame_coms::{InstrumentOutput, Note},
+    sequencer::{
+        ClockEvent, ExternalTempo, NoteEvent, ScheduledNote, ScheduledTransport, Sequencer,
+        Transport, N_CHANNELS,
+    },
+    synth::AudioClock,
+    tracker_state::{AllInstruments, StateUpdated, Tempo},
+    PlayingState,
+};
+use anyhow::Result;
+use bevy::{log::*, prelude::*};
+use clock::{ClockIn, ClockOut, SmoothClock};
+use crossbeam::channel::{unbounded, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use pyo3::pyclass;
+use serde::{Deserialize, Serialize};
+use std::{
+    thread::{sleep, spawn},
+    time::Duration,
+};
+
+pub mod clock;
+pub mod learn;
+pub mod port;
+
+/// how often the MIDI threads look for messages that have come due, or come in.
+const TICK: Duration = Duration::from_millis(1);
+/// the number of MIDI channels on a port.
+pub const N_MIDI_CHANNELS: u8 = 16;
+/// the controller that releases every note on a MIDI channel.
+const ALL_NOTES_OFF: u8 = 123;
+/// the controller of the mod wheel.
+pub const MOD_WHEEL: u8 = 1;
+/// the controllers that pick the bank the next program change is from.
+const BANK_MSB: u8 = 0;
+const BANK_LSB: u8 = 32;
+/// the value of a pitch bend message that doesn't bend.
+pub const PITCH_BEND_CENTER: u16 = 0x2000;
+/// the number of controllers an instrument can set from its phrases.
+pub const N_CC_SLOTS: usize = 4;
+/// the highest controller that isn't a channel mode message, like all notes off.
+const MAX_CONTROL: u8 = 119;
+/// how far the tempo of an external clock moves before the new tempo is shown.
+const TEMPO_HYSTERESIS: f32 = 0.5;
+
+/// how an instrument whose output is `InstrumentOutput::UsbMidi` plays.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MidiParams {
+    /// the MIDI channel notes are sent on, from 1 to 16.
+    pub channel: u8,
+    /// the velocity of a note played at full velocity.
+    pub velocity: u8,
+    /// the controller each CC slot sets, for the `MidiCc` commands of phrases.
+    pub cc: [u8; N_CC_SLOTS],
+    /// the bank and program picked when playback starts, or the instrument starts playing on a
+    /// channel. `None` leaves it as it is on the synth.
+    pub bank_msb: Option<u8>,
+    pub bank_lsb: Option<u8>,
+    pub program: Option<u8>,
+}
+
+impl Default for MidiParams {
+    fn default() -> Self {
+        Self {
+            channel: 1,
+            velocity: 100,
+            // the mod wheel, cutoff, resonance, and pan on most synths.
+            cc: [MOD_WHEEL, 74, 71, 10],
+            bank_msb: None,
+            bank_lsb: None,
+            program: None,
+        }
+    }
+}
+
+impl MidiParams {
+    pub fn params(&mut self) -> Vec<Param<'_>> {
+        let mut params = vec![
+            Param::new(
+                "MIDI CH",
+                &mut self.channel,
+                Range::new(1.0, N_MIDI_CHANNELS as f32, 1.0),
+            ),
+            Param::new("VELOCITY", &mut self.velocity, Range::new(1.0, 127.0, 1.0)),
+        ];
+
+        for (name, cc) in ["CC A", "CC B", "CC C", "CC D"]
+            .into_iter()
+            .zip(&mut self.cc)
+        {
+            params.push(Param::new(
+                name,
+                cc,
+                Range::new(0.0, MAX_CONTROL as f32, 1.0),
+            ));
+        }
+
+        params.extend([
+            Param::new("BANK MSB", &mut self.bank_msb, Range::new(0.0, 127.0, 1.0)),
+            Param::new("BANK LSB", &mut self.bank_lsb, Range::new(0.0, 127.0, 1.0)),
+            Param::new("PROGRAM", &mut self.program, Range::new(0.0, 127.0, 1.0)),
+        ]);
+
+        params
+    }
+
+    /// the MIDI channel counting from 0, as it is sent.
+    pub fn midi_channel(&self) -> u8 {
+        self.channel.clamp(1, N_MIDI_CHANNELS) - 1
+    }
+
+    /// the MIDI velocity of a note played at `velocity`, from 0.0 to 1.0.
+    pub fn note_velocity(&self, velocity: f32) -> u8 {
+        (self.velocity as f32 * velocity).round().clamp(1.0, 127.0) as u8
+    }
+
+    /// the controller set by CC slot `slot`.
+    pub fn control(&self, slot: u8) -> u8 {
+        self.cc[slot as usize % N_CC_SLOTS].min(MAX_CONTROL)
+    }
+}
+
+/// a MIDI message. channels count from 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MidiMessage {
+    NoteOn {
+        channel: u8,
+        note: Note,
+        velocity: u8,
+    },
+    NoteOff {
+        channel: u8,
+        note: Note,
+    },
+    ControlChange {
+        channel: u8,
+        control: u8,
+        value: u8,
+    },
+    /// `value` is 14 bits, `PITCH_BEND_CENTER` doesn't bend.
+    PitchBend {
+        channel: u8,
+        value: u16,
+    },
+    ProgramChange {
+        channel: u8,
+        program: u8,
+    },
+    /// where playback will continue from, in 16th notes from the start of the song.
+    SongPosition(u16),
+    /// one of the `clock::PPQN` pulses of a quarter note.
+    Clock,
+    Start,
+    Continue,
+    Stop,
+}
+
+impl MidiMessage {
+    /// adds the bytes of the message to `buf`.
+    pub fn write(&self, buf: &mut Vec<u8>) {
+        match *self {
+            Self::NoteOn {
+                channel,
+                note,
+                velocity,
+            } => buf.extend([0x90 | channel & 0x0F, note & 0x7F, velocity & 0x7F]),
+            Self::NoteOff { channel, note } => buf.extend([0x80 | channel & 0x0F, note & 0x7F, 0]),
+            Self::ControlChange {
+                channel,
+                control,
+                value,
+            } => buf.extend([0xB0 | channel & 0x0F, control & 0x7F, value & 0x7F]),
+            Self::PitchBend { channel, value } => buf.extend([
+                0xE0 | channel & 0x0F,
+                (value & 0x7F) as u8,
+                (value >> 7 & 0x7F) as u8,
+            ]),
+            Self::ProgramChange { channel, program } => {
+                buf.extend([0xC0 | channel & 0x0F, program & 0x7F])
+            }
+            Self::SongPosition(position) => {
+                buf.extend([0xF2, (position & 0x7F) as u8, (position >> 7 & 0x7F) as u8])
+            }
+            Self::Clock => buf.push(0xF8),
+            Self::Start => buf.push(0xFA),
+            Self::Continue => buf.push(0xFB),
+            Self::Stop => buf.push(0xFC),
+        }
+    }
+}
+
+/// turns a stream of MIDI bytes back into messages, following running status. messages the
+/// tracker doesn't use are skipped.
+#[derive(Debug, Clone, Default)]
+pub struct MidiParser {
+    /// the status of the message being read, `None` while reading something that is skipped.
+    status: Option<u8>,
+    data: [u8; 2],
+    len: usize,
+}
+
+impl MidiParser {
+    /// reads the next byte, returning a message once one is complete.
+    pub fn push(&mut self, byte: u8) -> Option<MidiMessage> {
+        // real time messages can come between the bytes of any other message.
+        match byte {
+            0xF8 => return Some(MidiMessage::Clock),
+            0xFA => return Some(MidiMessage::Start),
+            0xFB => return Some(MidiMessage::Continue),
+            0xFC => return Some(MidiMessage::Stop),
+            0xF9 | 0xFD.. => return None,
+            _ => {}
+        }
+
+        if byte & 0x80 != 0 {
+            // system common messages and sysex cancel running status.
+            self.status = (byte < 0xF0).then_some(byte);
+            self.len = 0;
+
+            return None;
+        }
+
+        let status = self.status?;
+        self.data[self.len] = byte;
+        self.len += 1;
+
+        let len = match status & 0xF0 {
+            0xC0 | 0xD0 => 1,
+            _ => 2,
+        };
+
+        if self.len < len {
+            return None;
+        }
+
+        self.len = 0;
+
+        let channel = status & 0x0F;
+        let [first, second] = self.data;
+
+        match status & 0xF0 {
+            0x90 if second > 0 => Some(MidiMessage::NoteOn {
+                channel,
+                note: first,
+                velocity: second,
+            }),
+            // a note on with no velocity is a note off.
+            0x80 | 0x90 => Some(MidiMessage::NoteOff {
+                channel,
+                note: first,
+            }),
+            0xB0 => Some(MidiMessage::ControlChange {
+                channel,
+                control: first,
+                value: second,
+            }),
+            0xE0 => Some(MidiMessage::PitchBend {
+                channel,
+                value: first as u16 | (second as u16) << 7,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// somewhere MIDI is sent.
+pub trait MidiSink {
+    fn send(&mut self, message: &MidiMessage) -> Result<()>;
+}
+
+/// somewhere MIDI is read from.
+pub trait MidiSource {
+    /// reads what has come in into `buf` without waiting, returning how many bytes were read.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+}
+
+/// sent for each message read from the MIDI input.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MidiReceived {
+    /// the frame of the `AudioClock` the message came in on.
+    pub at: u64,
+    pub message: MidiMessage,
+}
+
+/// the messages read by the MIDI input thread, and the way to tell it to read another port.
+#[derive(Debug, Clone, Resource)]
+pub struct MidiInput {
+    messages: Receiver<MidiReceived>,
+    ports: Sender<MidiPort>,
+}
+
+/// the MIDI clock read from the input, followed while `MidiConfig::clock_in` is on.
+#[derive(Debug, Clone, Resource)]
+struct FollowedClock(ClockIn);
+
+/// messages sent to the MIDI thread.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MidiCmd {
+    /// releases the notes held by `channel` then plays `notes` on it.
+    NoteOn {
+        channel: usize,
+        midi_channel: u8,
+        notes: Vec<Note>,
+        velocity: u8,
+    },
+    /// releases the notes held by `channel`.
+    NoteOff {
+        channel: usize,
+    },
+    /// releases every held note, and tells every MIDI channel to do the same.
+    AllNotesOff,
+    /// releases every held note, then closes the MIDI output and opens `port`.
+    SetOutput(MidiPort),
+    /// starts the clock, from `position` rows into the song. stops it first if it is running,
+    /// so whatever follows it jumps to `position`.
+    Start {
+        position: u16,
+    },
+    Stop,
+    /// the tempo the clock pulses at.
+    SetTempo(Bpm),
+    /// true to send clock, start, stop, and song position.
+    SetClock(bool),
+    /// sets `control` on `midi_channel`, gliding from the value it was last set to over `glide`
+    /// frames.
+    ControlChange {
+        midi_channel: u8,
+        control: u8,
+        value: u8,
+        glide: u64,
+    },
+    /// bends the notes of `midi_channel`. `value` is 14 bits, `PITCH_BEND_CENTER` doesn't bend.
+    PitchBend {
+        midi_channel: u8,
+        value: u16,
+    },
+    /// selects the bank, then the program, of `midi_channel`. each is only sent if it is set.
+    Program {
+        midi_channel: u8,
+        bank_msb: Option<u8>,
+        bank_lsb: Option<u8>,
+        program: Option<u8>,
+    },
+}
+
+/// used to send commands to the MIDI thread. each command goes with the frame of the
+/// `AudioClock` it lands on, so MIDI stays in time with the synth.
+#[derive(Debug, Clone, Resource)]
+pub struct MidiHandle(pub Sender<(u64, MidiCmd)>);
+
+impl MidiHandle {
+    /// runs `cmd` as soon as it arrives.
+    pub fn send(&self, cmd: MidiCmd) {
+        self.send_at(0, cmd);
+    }
+
+    /// runs `cmd` once frame `at` has been rendered.
+    pub fn send_at(&self, at: u64, cmd: MidiCmd) {
+        if let Err(e) = self.0.send((at, cmd)) {
+            error!("failed to send command to the MIDI thread: {e}");
+        }
+    }
+}
+
+/// a controller gliding from one value to another.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Ramp {
+    midi_channel: u8,
+    control: u8,
+    from: u8,
+    to: u8,
+    /// the frame the glide starts on, and the frames it takes.
+    start: f64,
+    len: f64,
+}
+
+impl Ramp {
+    /// the value of the controller on frame `now`.
+    fn value(&self, now: f64) -> u8 {
+        let t = ((now - self.start) / self.len).clamp(0.0, 1.0);
+
+        (self.from as f64 + (self.to as f64 - self.from as f64) * t).round() as u8
+    }
+
+    fn is_done(&self, now: f64) -> bool {
+        now >= self.start + self.len
+    }
+}
+
+/// turns commands into MIDI messages, keeping track of the notes each channel holds so they can
+/// be released. lives on the MIDI thread.
+pub struct MidiOut {
+    sink: Option<Box<dyn MidiSink>>,
+    /// the MIDI channel and notes held by each channel.
+    held: [Option<(u8, Vec<Note>)>; N_CHANNELS],
+    /// true to send the clock. it keeps time while off, so it can be turned on mid song.
+    send_clock: bool,
+    clock: ClockOut,
+    /// the value each controller of each MIDI channel was last set to.
+    controls: [[Option<u8>; 128]; N_MIDI_CHANNELS as usize],
+    ramps: Vec<Ramp>,
+    /// the MIDI channels whose notes are bent.
+    bent: [bool; N_MIDI_CHANNELS as usize],
+}
+
+impl MidiOut {
+    pub fn new(sink: Option<Box<dyn MidiSink>>, send_clock: bool) -> Self {
+        Self {
+            sink,
+            held: Default::default(),
+            send_clock,
+            clock: ClockOut::new(120),
+            controls: [[None; 128]; N_MIDI_CHANNELS as usize],
+            ramps: Vec::new(),
+            bent: [false; N_MIDI_CHANNELS as usize],
+        }
+    }
+
+    fn send(&mut self, message: MidiMessage) {
+        if let Some(sink) = self.sink.as_mut()
+            && let Err(e) = sink.send(&message)
+        {
+            error!("failed to send {message:?}: {e}");
+        }
+    }
+
+    /// sends a clock, start, stop, or song position message, if the clock is sent.
+    fn send_clock(&mut self, message: MidiMessage) {
+        if self.send_clock {
+            self.send(message);
+        }
+    }
+
+    fn send_control(&mut self, midi_channel: u8, control: u8, value: u8) {
+        self.controls[midi_channel as usize][control as usize] = Some(value);
+        self.send(MidiMessage::ControlChange {
+            channel: midi_channel,
+            control,
+            value,
+        });
+    }
+
+    fn release(&mut self, channel: usize) {
+        let Some((midi_channel, notes)) = self.held.get_mut(channel).and_then(Option::take) else {
+            return;
+        };
+
+        for note in notes {
+            self.send(MidiMessage::NoteOff {
+                channel: midi_channel,
+                note,
+            });
+        }
+    }
+
+    /// runs `cmd`, which was due on frame `at`.
+    pub fn handle(&mut self, at: u64, cmd: MidiCmd) {
+        match cmd {
+            MidiCmd::NoteOn {
+                channel,
+                midi_channel,
+                notes,
+                velocity,
+            } => {
+                if channel >= N_CHANNELS {
+                    warn!("channel {channel} can't send MIDI");
+                    return;
+                }
+
+                self.release(channel);
+
+                for &note in &notes {
+                    self.send(MidiMessage::NoteOn {
+                        channel: midi_channel,
+                        note,
+                        velocity,
+                    });
+                }
+
+                self.held[channel] = Some((midi_channel, notes));
+            }
+            MidiCmd::NoteOff { channel } => self.release(channel),
+            MidiCmd::AllNotesOff => {
+                (0..N_CHANNELS).for_each(|channel| self.release(channel));
+                self.ramps.clear();
+
+                for channel in 0..N_MIDI_CHANNELS {
+                    if std::mem::take(&mut self.bent[channel as usize]) {
+                        self.send(MidiMessage::PitchBend {
+                            channel,
+                            value: PITCH_BEND_CENTER,
+                        });
+                    }
+                }
+
+                for channel in 0..N_MIDI_CHANNELS {
+                    self.send(MidiMessage::ControlChange {
+                        channel,
+                        control: ALL_NOTES_OFF,
+                        value: 0,
+                    });
+                }
+            }
+            MidiCmd::SetOutput(port) => {
+                self.handle(at, MidiCmd::AllNotesOff);
+
+                if self.clock.is_running() {
+                    self.send_clock(MidiMessage::Stop);
+                }
+
+                self.sink = open_output(&port);
+            }
+            MidiCmd::Start { position } => {
+                if self.clock.is_running() {
+                    self.send_clock(MidiMessage::Stop);
+                }
+
+                if position == 0 {
+                    self.send_clock(MidiMessage::Start);
+                } else {
+                    // rows are 16th notes, the unit of the song position.
+                    self.send_clock(MidiMessage::SongPosition(position.min(0x3FFF)));
+                    self.send_clock(MidiMessage::Continue);
+                }
+
+                self.clock.start(at as f64);
+            }
+            MidiCmd::Stop => {
+                if self.clock.is_running() {
+                    self.send_clock(MidiMessage::Stop);
+                    self.clock.stop();
+                }
+            }
+            MidiCmd::SetTempo(tempo) => self.clock.tempo = tempo,
+            MidiCmd::SetClock(send_clock) => {
+                if !send_clock && self.clock.is_running() {
+                    self.send_clock(MidiMessage::Stop);
+                }
+
+                self.send_clock = send_clock;
+            }
+            MidiCmd::ControlChange {
+                midi_channel,
+                control,
+                value,
+                glide,
+            } => {
+                let (midi_channel, control) = (midi_channel & 0x0F, control & 0x7F);
+                self.ramps
+                    .retain(|ramp| ramp.midi_channel != midi_channel || ramp.control != control);
+
+                match self.controls[midi_channel as usize][control as usize] {
+                    // a controller that has never been set can't glide from anywhere.
+                    Some(from) if glide > 0 => self.ramps.push(Ramp {
+                        midi_channel,
+                        control,
+                        from,
+                        to: value,
+                        start: at as f64,
+                        len: glide as f64,
+                    }),
+                    _ => self.send_control(midi_channel, control, value),
+                }
+            }
+            MidiCmd::PitchBend {
+                midi_channel,
+                value,
+            } => {
+                let midi_channel = midi_channel & 0x0F;
+                self.bent[midi_channel as usize] = value != PITCH_BEND_CENTER;
+                self.send(MidiMessage::PitchBend {
+                    channel: midi_channel,
+                    value,
+                });
+            }
+            MidiCmd::Program {
+                midi_channel,
+                bank_msb,
+                bank_lsb,
+                program,
+            } => {
+                let midi_channel = midi_channel & 0x0F;
+
+                // a bank select only takes effect on the program change after it.
+                for (control, value) in [(BANK_MSB, bank_msb), (BANK_LSB, bank_lsb)] {
+                    if let Some(value) = value {
+                        self.send_control(midi_channel, control, value);
+                    }
+                }
+
+                if let Some(program) = program {
+                    self.send(MidiMessage::ProgramChange {
+                        channel: midi_channel,
+                        program,
+                    });
+                }
+            }
+        }
+    }
+
+    /// sends the clock pulses, and the steps of controllers gliding, that have come due by frame
+    /// `now`.
+    pub fn tick(&mut self, now: f64, sample_rate: f64) {
+        for _ in 0..self.clock.pulses(now, sample_rate) {
+            self.send_clock(MidiMessage::Clock);
+        }
+
+        for ramp in std::mem::take(&mut self.ramps) {
+            let value = ramp.value(now);
+
+            if self.controls[ramp.midi_channel as usize][ramp.control as usize] != Some(value) {
+                self.send_control(ramp.midi_channel, ramp.control, value);
+            }
+
+            if !ramp.is_done(now) {
+                self.ramps.push(ramp);
+            }
+        }
+    }
+}
+
+/// opens `port`, or nothing if it can't be opened.
+fn open_output(port: &MidiPort) -> Option<Box<dyn MidiSink>> {
+    match port::open_output(port) {
+        Ok(sink) => {
+            if sink.is_some() {
+                info!("MIDI output opened on {}", port.name);
+            }
+
+            sink
+        }
+        Err(e) => {
+            error!(
+                "failed to open MIDI output {}, MIDI won't be sent: {e}",
+                port.name
+            );
+            None
+        }
+    }
+}
+
+/// opens `port` to read from, or nothing if it can't be opened.
+fn open_input(port: &MidiPort) -> Option<Box<dyn MidiSource>> {
+    match port::open_input(port) {
+        Ok(source) => {
+            if source.is_some() {
+                info!("MIDI input opened on {}", port.name);
+            }
+
+            source
+        }
+        Err(e) => {
+            error!(
+                "failed to open MIDI input {}, MIDI won't be read: {e}",
+                port.name
+            );
+            None
+        }
+    }
+}
+
+/// reads MIDI from the input port until the app exits, opening another port when told to.
+fn input_thread(
+    ports: Receiver<MidiPort>,
+    messages: Sender<MidiReceived>,
+    port: MidiPort,
+    clock: AudioClock,
+) {
+    let mut clock = SmoothClock::new(clock);
+    let mut input = open_input(&port);
+    let mut parser = MidiParser::default();
+    let mut buf = [0; 64];
+
+    loop {
+        match ports.try_recv() {
+            Ok(port) => {
+                input = open_input(&port);
+                parser = MidiParser::default();
+            }
+            Err(TryRecvError::Empty) => {}
+            Err(TryRecvError::Disconnected) => return,
+        }
+
+        let read = match input.as_mut().map(|input| input.read(&mut buf)) {
+            Some(Ok(read)) => read,
+            Some(Err(e)) => {
+                error!("reading MIDI failed, the input is closed: {e}");
+                input = None;
+                0
+            }
+            None => 0,
+        };
+
+        let at = clock.now() as u64;
+
+        for message in buf[..read].iter().filter_map(|byte| parser.push(*byte)) {
+            if messages.send(MidiReceived { at, message }).is_err() {
+                return;
+            }
+        }
+
+        if read == 0 {
+            sleep(TICK);
+        }
+    }
+}
+
+/// sends MIDI as it comes due on the audio clock until the app exits.
+fn midi_thread(rx: Receiver<(u64, MidiCmd)>, config: MidiConfig, clock: AudioClock) {
+    let mut out = MidiOut::new(open_output(&config.output), config.clock_out);
+    let mut clock = SmoothClock::new(clock);
+    // commands waiting for their frame, soonest first.
+    let mut pending: Vec<(u64, MidiCmd)> = Vec::new();
+
+    loop {
+        // wakes up for the next clock pulse, rather than as much as a tick after it.
+        let now = clock.now();
+        let timeout = out.clock.next_pulse().map_or(TICK, |at| {
+            Duration::from_secs_f64(
+                ((at - now) / clock.sample_rate()).clamp(0.0, TICK.as_secs_f64()),
+            )
+        });
+
+        match rx.recv_timeout(timeout) {
+            Ok((at, cmd)) => {
+                let i = pending.partition_point(|(pending, _)| *pending <= at);
+                pending.insert(i, (at, cmd));
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                out.handle(0, MidiCmd::AllNotesOff);
+                out.handle(0, MidiCmd::Stop);
+                info!("MIDI thread exiting");
+                return;
+            }
+        }
+
+        let now = clock.now();
+        let due = pending.partition_point(|(at, _)| *at as f64 <= now);
+
+        for (at, cmd) in pending.drain(..due) {
+            out.handle(at, cmd);
+        }
+
+        out.tick(now, clock.sample_rate());
+    }
+}
+
+pub struct MidiPlugin;
+
+impl Plugin for MidiPlugin {
+    fn build(&self, app: &mut App) {
+        debug!("tracker_backend::midi::MidiPlugin loaded");
+
+        let (tx, rx) = unbounded();
+        let config = get_config().midi;
+        let midi_config = config.clone();
+        // MIDI is timed against the synth, so the SynthPlugin has to be added first.
+        let clock = app.world().resource::<AudioClock>().clone();
+        let followed = FollowedClock(ClockIn::new(
+            app.world().resource::<Tempo>().0,
+            clock.sample_rate() as f64,
+        ));
+        let in_clock = clock.clone();
+        spawn(move || midi_thread(rx, midi_config, clock));
+
+        let (messages_tx, messages) = unbounded();
+        let (ports, ports_rx) = unbounded();
+        let in_port = config.input.port.clone();
+        spawn(move || input_thread(ports_rx, messages_tx, in_port, in_clock));
+
+        app.insert_resource(MidiHandle(tx))
+            .insert_resource(MidiInput { messages, ports })
+            .insert_resource(followed)
+            .insert_resource(config)
+            .add_event::<MidiReceived>()
+            .add_systems(PreUpdate, (read_input, follow_clock).chain())
+            .add_systems(Update, (sync_config, save_config, sync_tempo))
+            .add_systems(Update, play_notes)
+            .add_systems(Update, learn::control_params)
+            .add_systems(Update, play_transport)
+            .add_systems(OnEnter(PlayingState::NotPlaying), all_notes_off);
+    }
+}
+
+/// passes changes made on the settings screen on to the MIDI threads.
+fn sync_config(
+    config: Res<MidiConfig>,
+    midi: Res<MidiHandle>,
+    input: Res<MidiInput>,
+    mut last: Local<Option<MidiConfig>>,
+) {
+    if !config.is_changed() {
+        return;
+    }
+
+    // the threads were started with the config as it was first added.
+    let Some(last) = last.replace(config.clone()) else {
+        return;
+    };
+
+    if last.output != config.output {
+        midi.send(MidiCmd::SetOutput(config.output.clone()));
+    }
+
+    if last.clock_out != config.clock_out {
+        midi.send(MidiCmd::SetClock(config.clock_out));
+    }
+
+    if last.input != config.input
+        && let Err(e) = input.ports.send(config.input.port.clone())
+    {
+        error!("failed to send the MIDI input port to its thread: {e}");
+    }
+}
+
+/// keeps changes to the config, like learned global mappings, for the next run.
+fn save_config(config: Res<MidiConfig>) {
+    if !config.is_changed() || config.is_added() {
+        return;
+    }
+
+    let path = MidiConfig::path();
+
+    if let Err(e) = config.save(&path) {
+        error!("failed to save the MIDI config to {}: {e}", path.display());
+    }
+}
+
+/// keeps the tempo the MIDI clock pulses at up to date.
+fn sync_tempo(tempo: Res<Tempo>, external: Res<ExternalTempo>, midi: Res<MidiHandle>) {
+    if tempo.is_changed() || external.is_changed() {
+        midi.send(MidiCmd::SetTempo(external.bpm(&tempo)));
+    }
+}
+
+/// hands what the MIDI input has read to the systems that play it.
+fn read_input(input: Res<MidiInput>, mut received: EventWriter<MidiReceived>) {
+    received.send_batch(input.messages.try_iter());
+}
+
+/// turns the MIDI clock, start, stop, and continue read from the input into `ClockEvent`s for the
+/// sequencer, and keeps its estimated tempo up to date. once the clock is lost, playback goes on
+/// at the tracker's own tempo.
+fn follow_clock(
+    config: Res<MidiConfig>,
+    audio_clock: Res<AudioClock>,
+    tempo: Res<Tempo>,
+    mut followed: ResMut<FollowedClock>,
+    mut received: EventReader<MidiReceived>,
+    mut clock_events: EventWriter<ClockEvent>,
+    mut external: ResMut<ExternalTempo>,
+    mut state_updated: EventWriter<StateUpdated>,
+) {
+    let sample_rate = audio_clock.sample_rate() as f64;
+
+    if config.clock_in {
+        for MidiReceived { at, message } in received.read() {
+            if let Some(event) = followed.0.receive(*at as f64, *message) {
+                clock_events.send(event);
+            }
+        }
+
+        if followed.0.lost(audio_clock.now() as f64) {
+            warn!("lost the MIDI clock, playing at the trackers own tempo");
+        }
+    } else if followed.0.is_running() {
+        followed.0 = ClockIn::new(tempo.0, sample_rate);
+    }
+
+    let estimate = followed.0.tempo(sample_rate);
+    // the estimate wanders a little, which isn't worth redrawing for.
+    let moved = match (external.0, estimate) {
+        (Some(shown), Some(estimate)) => (shown - estimate).abs() >= TEMPO_HYSTERESIS,
+        (shown, estimate) => shown.is_some() != estimate.is_some(),
+    };
+
+    if moved {
+        external.0 = estimate;
+        state_updated.send_default();
+    }
+}
+
+/// sends the notes of `InstrumentOutput::UsbMidi` instruments to the MIDI thread.
+fn play_notes(
+    mut note_events: EventReader<ScheduledNote>,
+    instruments: Res<AllInstruments>,
+    midi: Res<MidiHandle>,
+) {
+    for ScheduledNote { at, event } in note_events.read() {
+        let cmd = match event {
+            NoteEvent::NoteOn {
+                channel,
+                instrument,
+                notes,
+                velocity,
+            } => match instruments.0.get(*instrument).and_then(Option::as_ref) {
+                Some(inst) if inst.output == InstrumentOutput::UsbMidi => MidiCmd::NoteOn {
+                    channel: *channel,
+                    midi_channel: inst.midi.midi_channel(),
+                    notes: notes.clone(),
+                    velocity: inst.midi.note_velocity(*velocity),
+                },
+                // another instrument has taken the channel over.
+                _ => MidiCmd::NoteOff { channel: *channel },
+            },
+            NoteEvent::NoteOff { channel } => MidiCmd::NoteOff { channel: *channel },
+            NoteEvent::ControlChange {
+                instrument,
+                slot,
+                value,
+                glide,
+                ..
+            } => match instruments.0.get(*instrument).and_then(Option::as_ref) {
+                Some(inst) if inst.output == InstrumentOutput::UsbMidi => MidiCmd::ControlChange {
+                    midi_channel: inst.midi.midi_channel(),
+                    control: inst.midi.control(*slot),
+                    value: (*value).min(127),
+                    glide: *glide,
+                },
+                _ => continue,
+            },
+            NoteEvent::PitchBend {
+                instrument, bend, ..
+            } => match instruments.0.get(*instrument).and_then(Option::as_ref) {
+                Some(inst) if inst.output == InstrumentOutput::UsbMidi => MidiCmd::PitchBend {
+                    midi_channel: inst.midi.midi_channel(),
+                    value: pitch_bend(*bend),
+                },
+                _ => continue,
+            },
+            NoteEvent::Program { instrument, .. } => {
+                match instruments.0.get(*instrument).and_then(Option::as_ref) {
+                    Some(inst) if inst.output == InstrumentOutput::UsbMidi => MidiCmd::Program {
+                        midi_channel: inst.midi.midi_channel(),
+                        bank_msb: inst.midi.bank_msb,
+                        bank_lsb: inst.midi.bank_lsb,
+                        program: inst.midi.program,
+                    },
+                    _ => continue,
+                }
+            }
+            NoteEvent::Cutoff { .. } | NoteEvent::Volume { .. } | NoteEvent::Send { .. } => {
+                continue
+            }
+        };
+
+        midi.send_at(*at, cmd);
+    }
+}
+
+/// the 14 bit pitch bend with `bend` as its top 7 bits. the top of the range bends all the way.
+fn pitch_bend(bend: u8) -> u16 {
+    match bend.min(127) {
+        127 => 0x3FFF,
+        bend => (bend as u16) << 7,
+    }
+}
+
+/// starts, stops, and moves the MIDI clock along with playback.
+fn play_transport(mut transport: EventReader<ScheduledTransport>, midi: Res<MidiHandle>) {
+    for ScheduledTransport { at, event } in transport.read() {
+        let cmd = match *event {
+            Transport::Play { position } => MidiCmd::Start { position },
+            Transport::Stop => MidiCmd::Stop,
+        };
+
+        midi.send_at(*at, cmd);
+    }
+}
+
+/// silences everything playing on the MIDI output when playback stops.
+fn all_notes_off(sequencer: Res<Sequencer>, midi: Res<MidiHandle>) {
+    // lands with the row that would have come next, after the rows already scheduled.
+    let at = sequencer.next_row_at.map_or(0, |at| at as u64);
+    midi.send_at(at, MidiCmd::AllNotesOff);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// a port that keeps what is sent to it.
+    #[derive(Debug, Clone, Default)]
+    struct MockPort(Arc<Mutex<Vec<MidiMessage>>>);
+
+    impl MidiSink for MockPort {
+        fn send(&mut self, message: &MidiMessage) -> Result<()> {
+            self.0.lock().unwrap().push(*message);
+            Ok(())
+        }
+    }
+
+    fn play(cmds: Vec<MidiCmd>) -> Vec<MidiMessage> {
+        let port = MockPort::default();
+        let mut out = MidiOut::new(Some(Box::new(port.clone())), true);
+
+        cmds.into_iter().for_each(|cmd| out.handle(0, cmd));
+
+        let sent = port.0.lock().unwrap().clone();
+        sent
+    }
+
+    fn note_on(channel: usize, notes: Vec<Note>) -> MidiCmd {
+        MidiCmd::NoteOn {
+            channel,
+            midi_channel: channel as u8,
+            notes,
+            velocity: 100,
+        }
+    }
+
+    fn on(channel: u8, note: Note) -> MidiMessage {
+        MidiMessage::NoteOn {
+            channel,
+            note,
+            velocity: 100,
+        }
+    }
+
+    fn off(channel: u8, note: Note) -> MidiMessage {
+        MidiMessage::NoteOff { channel, note }
+    }
+
+    #[test]
+    fn notes_are_released_by_their_channel() {
+        let sent = play(vec![
+            note_on(0, vec![60, 64]),
+            note_on(1, vec![48]),
+            note_on(0, vec![62]),
+            MidiCmd::NoteOff { channel: 1 },
+            MidiCmd::NoteOff { channel: 1 },
+        ]);
+
+        assert_eq!(
+            sent,
+            [
+                on(0, 60),
+                on(0, 64),
+                on(1, 48),
+                off(0, 60),
+                off(0, 64),
+                on(0, 62),
+                off(1, 48),
+            ]
+        );
+    }
+
+    #[test]
+    fn all_notes_off_silences_every_channel() {
+        let sent = play(vec![note_on(2, vec![36]), MidiCmd::AllNotesOff]);
+
+        assert_eq!(sent.len(), 2 + N_MIDI_CHANNELS as usize);
+        assert_eq!(sent[1], off(2, 36));
+        assert!(sent[2..].iter().enumerate().all(|(i, message)| *message
+            == MidiMessage::ControlChange {
+                channel: i as u8,
+                control: ALL_NOTES_OFF,
+                value: 0,
+            }));
+
+        let mut bytes = Vec::new();
+        sent[1].write(&mut bytes);
+        sent[2].write(&mut bytes);
+        assert_eq!(bytes, [0x82, 36, 0, 0xB0, 123, 0]);
+    }
+
+    #[test]
+    fn clock_follows_the_transport() {
+        let port = MockPort::default();
+        let mut out = MidiOut::new(Some(Box::new(port.clone())), true);
+        let sent = |from: usize| port.0.lock().unwrap()[from..].to_vec();
+
+        out.handle(0, MidiCmd::SetTempo(125));
+        out.handle(480, MidiCmd::Start { position: 0 });
+        // 960 frames a pulse, so a beat and the first pulse of the next.
+        out.tick(480.0 + 960.0 * 24.0, 48_000.0);
+
+        let started = sent(0);
+        assert_eq!(started.len(), 1 + 25);
+        assert_eq!(started[0], MidiMessage::Start);
+        assert!(started[1..]
+            .iter()
+            .all(|message| *message == MidiMessage::Clock));
+
+        // jumped back to the second bar of the song.
+        out.handle(30_000, MidiCmd::Start { position: 16 });
+        assert_eq!(
+            sent(26),
+            [
+                MidiMessage::Stop,
+                MidiMessage::SongPosition(16),
+                MidiMessage::Continue
+            ]
+        );
+
+        out.handle(40_000, MidiCmd::SetClock(false));
+        out.tick(100_000.0, 48_000.0);
+        out.handle(100_000, MidiCmd::Stop);
+        out.handle(100_000, MidiCmd::Start { position: 0 });
+        assert_eq!(sent(29), [MidiMessage::Stop]);
+
+        let mut bytes = Vec::new();
+        MidiMessage::SongPosition(0x1234).write(&mut bytes);
+        assert_eq!(bytes, [0xF2, 0x34, 0x24]);
+    }
+
+    #[test]
+    fn controllers_glide_over_the_row() {
+        let port = MockPort::default();
+        let mut out = MidiOut::new(Some(Box::new(port.clone())), false);
+        let cc = |value: u8| MidiMessage::ControlChange {
+            channel: 2,
+            control: 74,
+            value,
+        };
+        let ramp = |at: u64, value: u8| {
+            (
+                at,
+                MidiCmd::ControlChange {
+                    midi_channel: 2,
+                    control: 74,
+                    value,
+                    glide: 1000,
+                },
+            )
+        };
+
+        // nothing to glide from, so it jumps.
+        let (at, cmd) = ramp(0, 100);
+        out.handle(at, cmd);
+        let (at, cmd) = ramp(1000, 0);
+        out.handle(at, cmd);
+
+        for now in [1000.0, 1500.0, 1500.0, 2000.0, 3000.0] {
+            out.tick(now, 48_000.0);
+        }
+
+        // set again mid glide, which starts from where the glide had got to.
+        let (at, cmd) = ramp(3000, 100);
+        out.handle(at, cmd);
+        out.tick(3500.0, 48_000.0);
+        out.handle(
+            3500,
+            MidiCmd::PitchBend {
+                midi_channel: 2,
+                value: pitch_bend(127),
+            },
+        );
+        out.handle(3500, MidiCmd::AllNotesOff);
+        out.tick(4000.0, 48_000.0);
+
+        let bend = |value: u16| MidiMessage::PitchBend { channel: 2, value };
+        let sent = port.0.lock().unwrap().clone();
+        assert_eq!(
+            sent[..6],
+            [
+                cc(100),
+                cc(50),
+                cc(0),
+                cc(50),
+                bend(0x3FFF),
+                bend(PITCH_BEND_CENTER)
+            ]
+        );
+        // the glide stopped with everything else.
+        assert_eq!(sent.len(), 6 + N_MIDI_CHANNELS as usize);
+    }
+
+    #[test]
+    fn programs_select_the_bank_first() {
+        let sent = play(vec![
+            MidiCmd::Program {
+                midi_channel: 3,
+                bank_msb: Some(1),
+                bank_lsb: Some(2),
+                program: Some(5),
+            },
+            // only the program is set.
+            MidiCmd::Program {
+                midi_channel: 4,
+                bank_msb: None,
+                bank_lsb: None,
+                program: Some(0),
+            },
+            MidiCmd::Program {
+                midi_channel: 4,
+                bank_msb: None,
+                bank_lsb: None,
+                program: None,
+            },
+        ]);
+
+        let mut bytes = Vec::new();
+        sent.iter().for_each(|message| message.write(&mut bytes));
+        assert_eq!(bytes, [0xB3, 0, 1, 0xB3, 32, 2, 0xC3, 5, 0xC4, 0]);
+    }
+
+    #[test]
+    fn parses_running_status() {
+        let mut parser = MidiParser::default();
+        let bytes = [
+            0x91, 60, 100, // note on
+            64, 0,    // running status, a note on with no velocity
+            0xFE, // active sensing, skipped
+            0xE1, 0x00, 0xF8, 0x40, // pitch bend, with a clock inside it
+            0xF0, 1, 2, 0xF7, // sysex, skipped
+            0xB1, MOD_WHEEL, 127,
+        ];
+        let parsed: Vec<_> = bytes.iter().filter_map(|byte| parser.push(*byte)).collect();
+
+        assert_eq!(
+            parsed,
+            [
+                on(1, 60),
+                off(1, 64),
+                MidiMessage::Clock,
+                MidiMessage::PitchBend {
+                    channel: 1,
+                    value: PITCH_BEND_CENTER,
+                },
+                MidiMessage::ControlChange {
+                    channel: 1,
+                    control: MOD_WHEEL,
+                    value: 127,
+                },
+            ]
+        );
+
+        let mut bytes = Vec::new();
+        parsed.iter().for_each(|message| message.write(&mut bytes));
+        assert_eq!(
+            bytes,
+            [0x91, 60, 100, 0x81, 64, 0, 0xF8, 0xE1, 0x00, 0x40, 0xB1, MOD_WHEEL, 127]
+        );
+    }
+
+    #[test]
+    fn velocity_scales_from_the_base() {
+        let params = MidiParams {
+            channel: 16,
+            velocity: 100,
+            ..default()
+        };
+
+        assert_eq!(params.midi_channel(), 15);
+        assert_eq!(params.note_velocity(1.0), 100);
+        assert_eq!(params.note_velocity(0.5), 50);
+        assert_eq!(params.note_velocity(0.0), 1);
+    }
+}
@@ -0,0 +1,279 @@
+use super::{MidiMessage, MidiSink, MidiSource};
+use crate::{
+    config::midi::{MidiInPort, MidiPort, MidiPortKind},
+    params::{Range, Value},
+};
+use alsa::{
+    device_name::HintIter,
+    rawmidi::Rawmidi,
+    seq::{Addr, ClientIter, MidiEvent, PortCap, PortInfo, PortIter, PortSubscribe, PortType, Seq},
+    Direction,
+};
+use anyhow::{anyhow, Result};
+use std::{
+    ffi::CString,
+    io::{ErrorKind, Read, Write},
+};
+
+/// the name other sequencer clients see the tracker by.
+const CLIENT_NAME: &str = "midi-tracker";
+/// the sequencer client of the system timer and announcements, which isn't a MIDI device.
+const SYSTEM_CLIENT: i32 = 0;
+
+/// the name of a sequencer port, as it is kept in the config.
+fn seq_name(seq: &Seq, port: &PortInfo) -> Option<String> {
+    let client = seq.get_any_client_info(port.get_client()).ok()?;
+
+    Some(format!(
+        "{}:{}",
+        client.get_name().ok()?,
+        port.get_name().ok()?
+    ))
+}
+
+/// the sequencer ports with the capability `caps`, other than the trackers own.
+fn seq_ports(seq: &Seq, caps: PortCap) -> Vec<(String, Addr)> {
+    let own = seq.client_id().unwrap_or(-1);
+
+    ClientIter::new(seq)
+        .map(|client| client.get_client())
+        .filter(|client| *client != own && *client != SYSTEM_CLIENT)
+        .flat_map(|client| PortIter::new(seq, client))
+        .filter(|port| port.get_capability().contains(caps))
+        .filter_map(|port| Some((seq_name(seq, &port)?, port.addr())))
+        .collect()
+}
+
+/// the capabilities a sequencer port needs to be sent to, or read from.
+fn caps(dir: Direction) -> PortCap {
+    match dir {
+        Direction::Playback => PortCap::WRITE | PortCap::SUBS_WRITE,
+        Direction::Capture => PortCap::READ | PortCap::SUBS_READ,
+    }
+}
+
+/// every port MIDI can be sent to, or read from, after the port that is no port.
+fn ports(dir: Direction) -> Vec<MidiPort> {
+    let mut ports = vec![MidiPort::default()];
+
+    if let Ok(seq) = Seq::open(None, Some(dir), false) {
+        ports.extend(
+            seq_ports(&seq, caps(dir))
+                .into_iter()
+                .map(|(name, _)| MidiPort {
+                    kind: MidiPortKind::Seq,
+                    name,
+                }),
+        );
+    }
+
+    if let Ok(hints) = HintIter::new_str(None, "rawmidi") {
+        ports.extend(
+            hints
+                .filter(|hint| hint.direction.is_none_or(|hint_dir| hint_dir == dir))
+                .filter_map(|hint| hint.name)
+                .map(|name| MidiPort {
+                    kind: MidiPortKind::Raw,
+                    name,
+                }),
+        );
+    }
+
+    ports
+}
+
+/// moves `port` through `ports`. a port that isn't plugged in stays in the list, after no port,
+/// so it isn't lost by scrolling past it.
+fn shift_port(port: &mut MidiPort, mut ports: Vec<MidiPort>, delta: i32) {
+    if !ports.contains(port) {
+        ports.insert(1, port.clone());
+    }
+
+    let i = ports.iter().position(|option| option == port).unwrap_or(0) as i32;
+    *port = ports[(i + delta.signum()).rem_euclid(ports.len() as i32) as usize].clone();
+}
+
+fn display_port(port: &MidiPort) -> String {
+    match port.kind {
+        MidiPortKind::None => "---".to_string(),
+        MidiPortKind::Seq | MidiPortKind::Raw => port.name.clone(),
+    }
+}
+
+impl Value for MidiPort {
+    fn shift(&mut self, delta: i32, _range: &Range) {
+        shift_port(self, ports(Direction::Playback), delta);
+    }
+
+    fn display(&self, _range: &Range) -> String {
+        display_port(self)
+    }
+}
+
+impl Value for MidiInPort {
+    fn shift(&mut self, delta: i32, _range: &Range) {
+        shift_port(&mut self.port, ports(Direction::Capture), delta);
+    }
+
+    fn display(&self, _range: &Range) -> String {
+        display_port(&self.port)
+    }
+}
+
+/// opens a client of the trackers own with a port named `name`, subscribed to, or from, the
+/// port named `port`. returns the client and its port.
+fn open_seq(port: &MidiPort, dir: Direction, name: &str) -> Result<(Seq, i32)> {
+    // input is read without blocking, so the port can be changed while nothing is played.
+    let seq = Seq::open(None, Some(dir), dir == Direction::Capture)?;
+    seq.set_client_name(&CString::new(CLIENT_NAME)?)?;
+
+    let (_, other) = seq_ports(&seq, caps(dir))
+        .into_iter()
+        .find(|(name, _)| *name == port.name)
+        .ok_or_else(|| anyhow!("there is no sequencer port named {}", port.name))?;
+    let own_caps = match dir {
+        Direction::Playback => PortCap::READ | PortCap::SUBS_READ,
+        Direction::Capture => PortCap::WRITE | PortCap::SUBS_WRITE,
+    };
+    let own = seq.create_simple_port(
+        &CString::new(name)?,
+        own_caps,
+        PortType::MIDI_GENERIC | PortType::APPLICATION,
+    )?;
+    let own_addr = Addr {
+        client: seq.client_id()?,
+        port: own,
+    };
+
+    let subscription = PortSubscribe::empty()?;
+
+    match dir {
+        Direction::Playback => {
+            subscription.set_sender(own_addr);
+            subscription.set_dest(other);
+        }
+        Direction::Capture => {
+            subscription.set_sender(other);
+            subscription.set_dest(own_addr);
+        }
+    }
+
+    seq.subscribe_port(&subscription)?;
+
+    Ok((seq, own))
+}
+
+/// a port of the trackers own sequencer client, subscribed to the port MIDI is sent to.
+struct SeqOut {
+    seq: Seq,
+    port: i32,
+    encoder: MidiEvent,
+    buf: Vec<u8>,
+}
+
+impl MidiSink for SeqOut {
+    fn send(&mut self, message: &MidiMessage) -> Result<()> {
+        self.buf.clear();
+        message.write(&mut self.buf);
+
+        if let (_, Some(mut event)) = self.encoder.encode(&self.buf)? {
+            event.set_source(self.port);
+            event.set_subs();
+            event.set_direct();
+            self.seq.event_output_direct(&mut event)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// an ALSA raw MIDI device.
+struct RawOut {
+    midi: Rawmidi,
+    buf: Vec<u8>,
+}
+
+impl MidiSink for RawOut {
+    fn send(&mut self, message: &MidiMessage) -> Result<()> {
+        self.buf.clear();
+        message.write(&mut self.buf);
+        self.midi.io().write_all(&self.buf)?;
+
+        Ok(())
+    }
+}
+
+/// opens `port` to send MIDI to. `None` if it is no port.
+pub fn open_output(port: &MidiPort) -> Result<Option<Box<dyn MidiSink>>> {
+    Ok(match port.kind {
+        MidiPortKind::None => None,
+        MidiPortKind::Seq => {
+            let (seq, own) = open_seq(port, Direction::Playback, "out")?;
+
+            Some(Box::new(SeqOut {
+                seq,
+                port: own,
+                encoder: MidiEvent::new(16)?,
+                buf: Vec::with_capacity(3),
+            }))
+        }
+        MidiPortKind::Raw => Some(Box::new(RawOut {
+            midi: Rawmidi::new(&port.name, Direction::Playback, false)?,
+            buf: Vec::with_capacity(3),
+        })),
+    })
+}
+
+/// a port of the trackers own sequencer client, subscribed from the port MIDI is read from.
+struct SeqIn {
+    seq: Seq,
+    decoder: MidiEvent,
+}
+
+impl MidiSource for SeqIn {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let mut input = self.seq.input();
+
+        if input.event_input_pending(true)? == 0 {
+            return Ok(0);
+        }
+
+        let mut event = input.event_input()?;
+
+        // events that aren't MIDI, like subscriptions coming and going, can't be decoded.
+        Ok(self.decoder.decode(buf, &mut event).unwrap_or(0))
+    }
+}
+
+/// an ALSA raw MIDI device, read without blocking.
+struct RawIn {
+    midi: Rawmidi,
+}
+
+impl MidiSource for RawIn {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        match self.midi.io().read(buf) {
+            Ok(n) => Ok(n),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// opens `port` to read MIDI from. `None` if it is no port.
+pub fn open_input(port: &MidiPort) -> Result<Option<Box<dyn MidiSource>>> {
+    Ok(match port.kind {
+        MidiPortKind::None => None,
+        MidiPortKind::Seq => {
+            let (seq, _) = open_seq(port, Direction::Capture, "in")?;
+
+            Some(Box::new(SeqIn {
+                seq,
+                decoder: MidiEvent::new(16)?,
+            }))
+        }
+        MidiPortKind::Raw => Some(Box::new(RawIn {
+            midi: Rawmidi::new(&port.name, Direction::Capture, true)?,
+        })),
+    })
+}
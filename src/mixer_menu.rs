@@ -0,0 +1,242 @@
+use crate::{
+    controls::MyGamepad,
+    pygame_coms::{DisplayCursor, Song},
+    sequencer::N_CHANNELS,
+    tracker_state::StateUpdated,
+    ExitMenuState, ScreenState,
+};
+use bevy::{log::*, prelude::*};
+
+/// how often, in seconds, the levels on the mixer screen are redrawn.
+const LEVELS_REFRESH: f32 = 0.05;
+
+/// the cursor of the screen the mixer screen was entered from.
+#[derive(Debug, Clone, Default, Resource)]
+struct ReturnCursor(DisplayCursor);
+
+pub struct MixerMenuPlugin;
+
+impl Plugin for MixerMenuPlugin {
+    fn build(&self, app: &mut App) {
+        debug!("tracker_backend::mixer_menu::MixerMenuPlugin loaded");
+
+        app.init_resource::<ReturnCursor>()
+            .add_event::<EditParam>()
+            .add_systems(
+                Update,
+                movement
+                    .run_if(in_state(ScreenState::EditMixer))
+                    .run_if(not(in_state(ExitMenuState::Opened))),
+            )
+            .add_systems(
+                Update,
+                set_select
+                    .run_if(in_state(ScreenState::EditMixer))
+                    .run_if(not(in_state(ExitMenuState::Opened))),
+            )
+            .add_systems(
+                Update,
+                change_param
+                    .run_if(in_state(ScreenState::EditMixer))
+                    .run_if(not(in_state(ExitMenuState::Opened))),
+            )
+            .add_systems(
+                Update,
+                edit_param
+                    .run_if(in_state(ScreenState::EditMixer))
+                    .run_if(not(in_state(ExitMenuState::Opened))),
+            )
+            .add_systems(
+                Update,
+                refresh_levels.run_if(in_state(ScreenState::EditMixer)),
+            )
+            .add_systems(OnEnter(ScreenState::EditMixer), set_selected)
+            .add_systems(
+                OnEnter(ScreenState::EditMixer),
+                (save_cursor, set_cursor).chain(),
+            )
+            .add_systems(OnExit(ScreenState::EditMixer), restore_cursor);
+    }
+}
+
+#[derive(Event, Debug, Default)]
+struct EditParam {
+    /// how many steps to move the parameter under the cursor by.
+    delta: i32,
+}
+
+fn set_selected(mut display_cursor: ResMut<DisplayCursor>) {
+    display_cursor.selected = false;
+}
+
+fn save_cursor(display_cursor: Res<DisplayCursor>, mut return_cursor: ResMut<ReturnCursor>) {
+    return_cursor.0 = display_cursor.clone();
+}
+
+fn restore_cursor(mut display_cursor: ResMut<DisplayCursor>, return_cursor: Res<ReturnCursor>) {
+    display_cursor.row = return_cursor.0.row;
+    display_cursor.col = return_cursor.0.col;
+}
+
+fn set_cursor(mut display_cursor: ResMut<DisplayCursor>) {
+    display_cursor.row = 0;
+    display_cursor.col = 0;
+}
+
+/// the levels change all the time, so the screen is redrawn while it's open.
+fn refresh_levels(
+    time: Res<Time>,
+    mut since_refresh: Local<f32>,
+    mut state_updated: EventWriter<StateUpdated>,
+) {
+    *since_refresh += time.delta_seconds();
+
+    if *since_refresh >= LEVELS_REFRESH {
+        *since_refresh = 0.0;
+        state_updated.send_default();
+    }
+}
+
+fn set_select(
+    mut display_cursor: ResMut<DisplayCursor>,
+    mut state_updated: EventWriter<StateUpdated>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    my_gamepad: Option<Res<MyGamepad>>,
+) {
+    let Some(&MyGamepad(gamepad)) = my_gamepad.as_deref() else {
+        // no gamepad is connected
+        return;
+    };
+
+    let a_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::East,
+    };
+
+    if buttons.pressed(a_button) != display_cursor.selected {
+        display_cursor.selected = buttons.pressed(a_button);
+        state_updated.send_default();
+    }
+}
+
+fn change_param(
+    display_cursor: Res<DisplayCursor>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    my_gamepad: Option<Res<MyGamepad>>,
+    mut edit_param_event: EventWriter<EditParam>,
+) {
+    let Some(&MyGamepad(gamepad)) = my_gamepad.as_deref() else {
+        // no gamepad is connected
+        return;
+    };
+
+    let button = |button_type| GamepadButton {
+        gamepad,
+        button_type,
+    };
+
+    if !buttons.pressed(button(GamepadButtonType::East)) || !display_cursor.selected {
+        return;
+    }
+
+    for (button_type, delta) in [
+        (GamepadButtonType::DPadUp, 1),
+        (GamepadButtonType::DPadDown, -1),
+        (GamepadButtonType::DPadRight, 10),
+        (GamepadButtonType::DPadLeft, -10),
+    ] {
+        if buttons.just_released(button(button_type)) {
+            edit_param_event.send(EditParam { delta });
+        }
+    }
+}
+
+fn edit_param(
+    mut song: ResMut<Song>,
+    display_cursor: Res<DisplayCursor>,
+    mut events: EventReader<EditParam>,
+    mut state_updated: EventWriter<StateUpdated>,
+) {
+    for ev in events.read() {
+        if let Some(param) = song
+            .mixer
+            .params(display_cursor.col)
+            .get_mut(display_cursor.row)
+        {
+            param.shift(ev.delta);
+            state_updated.send_default();
+        } else {
+            error!(
+                "row {} is past the last parameter of mixer column {}",
+                display_cursor.row, display_cursor.col
+            );
+        }
+    }
+}
+
+fn movement(
+    buttons: Res<ButtonInput<GamepadButton>>,
+    my_gamepad: Option<Res<MyGamepad>>,
+    mut display_cursor: ResMut<DisplayCursor>,
+    mut state_updated: EventWriter<StateUpdated>,
+    gamepads: Res<Gamepads>,
+    song: Res<Song>,
+) {
+    if display_cursor.selected {
+        return;
+    }
+
+    let Some(&MyGamepad(gamepad)) = my_gamepad.as_deref() else {
+        // no gamepad is connected
+        return;
+    };
+
+    let button = |button_type| GamepadButton {
+        gamepad,
+        button_type,
+    };
+
+    let start_button = if let Some(name) = gamepads.name(gamepad)
+        && name.starts_with("PS5")
+    {
+        button(GamepadButtonType::Start)
+    } else {
+        button(GamepadButtonType::Select)
+    };
+
+    if buttons.pressed(start_button) {
+        return;
+    }
+
+    // a column for each channel and one for the master.
+    let n_cols = N_CHANNELS + 1;
+    let mut col = display_cursor.col;
+    let mut row = display_cursor.row;
+
+    if buttons.just_released(button(GamepadButtonType::DPadLeft)) {
+        col = (col + n_cols - 1) % n_cols;
+    }
+
+    if buttons.just_released(button(GamepadButtonType::DPadRight)) {
+        col = (col + 1) % n_cols;
+    }
+
+    let mut mixer = song.mixer;
+    let n_rows = mixer.params(col).len();
+
+    if buttons.just_released(button(GamepadButtonType::DPadUp)) {
+        row = (row + n_rows - 1) % n_rows;
+    }
+
+    if buttons.just_released(button(GamepadButtonType::DPadDown)) {
+        row = (row + 1) % n_rows;
+    }
+
+    row = row.min(n_rows - 1);
+
+    if (col, row) != (display_cursor.col, display_cursor.row) {
+        display_cursor.col = col;
+        display_cursor.row = row;
+        state_updated.send_default();
+    }
+}
@@ -0,0 +1,270 @@
+use pyo3::{
+    exceptions::PyValueError, Bound, FromPyObject, IntoPy, PyAny, PyObject, PyResult, Python,
+};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// the longest parameter name, in bytes, a `ParamName` can hold.
+pub const MAX_NAME_LEN: usize = 16;
+
+/// the limits and step size of a parameter.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Range {
+    pub min: f32,
+    pub max: f32,
+    pub step: f32,
+}
+
+impl Range {
+    pub const fn new(min: f32, max: f32, step: f32) -> Self {
+        Self { min, max, step }
+    }
+
+    /// the value `fraction` of the way from `min` to `max`, on a step.
+    fn at(&self, fraction: f32) -> f32 {
+        let steps = ((self.max - self.min) / self.step * fraction.clamp(0.0, 1.0)).round();
+
+        (self.min + steps * self.step).clamp(self.min, self.max)
+    }
+}
+
+/// a value that can be edited one step at a time from one of the menus.
+pub trait Value {
+    /// moves the value by `delta` steps, keeping it inside of `range`.
+    fn shift(&mut self, delta: i32, range: &Range);
+
+    /// the value as it should be displayed to the user.
+    fn display(&self, range: &Range) -> String;
+
+    /// sets the value to `fraction` of the way through `range`, for a knob on a MIDI controller.
+    /// values that aren't on a scale, like ports and files, are left as they are.
+    fn set_fraction(&mut self, _fraction: f32, _range: &Range) {}
+}
+
+/// a value that is one of a fixed set of options, like an enum.
+pub trait Choice: Copy + PartialEq + Sized + 'static {
+    /// every option, in the order they are scrolled through.
+    const ALL: &'static [Self];
+
+    /// a short name for the option to display.
+    fn name(&self) -> &'static str;
+}
+
+impl<T: Choice> Value for T {
+    fn shift(&mut self, delta: i32, _range: &Range) {
+        let n = T::ALL.len() as i32;
+        let i = T::ALL.iter().position(|option| option == self).unwrap_or(0) as i32;
+
+        *self = T::ALL[(i + delta.signum()).rem_euclid(n) as usize];
+    }
+
+    fn display(&self, _range: &Range) -> String {
+        self.name().to_string()
+    }
+
+    fn set_fraction(&mut self, fraction: f32, _range: &Range) {
+        let last = T::ALL.len() - 1;
+
+        *self = T::ALL[(fraction.clamp(0.0, 1.0) * last as f32).round() as usize];
+    }
+}
+
+impl Value for f32 {
+    fn shift(&mut self, delta: i32, range: &Range) {
+        *self = (*self + delta as f32 * range.step).clamp(range.min, range.max);
+    }
+
+    fn display(&self, range: &Range) -> String {
+        if range.step >= 1.0 {
+            format!("{self:.0}")
+        } else if range.step >= 0.1 {
+            format!("{self:.1}")
+        } else if range.step >= 0.01 {
+            format!("{self:.2}")
+        } else {
+            format!("{self:.3}")
+        }
+    }
+
+    fn set_fraction(&mut self, fraction: f32, range: &Range) {
+        *self = range.at(fraction);
+    }
+}
+
+impl Value for u8 {
+    fn shift(&mut self, delta: i32, range: &Range) {
+        *self = (*self as i32 + delta * range.step as i32).clamp(range.min as i32, range.max as i32)
+            as u8;
+    }
+
+    fn display(&self, _range: &Range) -> String {
+        format!("{self:02X}")
+    }
+
+    fn set_fraction(&mut self, fraction: f32, range: &Range) {
+        *self = range.at(fraction) as u8;
+    }
+}
+
+/// `None` is one step below the bottom of the range.
+impl Value for Option<u8> {
+    fn shift(&mut self, delta: i32, range: &Range) {
+        *self = match *self {
+            Some(value) if value as i32 + delta * (range.step as i32) < range.min as i32 => None,
+            Some(mut value) => {
+                value.shift(delta, range);
+                Some(value)
+            }
+            None if delta > 0 => Some(range.min as u8),
+            None => None,
+        };
+    }
+
+    fn display(&self, range: &Range) -> String {
+        self.map_or("---".to_string(), |value| value.display(range))
+    }
+
+    fn set_fraction(&mut self, fraction: f32, range: &Range) {
+        // `None` takes the step below the range.
+        let below = Range::new(range.min - range.step, range.max, range.step);
+        let value = below.at(fraction);
+
+        *self = (value >= range.min).then_some(value as u8);
+    }
+}
+
+impl Value for i8 {
+    fn shift(&mut self, delta: i32, range: &Range) {
+        *self = (*self as i32 + delta * range.step as i32).clamp(range.min as i32, range.max as i32)
+            as i8;
+    }
+
+    fn display(&self, _range: &Range) -> String {
+        format!("{self:+}")
+    }
+
+    fn set_fraction(&mut self, fraction: f32, range: &Range) {
+        *self = range.at(fraction) as i8;
+    }
+}
+
+impl Value for bool {
+    fn shift(&mut self, _delta: i32, _range: &Range) {
+        *self = !*self;
+    }
+
+    fn display(&self, _range: &Range) -> String {
+        if *self { "ON" } else { "OFF" }.to_string()
+    }
+
+    fn set_fraction(&mut self, fraction: f32, _range: &Range) {
+        *self = fraction >= 0.5;
+    }
+}
+
+/// a named, editable, value. a menu is made of a list of these.
+pub struct Param<'a> {
+    pub name: &'static str,
+    pub value: &'a mut dyn Value,
+    pub range: Range,
+}
+
+impl<'a> Param<'a> {
+    pub fn new(name: &'static str, value: &'a mut dyn Value, range: Range) -> Self {
+        Self { name, value, range }
+    }
+
+    /// a parameter that has no numeric range, like a `Choice` or a `bool`.
+    pub fn choice(name: &'static str, value: &'a mut dyn Value) -> Self {
+        Self::new(name, value, Range::new(0.0, 1.0, 1.0))
+    }
+
+    pub fn shift(&mut self, delta: i32) {
+        self.value.shift(delta, &self.range);
+    }
+
+    pub fn set_fraction(&mut self, fraction: f32) {
+        self.value.set_fraction(fraction, &self.range);
+    }
+
+    /// the name and value, formatted for display.
+    pub fn row(&self) -> (String, String) {
+        (self.name.to_string(), self.value.display(&self.range))
+    }
+}
+
+/// the name of a parameter, kept inline so what holds one can stay `Copy`. it finds the
+/// parameter again when the rows around it change.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ParamName {
+    len: u8,
+    bytes: [u8; MAX_NAME_LEN],
+}
+
+impl ParamName {
+    /// `None` if `name` is longer than `MAX_NAME_LEN`.
+    pub fn new(name: &str) -> Option<Self> {
+        let mut bytes = [0; MAX_NAME_LEN];
+        bytes
+            .get_mut(..name.len())?
+            .copy_from_slice(name.as_bytes());
+
+        Some(Self {
+            len: name.len() as u8,
+            bytes,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        // only ever made from a `&str`.
+        std::str::from_utf8(&self.bytes[..self.len as usize]).unwrap_or_default()
+    }
+}
+
+impl PartialEq<&str> for ParamName {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Debug for ParamName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+impl fmt::Display for ParamName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+impl Serialize for ParamName {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for ParamName {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+
+        Self::new(&name)
+            .ok_or_else(|| de::Error::custom(format!("parameter name too long: {name}")))
+    }
+}
+
+impl IntoPy<PyObject> for ParamName {
+    fn into_py(self, py: Python<'_>) -> PyObject {
+        self.as_str().into_py(py)
+    }
+}
+
+impl<'py> FromPyObject<'py> for ParamName {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        let name = String::extract_bound(ob)?;
+
+        Self::new(&name)
+            .ok_or_else(|| PyValueError::new_err(format!("parameter name too long: {name}")))
+    }
+}
@@ -103,8 +103,8 @@ impl Default for EditInst {
 struct EditCmd {
     /// true when changing the command, false when changing the args
     change_cmd: bool,
-    /// true when shifting up, false when shifting down
-    up: bool,
+    /// positive when shifting up, negative when shifting down, zero when only placing a command
+    delta: i8,
 }
 
 // fn log_phrase_data(phrases: Res<AllPhrases>, screen: Res<Screen>) {
@@ -244,7 +244,7 @@ fn change_entry(
             // send edit command event
             edit_cmd_event.send(EditCmd {
                 change_cmd: true,
-                up: true,
+                delta: 1,
             });
         } else {
             error!("column set to value that is too high for the phrases tab.");
@@ -267,7 +267,7 @@ fn change_entry(
             // send edit command event
             edit_cmd_event.send(EditCmd {
                 change_cmd: false,
-                up: true,
+                delta: 1,
             });
         } else {
             error!("column set to value that is too high for the phrases tab.");
@@ -290,7 +290,7 @@ fn change_entry(
             // send edit command event
             edit_cmd_event.send(EditCmd {
                 change_cmd: true,
-                up: false,
+                delta: -1,
             });
         } else {
             error!("column set to value that is too high for the phrases tab.");
@@ -313,7 +313,7 @@ fn change_entry(
             // send edit command event
             edit_cmd_event.send(EditCmd {
                 change_cmd: false,
-                up: false,
+                delta: -1,
             });
         } else {
             error!("column set to value that is too high for the phrases tab.");
@@ -423,25 +423,25 @@ fn edit_cmd(
 ) {
     let phrase_i = phrase_index.0;
 
-    for _ev in events.read() {
+    for ev in events.read() {
         debug!("editing of commands");
 
         if let Some(Some(ref mut phrase)) = phrases.0.get_mut(phrase_i) {
-            if let Some(ref mut _cmd) = phrase.rows[display_cursor.row].command {
-                // if (ev.delta > 0 && ev.delta as Note <= 128 - *note)
-                //     || (ev.delta < 0 && (ev.delta.abs() as Note) < *note)
-                // {
-                //     if ev.delta > 0 {
-                //         *note += ev.delta as Note;
-                //     } else {
-                //         *note -= ev.delta.abs() as Note;
-                //     }
-                //     last_added.note = *note;
-                //     state_updated.0 = true;
-                // } else {
-                //     warn!("not changing note.");
-                // }
-                warn!("TODO: edit command");
+            if let Some(ref mut cmd) = phrase.rows[display_cursor.row].command {
+                if ev.delta == 0 {
+                    continue;
+                }
+
+                if ev.change_cmd && ev.delta > 0 {
+                    *cmd = cmd.next();
+                } else if ev.change_cmd {
+                    *cmd = cmd.prev();
+                } else {
+                    cmd.shift_arg(ev.delta > 0);
+                }
+
+                last_added.command = *cmd;
+                state_updated.send_default();
             } else {
                 phrase.rows[display_cursor.row].command = Some(last_added.command);
                 // state_updated.0 = true;
@@ -0,0 +1,321 @@
+use crate::{
+    config::{self, move_aside, ui::Bpm},
+    pygame_coms::{Chain, Instrument, Instruments, Phrase, Song},
+    synth::wavetable::Wavetable,
+    tracker_state::{load_wavetables, AllChains, AllInstruments, AllPhrases, AllWavetables, Tempo},
+};
+use anyhow::Result;
+use bevy::{log::*, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+pub struct ProjectPlugin;
+
+impl Plugin for ProjectPlugin {
+    fn build(&self, app: &mut App) {
+        debug!("tracker_backend::project::ProjectPlugin loaded");
+
+        app.insert_resource(SaveOnExit(true))
+            // so the wavetable files are loaded into the slots the drawn tables leave free.
+            .add_systems(Startup, load_project.before(load_wavetables))
+            .add_systems(Last, save_project.run_if(on_event::<AppExit>()));
+    }
+}
+
+/// false if the project file failed to load and couldn't be moved aside, so it isn't saved over.
+#[derive(Debug, Clone, Copy, Resource)]
+struct SaveOnExit(bool);
+
+/// a song and everything it plays, as it is saved. what is missing from a file, like settings
+/// added since it was saved, is left at its default.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Project {
+    pub tempo: Bpm,
+    pub song: Song,
+    /// the chains and phrases by index, `None` where there isn't one.
+    pub chains: Vec<Option<Chain>>,
+    pub phrases: Vec<Option<Phrase>>,
+    /// with their LFOs, modulation matrices, and the rest of their settings.
+    pub instruments: Instruments,
+    /// the drawn wavetables by index. tables loaded from files are loaded from them again.
+    pub wavetables: Vec<Option<Wavetable>>,
+}
+
+impl Default for Project {
+    fn default() -> Self {
+        Self {
+            tempo: 120,
+            song: Song::default(),
+            chains: vec![None; 256],
+            phrases: vec![None; 256],
+            instruments: AllInstruments::default().0,
+            wavetables: drawn(&AllWavetables::default()),
+        }
+    }
+}
+
+impl Project {
+    pub fn load(path: &Path) -> Result<Self> {
+        config::load(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        config::save(self, path)
+    }
+
+    /// the instruments to play the project with. instruments past the last index are dropped, and
+    /// the default instruments, and any others the song plays by default, are put back if the file
+    /// doesn't have them.
+    fn instruments(&self) -> Instruments {
+        let mut instruments = self.instruments.clone();
+        instruments.truncate(256);
+
+        for (i, default) in AllInstruments::default().0.into_iter().enumerate() {
+            if instruments.len() <= i {
+                instruments.resize(i + 1, None);
+            }
+
+            if instruments[i].is_none() {
+                instruments[i] = default;
+            }
+        }
+
+        for &i in &self.song.default_instrument {
+            if instruments.len() <= i {
+                instruments.resize(i + 1, None);
+            }
+
+            instruments[i].get_or_insert_with(|| Instrument::new(i));
+        }
+
+        instruments
+    }
+}
+
+/// the wavetables that were drawn, rather than loaded from a file.
+fn drawn(wavetables: &AllWavetables) -> Vec<Option<Wavetable>> {
+    wavetables
+        .0
+        .iter()
+        .map(|table| table.as_deref().filter(|table| table.editable()).cloned())
+        .collect()
+}
+
+/// the file the project is kept in between runs.
+pub fn project_path() -> PathBuf {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join(".local/share/midi-tracker/project.ron")
+}
+
+/// picks up where the last run left off.
+fn load_project(
+    mut tempo: ResMut<Tempo>,
+    mut song: ResMut<Song>,
+    mut chains: ResMut<AllChains>,
+    mut phrases: ResMut<AllPhrases>,
+    mut instruments: ResMut<AllInstruments>,
+    mut wavetables: ResMut<AllWavetables>,
+    mut save_on_exit: ResMut<SaveOnExit>,
+) {
+    let path = project_path();
+
+    if !path.exists() {
+        return;
+    }
+
+    let project = match Project::load(&path) {
+        Ok(project) => project,
+        Err(e) => {
+            error!("failed to load the project from {}: {e}", path.display());
+
+            match move_aside(&path) {
+                Ok(moved) => warn!(
+                    "moved the project that failed to load to {}",
+                    moved.display()
+                ),
+                Err(e) => {
+                    error!(
+                        "failed to move {} aside, so it won't be saved over: {e}",
+                        path.display()
+                    );
+                    save_on_exit.0 = false;
+                }
+            }
+
+            return;
+        }
+    };
+
+    instruments.0 = project.instruments();
+    tempo.0 = project.tempo;
+    *song = project.song;
+    chains.0 = [None; 256];
+    phrases.0 = [None; 256];
+
+    for (kept, loaded) in chains.0.iter_mut().zip(project.chains) {
+        *kept = loaded;
+    }
+
+    for (kept, loaded) in phrases.0.iter_mut().zip(project.phrases) {
+        *kept = loaded;
+    }
+
+    wavetables.0 = vec![None; 256];
+
+    for (kept, loaded) in wavetables.0.iter_mut().zip(project.wavetables) {
+        *kept = loaded.map(Arc::new);
+    }
+
+    info!("loaded the project from {}", path.display());
+}
+
+/// keeps the project for the next run, on the way out.
+fn save_project(
+    tempo: Res<Tempo>,
+    song: Res<Song>,
+    chains: Res<AllChains>,
+    phrases: Res<AllPhrases>,
+    instruments: Res<AllInstruments>,
+    wavetables: Res<AllWavetables>,
+    save_on_exit: Res<SaveOnExit>,
+) {
+    if !save_on_exit.0 {
+        return;
+    }
+
+    let path = project_path();
+    let project = Project {
+        tempo: tempo.0,
+        song: *song,
+        chains: chains.0.to_vec(),
+        phrases: phrases.0.to_vec(),
+        instruments: instruments.0.clone(),
+        wavetables: drawn(&wavetables),
+    };
+
+    match project.save(&path) {
+        Ok(()) => info!("saved the project to {}", path.display()),
+        Err(e) => error!("failed to save the project to {}: {e}", path.display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        midi::learn::{MidiMapping, ParamTarget},
+        params::ParamName,
+        pygame_coms::{InstrumentOutput, PhraseRow, TrackerCommand},
+        synth::{
+            lfo::LfoShape,
+            mod_matrix::{ModDest, ModSlot, ModSource},
+            sampler::{sample_dir, SamplerMode},
+        },
+    };
+    use std::fs;
+
+    #[test]
+    fn a_project_is_loaded_as_it_was_saved() {
+        let mut project = Project {
+            tempo: 96,
+            ..Project::default()
+        };
+
+        project.song.rows[0].lead_1 = Some(3);
+        project.song.mixer.channels[1].pan = -0.5;
+        project.song.mixer.channels[3].mute = true;
+        project.song.effects.reverb_mix = 0.25;
+        project.song.mappings[1] = Some(MidiMapping {
+            channel: 9,
+            control: 0x4A,
+            target: ParamTarget::Instrument(0, ParamName::new("CUTOFF").unwrap()),
+        });
+        project.chains[3] = Some(Chain::default());
+        project.chains[3].as_mut().unwrap().rows[0].phrase = Some(7);
+        project.phrases[7] = Some(Phrase::default());
+        project.phrases[7].as_mut().unwrap().rows[2] = PhraseRow {
+            note: Some(60),
+            instrument: Some(1),
+            command: Some(TrackerCommand::Volume(0.5)),
+        };
+
+        let instrument = project.instruments[0].as_mut().unwrap();
+        instrument.lfos[1].shape = LfoShape::SampleHold;
+        instrument.lfos[1].sync = true;
+        instrument.mod_matrix.slots[0] = ModSlot {
+            source: ModSource::Lfo2,
+            dest: ModDest::Cutoff,
+            amount: -0.75,
+        };
+
+        // samples are kept by their path, and loaded from it again.
+        let instrument = project.instruments[1].as_mut().unwrap();
+        instrument.output = InstrumentOutput::Sampler;
+        instrument.sampler.sample = Some(sample_dir().join("breaks/amen.wav"));
+        instrument.sampler.mode = SamplerMode::Slice;
+        instrument.sampler.reverse = true;
+
+        // so the song picks the same patches on the synths it plays.
+        let instrument = project.instruments[2].as_mut().unwrap();
+        instrument.output = InstrumentOutput::UsbMidi;
+        instrument.midi.bank_msb = Some(2);
+        instrument.midi.bank_lsb = None;
+        instrument.midi.program = Some(0x41);
+
+        // drawn wavetables are kept with every frame.
+        let mut table = Wavetable::new(4);
+        table.frames.push(vec![0.5, -0.25, 0.125, 0.0]);
+        project.wavetables[4] = Some(table);
+
+        let path = std::env::temp_dir().join(format!("midi-tracker-{}.ron", std::process::id()));
+        project.save(&path).unwrap();
+        let loaded = Project::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.unwrap(), project);
+    }
+
+    #[test]
+    fn what_a_file_is_missing_is_left_at_its_default() {
+        let project: Project =
+            ron::from_str("(tempo: 90, instruments: [None, Some((name: 1))])").unwrap();
+
+        assert_eq!(project.tempo, 90);
+        assert_eq!(project.song, Song::default());
+        assert_eq!(project.chains.len(), 256);
+
+        // only what the file has is changed.
+        let instruments = project.instruments();
+        assert_eq!(instruments.len(), 3);
+        assert_eq!(instruments[0], AllInstruments::default().0[0]);
+        assert_eq!(
+            instruments[1].as_ref().unwrap().filter,
+            Instrument::new(1).filter
+        );
+    }
+
+    #[test]
+    fn a_file_that_failed_to_load_is_moved_aside() {
+        let dir = std::env::temp_dir().join(format!("midi-tracker-aside-{}", std::process::id()));
+        let path = dir.join("project.ron");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&path, "(tempo: \"fast\")").unwrap();
+        fs::write(dir.join("project.ron.bak"), "").unwrap();
+
+        assert!(Project::load(&path).is_err());
+        let moved = move_aside(&path).unwrap();
+        let kept = fs::read_to_string(&moved);
+        let still_there = path.exists();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(moved, dir.join("project.ron.1.bak"));
+        assert_eq!(kept.unwrap(), "(tempo: \"fast\")");
+        assert!(!still_there);
+    }
+}
@@ -1,8 +1,35 @@
-use crate::config::ui::Bpm;
+use crate::{
+    config::{audio::AudioConfig, midi::MidiConfig, ui::Bpm},
+    midi::{
+        learn::{MidiMapping, N_SONG_MAPPINGS},
+        MidiParams, N_CC_SLOTS,
+    },
+    params::{Choice, Param},
+    synth::{
+        drums::DrumKit,
+        dynamics::Dynamics,
+        effects::Effects,
+        envelope::{Adsr, ModEnvelope},
+        filter::{FilterParams, MAX_CUTOFF},
+        fm::FmParams,
+        gameboy::GbParams,
+        inserts::Inserts,
+        lfo::{LfoParams, N_LFOS},
+        mixer::{Mixer, MixerLevels},
+        mod_matrix::ModMatrix,
+        sampler::SamplerParams,
+        scope::Scope,
+        voices::Polyphony,
+        wavetable::Wavetable,
+        SynthParams,
+    },
+};
 use bevy::prelude::{Component, Resource};
-use pyo3::pyclass;
+use pyo3::{pyclass, pymethods, Bound, FromPyObject, IntoPy, PyAny, PyObject, PyResult, Python};
+use serde::{Deserialize, Serialize};
 use std::{
-    ops::{Index as IndexInto, IndexMut},
+    mem::discriminant,
+    ops::{Deref, Index as IndexInto, IndexMut},
     sync::{Arc, Mutex},
 };
 
@@ -46,11 +73,109 @@ pub type Instruments = Vec<Option<Instrument>>;
 /// an index into a list of all known type T
 pub type Index = usize;
 
+/// the shape of a chord stacked on top of a phrase rows note.
+#[pyclass(module = "tracker_backend", eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ChordShape {
+    Major,
+    Minor,
+    Sus2,
+    Sus4,
+    Major7,
+    Minor7,
+    Dom7,
+}
+
+impl ChordShape {
+    const ALL: [ChordShape; 7] = [
+        Self::Major,
+        Self::Minor,
+        Self::Sus2,
+        Self::Sus4,
+        Self::Major7,
+        Self::Minor7,
+        Self::Dom7,
+    ];
+
+    /// the intervals, in semitones above the root, of each note in the chord.
+    pub fn intervals(&self) -> &'static [Note] {
+        match self {
+            Self::Major => &[0, 4, 7],
+            Self::Minor => &[0, 3, 7],
+            Self::Sus2 => &[0, 2, 7],
+            Self::Sus4 => &[0, 5, 7],
+            Self::Major7 => &[0, 4, 7, 11],
+            Self::Minor7 => &[0, 3, 7, 10],
+            Self::Dom7 => &[0, 4, 7, 10],
+        }
+    }
+
+    /// builds the chord on top of `root`. the lowest `inversion` notes are moved up an octave.
+    pub fn voicing(&self, root: Note, inversion: u8) -> Vec<Note> {
+        let intervals = self.intervals();
+        let inversion = inversion as usize % intervals.len();
+
+        let mut notes: Vec<Note> = intervals
+            .iter()
+            .enumerate()
+            .map(|(i, interval)| {
+                let octave = if i < inversion { 12 } else { 0 };
+
+                root.saturating_add(interval + octave).min(127)
+            })
+            .collect();
+        notes.sort();
+        notes.dedup();
+
+        notes
+    }
+
+    pub fn next(&self) -> Self {
+        let i = Self::ALL
+            .iter()
+            .position(|shape| shape == self)
+            .unwrap_or(0);
+
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    pub fn prev(&self) -> Self {
+        let i = Self::ALL
+            .iter()
+            .position(|shape| shape == self)
+            .unwrap_or(0);
+
+        Self::ALL[(i + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+}
+
 /// a command used in the a Phrase
 #[pyclass(module = "tracker_backend", get_all)]
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub enum TrackerCommand {
+    /// on a row with notes, the velocity they play at. on a row without, fades the notes held on
+    /// the channel to this gain, till the next row with notes.
     Volume(f32),
+    /// plays a chord on top of the rows note, holds the chord shape and the inversion.
+    Chord(ChordShape, u8),
+    /// releases the notes held on the channel.
+    NoteOff(),
+    /// sets the filter cutoff, as a MIDI note, of the notes playing on the channel.
+    Cutoff(u8),
+    /// sets how much of the channel is sent to the delay, it holds till playback stops.
+    DelaySend(f32),
+    /// sets how much of the channel is sent to the reverb, it holds till playback stops.
+    ReverbSend(f32),
+    /// sets a controller of a `InstrumentOutput::UsbMidi` instrument, holds the CC slot of the
+    /// instrument and the value.
+    MidiCc(u8, u8),
+    /// glides a controller of a `InstrumentOutput::UsbMidi` instrument to the value over the row,
+    /// holds the CC slot of the instrument and the value. ramps on rows one after another make a
+    /// longer ramp.
+    MidiCcRamp(u8, u8),
+    /// bends the notes of a `InstrumentOutput::UsbMidi` instrument, holds the top 7 bits of the
+    /// pitch bend, 64 doesn't bend.
+    PitchBend(u8),
 }
 
 impl Default for TrackerCommand {
@@ -59,21 +184,155 @@ impl Default for TrackerCommand {
     }
 }
 
-#[pyclass(module = "tracker_backend", get_all)]
-#[derive(Debug, Clone, PartialEq, Eq, Ord, PartialOrd)]
+impl TrackerCommand {
+    /// every kind of command, in the order they are scrolled through when editing a phrase. each
+    /// CC slot is a kind of its own.
+    fn kinds() -> Vec<TrackerCommand> {
+        let mut kinds = vec![
+            Self::Volume(1.0),
+            Self::Chord(ChordShape::Major, 0),
+            Self::NoteOff(),
+            Self::Cutoff(MAX_CUTOFF as u8),
+            Self::DelaySend(0.0),
+            Self::ReverbSend(0.0),
+        ];
+        kinds.extend((0..N_CC_SLOTS as u8).map(|slot| Self::MidiCc(slot, 0)));
+        kinds.extend((0..N_CC_SLOTS as u8).map(|slot| Self::MidiCcRamp(slot, 0)));
+        kinds.push(Self::PitchBend(64));
+
+        kinds
+    }
+
+    fn kind_index(&self) -> usize {
+        Self::kinds()
+            .iter()
+            .position(|kind| match (kind, self) {
+                (Self::MidiCc(slot, _), Self::MidiCc(other, _))
+                | (Self::MidiCcRamp(slot, _), Self::MidiCcRamp(other, _)) => slot == other,
+                _ => discriminant(kind) == discriminant(self),
+            })
+            .unwrap_or(0)
+    }
+
+    /// the next kind of command, used when scrolling up through commands.
+    pub fn next(&self) -> Self {
+        let kinds = Self::kinds();
+
+        kinds[(self.kind_index() + 1) % kinds.len()]
+    }
+
+    /// the previous kind of command, used when scrolling down through commands.
+    pub fn prev(&self) -> Self {
+        let kinds = Self::kinds();
+
+        kinds[(self.kind_index() + kinds.len() - 1) % kinds.len()]
+    }
+
+    /// shifts the argument of the command one step up or down.
+    pub fn shift_arg(&mut self, up: bool) {
+        match self {
+            Self::Volume(vol) | Self::DelaySend(vol) | Self::ReverbSend(vol) => {
+                let step = if up { 1.0 } else { -1.0 };
+                *vol = ((*vol * 15.0).round() + step).clamp(0.0, 15.0) / 15.0;
+            }
+            Self::Chord(shape, inversion) => {
+                if up {
+                    *inversion += 1;
+
+                    if *inversion as usize >= shape.intervals().len() {
+                        *inversion = 0;
+                        *shape = shape.next();
+                    }
+                } else if *inversion > 0 {
+                    *inversion -= 1;
+                } else {
+                    *shape = shape.prev();
+                    *inversion = shape.intervals().len() as u8 - 1;
+                }
+            }
+            Self::NoteOff() => {}
+            Self::Cutoff(cutoff) => {
+                *cutoff = if up {
+                    cutoff.saturating_add(1).min(MAX_CUTOFF as u8)
+                } else {
+                    cutoff.saturating_sub(1)
+                };
+            }
+            Self::MidiCc(_, value) | Self::MidiCcRamp(_, value) | Self::PitchBend(value) => {
+                *value = if up {
+                    value.saturating_add(1).min(127)
+                } else {
+                    value.saturating_sub(1)
+                };
+            }
+        }
+    }
+}
+
+#[pyclass(module = "tracker_backend", eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Ord, PartialOrd, Serialize, Deserialize)]
 pub enum InstrumentOutput {
     UsbMidi,
     // InternalMidi,
     Synth,
     Percusion,
+    Sampler,
+    Fm,
+    GameBoy,
+}
+
+impl Choice for InstrumentOutput {
+    const ALL: &'static [Self] = &[
+        Self::UsbMidi,
+        Self::Synth,
+        Self::Percusion,
+        Self::Sampler,
+        Self::Fm,
+        Self::GameBoy,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::UsbMidi => "USB MIDI",
+            Self::Synth => "SYNTH",
+            Self::Percusion => "PERC",
+            Self::Sampler => "SAMPLER",
+            Self::Fm => "FM",
+            Self::GameBoy => "GB",
+        }
+    }
 }
 
 #[pyclass(module = "tracker_backend", get_all)]
-#[derive(Debug, Clone, PartialEq, Eq, Ord, PartialOrd)]
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Instrument {
     pub output: InstrumentOutput,
     pub human_name: String,
     pub name: Index,
+    /// parameters for the built-in synth, used when output is `InstrumentOutput::Synth`.
+    pub synth: SynthParams,
+    pub filter: FilterParams,
+    /// the amplitude envelope of the built-in synth.
+    pub amp_env: Adsr,
+    pub mod_env: ModEnvelope,
+    pub lfos: [LfoParams; N_LFOS],
+    pub mod_matrix: ModMatrix,
+    /// the drum kit, used when output is `InstrumentOutput::Percusion`.
+    pub drums: DrumKit,
+    /// the sample and how it's played, used when output is `InstrumentOutput::Sampler`.
+    pub sampler: SamplerParams,
+    /// the operators and algorithm, used when output is `InstrumentOutput::Fm`.
+    pub fm: FmParams,
+    /// the Game Boy channel and its settings, used when output is `InstrumentOutput::GameBoy`.
+    pub gameboy: GbParams,
+    /// how many notes play at once, and which voice is stolen for a new note. not used by
+    /// percussion.
+    pub polyphony: Polyphony,
+    /// the insert effects the instrument is run through, whatever the output.
+    pub inserts: Inserts,
+    /// the MIDI channel and velocity, used when output is `InstrumentOutput::UsbMidi`.
+    pub midi: MidiParams,
 }
 
 impl Instrument {
@@ -83,18 +342,122 @@ impl Instrument {
             output: InstrumentOutput::Synth,
             human_name: format!("Synth {index}"),
             name: index,
+            synth: SynthParams::default(),
+            filter: FilterParams::default(),
+            amp_env: Adsr::default(),
+            mod_env: ModEnvelope::default(),
+            lfos: [LfoParams::default(); N_LFOS],
+            mod_matrix: ModMatrix::default(),
+            drums: DrumKit::default(),
+            sampler: SamplerParams::default(),
+            fm: FmParams::default(),
+            gameboy: GbParams::default(),
+            polyphony: Polyphony::default(),
+            inserts: Inserts::default(),
+            midi: MidiParams::default(),
+        }
+    }
+
+    /// the parameters shown on the instrument screen. which are shown depends on the output.
+    pub fn params(&mut self) -> Vec<Param<'_>> {
+        let output = self.output;
+        let mut params = vec![Param::choice("OUTPUT", &mut self.output)];
+
+        match output {
+            InstrumentOutput::Synth => {
+                params.append(&mut self.synth.params());
+                params.append(&mut self.filter.params());
+                params.append(
+                    &mut self
+                        .amp_env
+                        .params(["ATTACK", "DECAY", "SUSTAIN", "RELEASE"]),
+                );
+                params.append(&mut self.mod_env.params());
+
+                let [lfo_1, lfo_2] = &mut self.lfos;
+                params.append(&mut lfo_1.params([
+                    "LFO1 WAVE",
+                    "LFO1 RATE",
+                    "LFO1 SYNC",
+                    "LFO1 ROWS",
+                ]));
+                params.append(&mut lfo_2.params([
+                    "LFO2 WAVE",
+                    "LFO2 RATE",
+                    "LFO2 SYNC",
+                    "LFO2 ROWS",
+                ]));
+                params.append(&mut self.mod_matrix.params());
+            }
+            InstrumentOutput::Percusion => params.append(&mut self.drums.params()),
+            InstrumentOutput::Fm => params.append(&mut self.fm.params()),
+            InstrumentOutput::GameBoy => params.append(&mut self.gameboy.params()),
+            InstrumentOutput::Sampler => {
+                params.append(&mut self.sampler.params());
+                params.append(
+                    &mut self
+                        .amp_env
+                        .params(["ATTACK", "DECAY", "SUSTAIN", "RELEASE"]),
+                );
+            }
+            InstrumentOutput::UsbMidi => {
+                params.append(&mut self.midi.params());
+                return params;
+            }
         }
+
+        if output != InstrumentOutput::Percusion {
+            params.append(&mut self.polyphony.params());
+        }
+
+        params.append(&mut self.inserts.params());
+
+        params
+    }
+
+    pub fn n_params(&self) -> usize {
+        self.clone().params().len()
+    }
+}
+
+impl Default for Instrument {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+#[pymethods]
+impl Instrument {
+    /// the name and value of each parameter, for display on the instrument screen.
+    fn rows(&self) -> Vec<(String, String)> {
+        self.clone().params().iter().map(Param::row).collect()
     }
 }
 
 #[pyclass(module = "tracker_backend", get_all)]
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct PhraseRow {
     pub note: Option<Note>,
     pub instrument: Option<Index>,
     pub command: Option<TrackerCommand>,
 }
 
+impl PhraseRow {
+    /// the notes this row triggers. that is the rows note plus the rest of its chord, if the row
+    /// has a chord command.
+    pub fn notes(&self) -> Vec<Note> {
+        let Some(root) = self.note else {
+            return Vec::new();
+        };
+
+        match self.command {
+            Some(TrackerCommand::Chord(shape, inversion)) => shape.voicing(root, inversion),
+            _ => vec![root],
+        }
+    }
+}
+
 // impl IndexInto<Index> for PhraseRow {
 //     type Output = Option<Index>;
 //
@@ -111,14 +474,16 @@ pub struct PhraseRow {
 
 /// a single phrase to be used as a part of chains
 #[pyclass(module = "tracker_backend", get_all)]
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Phrase {
     pub rows: [PhraseRow; 16],
     pub name: Index,
 }
 
 #[pyclass(module = "tracker_backend", get_all)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Ord, PartialOrd, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Ord, PartialOrd, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ChainRow {
     pub phrase: Option<Index>,
 }
@@ -144,14 +509,16 @@ impl IndexInto<Index> for ChainRow {
 
 /// a chain of phrases strung together to make a song
 #[pyclass(module = "tracker_backend", get_all)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Ord, PartialOrd, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Ord, PartialOrd, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Chain {
     pub rows: [ChainRow; 16],
     pub name: Index,
 }
 
 #[pyclass(module = "tracker_backend", get_all)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Ord, PartialOrd, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Ord, PartialOrd, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SongRow {
     pub lead_1: Option<Index>,
     pub lead_2: Option<Index>,
@@ -197,11 +564,18 @@ impl IndexMut<Index> for SongRow {
 
 /// the whole song
 #[pyclass(module = "tracker_backend", get_all)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Ord, PartialOrd, Resource)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Resource, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Song {
     pub rows: [SongRow; 16],
     // pub name: Index,
     pub default_instrument: [Index; 4],
+    pub mixer: Mixer,
+    pub effects: Effects,
+    /// the master bus compressor, limiter, and sidechain.
+    pub dynamics: Dynamics,
+    /// the controllers mapped to parameters for this song.
+    pub mappings: [Option<MidiMapping>; N_SONG_MAPPINGS],
 }
 
 impl Default for Song {
@@ -209,10 +583,28 @@ impl Default for Song {
         Self {
             rows: [SongRow::default(); 16],
             default_instrument: [0, 0, 1, 2],
+            mixer: Mixer::default(),
+            effects: Effects::default(),
+            dynamics: Dynamics::default(),
+            mappings: [None; N_SONG_MAPPINGS],
         }
     }
 }
 
+impl Song {
+    /// the parameters shown on the effects screen, the send effects then the master dynamics.
+    pub fn effects_params(&mut self) -> Vec<Param<'_>> {
+        let mut params = self.effects.params();
+        params.append(&mut self.dynamics.params());
+
+        params
+    }
+
+    pub fn n_effects_params(&self) -> usize {
+        self.effects.n_params() + self.dynamics.n_params()
+    }
+}
+
 #[pyclass(module = "tracker_backend", get_all)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Ord, PartialOrd, Resource)]
 pub enum Screen {
@@ -220,8 +612,12 @@ pub enum Screen {
     EditChain(Index),
     EditPhrase(Index),
     Instrument(Index),
+    Wavetable(Index),
     PlaySynth(),
+    Mixer(),
+    Effects(),
     Settings(),
+    Mappings(),
 }
 
 #[pyclass(module = "tracker_backend", get_all)]
@@ -263,15 +659,52 @@ pub struct DisplayCursor {
     pub selected: bool,
 }
 
+/// a value kept on the heap, so the large screens don't make every `ScreenData` as big as they
+/// are. the frontend sees the value itself.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Boxed<T>(pub Box<T>);
+
+impl<T> From<T> for Boxed<T> {
+    fn from(value: T) -> Self {
+        Self(Box::new(value))
+    }
+}
+
+impl<T> Deref for Boxed<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: IntoPy<PyObject>> IntoPy<PyObject> for Boxed<T> {
+    fn into_py(self, py: Python<'_>) -> PyObject {
+        (*self.0).into_py(py)
+    }
+}
+
+impl<'py, T: FromPyObject<'py>> FromPyObject<'py> for Boxed<T> {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        T::extract_bound(ob).map(Self::from)
+    }
+}
+
 #[pyclass(module = "tracker_backend", get_all)]
 #[derive(Debug, Clone, PartialEq, PartialOrd, Resource)]
 pub enum ScreenData {
-    Song(Song),
-    Chain(Chain),
-    Phrase(Phrase),
-    Instrument(Instrument),
-    PlaySynth(),
-    Settings(),
+    Song(Boxed<Song>),
+    Chain(Boxed<Chain>),
+    Phrase(Boxed<Phrase>),
+    Instrument(Boxed<Instrument>),
+    Wavetable(Boxed<Wavetable>),
+    /// the instrument played from the MIDI input, and the channel it is heard on.
+    PlaySynth(Index, usize),
+    Mixer(Boxed<Mixer>, Boxed<MixerLevels>),
+    Effects(Effects, Dynamics),
+    Settings(AudioConfig, MidiConfig),
+    /// the controller, parameter, and scope of each MIDI mapping.
+    Mappings(Vec<(String, String, String)>),
 }
 
 #[pyclass(module = "tracker_backend", get_all)]
@@ -288,5 +721,90 @@ pub struct State {
     pub screen: ScreenData,
     pub playing: [Option<Note>; 4],
     pub tempo: Bpm,
+    /// the tempo of the external clock the song follows, shown in place of `tempo`. `None` while
+    /// it plays at its own tempo.
+    pub clock_tempo: Option<f32>,
     pub display_cursor: DisplayCursor,
+    /// how far, in dB, the master bus compressor and limiter are turning the song down.
+    pub gain_reduction: f32,
+    /// the times the audio output has run dry since the app started.
+    pub underruns: u64,
+}
+
+/// the most times a second `Telemetry` is sent to the frontend.
+pub const TELEMETRY_RATE: f32 = 30.0;
+
+/// what the audio engine is doing right now, sent on its own, apart from `State`, as it changes
+/// every frame.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Telemetry {
+    pub scope: Scope,
+    pub levels: MixerLevels,
+    /// the level of each band of the master, lowest first, in dB from full scale.
+    pub spectrum: Vec<f32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chords_are_voiced_from_the_root() {
+        assert_eq!(ChordShape::Major.voicing(60, 0), [60, 64, 67]);
+        assert_eq!(ChordShape::Minor7.voicing(60, 0), [60, 63, 67, 70]);
+
+        // each inversion moves the lowest note up an octave.
+        assert_eq!(ChordShape::Major.voicing(60, 1), [64, 67, 72]);
+        assert_eq!(ChordShape::Major.voicing(60, 2), [67, 72, 76]);
+        assert_eq!(ChordShape::Major.voicing(60, 3), [60, 64, 67]);
+        assert_eq!(ChordShape::Dom7.voicing(60, 3), [70, 72, 76, 79]);
+
+        // notes past the top of the MIDI range are dropped onto it, once.
+        assert_eq!(ChordShape::Major7.voicing(120, 0), [120, 124, 127]);
+    }
+
+    #[test]
+    fn chord_args_step_through_every_inversion() {
+        let mut cmd = TrackerCommand::Chord(ChordShape::Major, 2);
+        cmd.shift_arg(true);
+        assert_eq!(cmd, TrackerCommand::Chord(ChordShape::Minor, 0));
+
+        cmd.shift_arg(false);
+        assert_eq!(cmd, TrackerCommand::Chord(ChordShape::Major, 2));
+
+        // wraps around the shapes both ways.
+        let mut cmd = TrackerCommand::Chord(ChordShape::Major, 0);
+        cmd.shift_arg(false);
+        assert_eq!(cmd, TrackerCommand::Chord(ChordShape::Dom7, 3));
+
+        cmd.shift_arg(true);
+        assert_eq!(cmd, TrackerCommand::Chord(ChordShape::Major, 0));
+    }
+
+    #[test]
+    fn args_are_clamped() {
+        let mut volume = TrackerCommand::Volume(1.0);
+        volume.shift_arg(true);
+        assert_eq!(volume, TrackerCommand::Volume(1.0));
+
+        volume.shift_arg(false);
+        assert_eq!(volume, TrackerCommand::Volume(14.0 / 15.0));
+
+        let mut silent = TrackerCommand::Volume(0.0);
+        silent.shift_arg(false);
+        assert_eq!(silent, TrackerCommand::Volume(0.0));
+
+        let mut cutoff = TrackerCommand::Cutoff(MAX_CUTOFF as u8);
+        cutoff.shift_arg(true);
+        assert_eq!(cutoff, TrackerCommand::Cutoff(MAX_CUTOFF as u8));
+
+        let mut cc = TrackerCommand::MidiCc(1, 127);
+        cc.shift_arg(true);
+        assert_eq!(cc, TrackerCommand::MidiCc(1, 127));
+
+        let mut bend = TrackerCommand::PitchBend(0);
+        bend.shift_arg(false);
+        assert_eq!(bend, TrackerCommand::PitchBend(0));
+    }
 }
@@ -0,0 +1,1044 @@
+use crate::{
+    config::ui::Bpm,
+    controls::{LastViewed, MyGamepad},
+    pygame_coms::{
+        DisplayCursor, Index, Note, PlaybackCursor, PlaybackCursorWrapper, Screen, Song,
+        TrackerCommand,
+    },
+    synth::{mixer::SendBus, AudioClock, SCHEDULE_AHEAD},
+    tracker_state::{AllChains, AllPhrases, StateUpdated, Tempo},
+    ExitMenuState, PlayingState,
+};
+use bevy::{log::*, prelude::*};
+
+/// the number of phrase rows played per beat.
+pub const ROWS_PER_BEAT: f32 = 4.0;
+/// the number of channels in a song. (lead 1, lead 2, bass, & percussion)
+pub const N_CHANNELS: usize = 4;
+
+pub struct SequencerPlugin;
+
+impl Plugin for SequencerPlugin {
+    fn build(&self, app: &mut App) {
+        debug!("tracker_backend::sequencer::SequencerPlugin loaded");
+
+        app.init_resource::<Sequencer>()
+            .init_resource::<ExternalTempo>()
+            .add_event::<ScheduledNote>()
+            .add_event::<ScheduledTransport>()
+            .add_event::<ClockEvent>()
+            .add_systems(
+                Update,
+                toggle_playback.run_if(not(in_state(ExitMenuState::Opened))),
+            )
+            .add_systems(
+                Update,
+                step.run_if(in_state(PlayingState::Playing))
+                    .run_if(|external: Res<ExternalTempo>| external.0.is_none()),
+            )
+            .add_systems(Update, follow_clock)
+            .add_systems(OnEnter(PlayingState::NotPlaying), stop_notes);
+    }
+}
+
+/// what a row does to a channel.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NoteEvent {
+    /// start playing `notes` on `channel`. every note of a chord is sent in the same event.
+    NoteOn {
+        channel: usize,
+        instrument: Index,
+        notes: Vec<Note>,
+        /// from 0.0 to 1.0, set by the rows volume command.
+        velocity: f32,
+    },
+    /// stop all notes playing on `channel`.
+    NoteOff { channel: usize },
+    /// set the filter cutoff, as a MIDI note, of the notes playing on `channel` and the notes it
+    /// plays after. `None` goes back to the cutoff of the instrument.
+    Cutoff { channel: usize, cutoff: Option<f32> },
+    /// set the gain of `channel` on the mixer, from 0.0 to 1.0, on top of its mixer volume. set by
+    /// the volume commands of rows without notes, to fade what is held, and back to 1.0 by the next
+    /// row with notes.
+    Volume { channel: usize, volume: f32 },
+    /// set how much of `channel` is sent to an effect, from 0.0 to 1.0. `None` goes back to the
+    /// send level set on the mixer.
+    Send {
+        channel: usize,
+        bus: SendBus,
+        amount: Option<f32>,
+    },
+    /// set a controller of `instrument`, if it plays over MIDI, gliding to `value` over `glide`
+    /// frames.
+    ControlChange {
+        channel: usize,
+        instrument: Index,
+        slot: u8,
+        value: u8,
+        glide: u64,
+    },
+    /// bend the notes of `instrument`, if it plays over MIDI. `bend` is the top 7 bits of the pitch
+    /// bend.
+    PitchBend {
+        channel: usize,
+        instrument: Index,
+        bend: u8,
+    },
+    /// pick the bank and program of `instrument`, if it plays over MIDI, as it starts playing on
+    /// `channel`.
+    Program { channel: usize, instrument: Index },
+}
+
+/// sent by the sequencer when a row starts or stops notes on a channel.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct ScheduledNote {
+    /// the frame of the `AudioClock` the event lands on, 0 for as soon as possible.
+    pub at: u64,
+    pub event: NoteEvent,
+}
+
+/// what playback does, for things that follow along with the song, like MIDI clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    /// playback starts, or jumps, `position` rows into the song.
+    Play {
+        position: u16,
+    },
+    Stop,
+}
+
+/// sent by the sequencer when playback starts, stops, or jumps somewhere other than the next row.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScheduledTransport {
+    /// the frame of the `AudioClock` the event lands on, 0 for as soon as possible.
+    pub at: u64,
+    pub event: Transport,
+}
+
+/// what an external clock, like the MIDI clock of another device, tells playback to do.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub enum ClockEvent {
+    /// play from the top.
+    Start,
+    /// play from where the cursor is.
+    Continue,
+    Stop,
+    /// a row starts on frame `at` of the `AudioClock`, and lasts `len` frames.
+    Row {
+        at: f64,
+        len: f64,
+    },
+}
+
+/// the tempo of the external clock playback follows, `None` while it plays at its own tempo.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Resource)]
+pub struct ExternalTempo(pub Option<f32>);
+
+impl ExternalTempo {
+    /// the tempo the song plays at.
+    pub fn bpm(&self, tempo: &Tempo) -> Bpm {
+        self.0.map_or(tempo.0, |external| {
+            external.round().clamp(1.0, Bpm::MAX as f32) as Bpm
+        })
+    }
+}
+
+/// what the sequencer is playing through.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PlaySource {
+    /// the full song, from a row of the song screen.
+    #[default]
+    Song,
+    /// loops a single chain on one channel.
+    Chain(Index),
+    /// loops a single phrase on one channel.
+    Phrase(Index),
+}
+
+/// the position of one channel in the song.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChannelCursor {
+    pub song_row: Index,
+    pub chain_row: Index,
+    pub phrase_row: Index,
+    /// false once the channel has run out of things to play.
+    pub active: bool,
+}
+
+#[derive(Debug, Clone, Default, Resource)]
+pub struct Sequencer {
+    pub source: PlaySource,
+    /// the channel that chains and phrases are played on when not playing the full song.
+    pub channel: usize,
+    pub cursors: [ChannelCursor; N_CHANNELS],
+    /// seconds until the next row is due.
+    pub next_row_in: f32,
+    /// the frame of the `AudioClock` the next row lands on, `None` till the first row is played.
+    pub next_row_at: Option<f64>,
+    /// the notes held by each channel.
+    pub held: [Vec<Note>; N_CHANNELS],
+    /// the last instrument used by each channel.
+    pub instruments: [Option<Index>; N_CHANNELS],
+    /// the instrument each channel last picked the program of, `None` till its first row.
+    pub programs: [Option<Index>; N_CHANNELS],
+}
+
+impl Sequencer {
+    /// the length of a row in seconds at the given tempo.
+    pub fn row_len(tempo: &Tempo) -> f32 {
+        60.0 / (tempo.0.max(1) as f32 * ROWS_PER_BEAT)
+    }
+
+    /// the lowest note held by each channel.
+    pub fn playing(&self) -> [Option<Note>; N_CHANNELS] {
+        let mut playing = [None; N_CHANNELS];
+
+        for (i, held) in self.held.iter().enumerate() {
+            playing[i] = held.first().copied();
+        }
+
+        playing
+    }
+
+    /// how many rows into the song playback is, counted along the channel playback was started
+    /// from, or the first one still playing. `None` once every channel has stopped.
+    pub fn position(&self, song: &Song, chains: &AllChains) -> Option<u16> {
+        let channel = std::iter::once(self.channel)
+            .chain(0..N_CHANNELS)
+            .find(|channel| {
+                self.cursors
+                    .get(*channel)
+                    .is_some_and(|cursor| cursor.active)
+            })?;
+        let cursor = self.cursors[channel];
+        // a chain plays phrases till its first empty row, and is always at least one phrase long.
+        let chain_len = |chain_i: Index| {
+            chains.0[chain_i].map_or(1, |chain| {
+                chain
+                    .rows
+                    .iter()
+                    .take_while(|row| row.phrase.is_some())
+                    .count()
+                    .max(1)
+            })
+        };
+
+        let chains_before: usize = match self.source {
+            PlaySource::Song => (0..cursor.song_row)
+                .filter_map(|row| song.rows[row][channel])
+                .map(chain_len)
+                .sum(),
+            PlaySource::Chain(_) | PlaySource::Phrase(_) => 0,
+        };
+        let rows = (chains_before + cursor.chain_row) * 16 + cursor.phrase_row;
+
+        Some(rows.min(u16::MAX as usize) as u16)
+    }
+
+    /// the chain and phrase played by `channel`.
+    fn chain_and_phrase(
+        &self,
+        channel: usize,
+        song: &Song,
+        chains: &AllChains,
+    ) -> (Option<Index>, Option<Index>) {
+        let cursor = self.cursors[channel];
+
+        match self.source {
+            PlaySource::Song => {
+                let chain = song.rows[cursor.song_row][channel];
+                let phrase = chain
+                    .and_then(|chain_i| chains.0[chain_i])
+                    .and_then(|chain| chain.rows[cursor.chain_row].phrase);
+
+                (chain, phrase)
+            }
+            PlaySource::Chain(chain_i) => (
+                Some(chain_i),
+                chains.0[chain_i].and_then(|chain| chain.rows[cursor.chain_row].phrase),
+            ),
+            PlaySource::Phrase(phrase_i) => (None, Some(phrase_i)),
+        }
+    }
+
+    /// moves `channel` to the next row that has a phrase to play.
+    fn advance(&mut self, channel: usize, song: &Song, chains: &AllChains) {
+        let source = self.source;
+        let cursor = &mut self.cursors[channel];
+        cursor.phrase_row += 1;
+
+        if cursor.phrase_row < 16 {
+            return;
+        }
+
+        cursor.phrase_row = 0;
+
+        let chain = match source {
+            PlaySource::Song => song.rows[cursor.song_row][channel],
+            PlaySource::Chain(chain_i) => Some(chain_i),
+            // a lone phrase just loops.
+            PlaySource::Phrase(_) => return,
+        };
+
+        cursor.chain_row += 1;
+
+        let chain_over = cursor.chain_row >= 16
+            || chain
+                .and_then(|chain_i| chains.0[chain_i])
+                .is_none_or(|chain| chain.rows[cursor.chain_row].phrase.is_none());
+
+        if !chain_over {
+            return;
+        }
+
+        cursor.chain_row = 0;
+
+        if source != PlaySource::Song {
+            return;
+        }
+
+        cursor.song_row += 1;
+
+        if cursor.song_row >= 16 || song.rows[cursor.song_row][channel].is_none() {
+            // loop back to the top of this block of chains, like LSDJ.
+            cursor.song_row -= 1;
+
+            while cursor.song_row > 0 && song.rows[cursor.song_row - 1][channel].is_some() {
+                cursor.song_row -= 1;
+            }
+        }
+
+        cursor.active = song.rows[cursor.song_row][channel].is_some();
+    }
+
+    /// plays the rows the channel cursors are pointing at, landing on frame `at` and lasting
+    /// `len` frames. the notes they play are added to `notes`.
+    fn play_rows(
+        &mut self,
+        at: u64,
+        len: u64,
+        song: &Song,
+        chains: &AllChains,
+        phrases: &AllPhrases,
+        notes: &mut Vec<ScheduledNote>,
+    ) -> bool {
+        let mut changed = false;
+        let mut send = |event| {
+            notes.push(ScheduledNote { at, event });
+        };
+
+        for channel in 0..N_CHANNELS {
+            if !self.cursors[channel].active {
+                continue;
+            }
+
+            let (_, phrase) = self.chain_and_phrase(channel, song, chains);
+            let Some(row) = phrase
+                .and_then(|phrase_i| phrases.0[phrase_i])
+                .map(|phrase| phrase.rows[self.cursors[channel].phrase_row])
+            else {
+                continue;
+            };
+
+            if let Some(instrument) = row.instrument {
+                self.instruments[channel] = Some(instrument);
+            }
+
+            let instrument = self.instruments[channel].unwrap_or(song.default_instrument[channel]);
+
+            if self.programs[channel] != Some(instrument) {
+                send(NoteEvent::Program {
+                    channel,
+                    instrument,
+                });
+                self.programs[channel] = Some(instrument);
+            }
+
+            let notes = row.notes();
+
+            // sent before the notes so they start at the new send levels.
+            match row.command {
+                // the volume of a row with notes is their velocity.
+                Some(TrackerCommand::Volume(volume)) if notes.is_empty() => {
+                    send(NoteEvent::Volume { channel, volume });
+                }
+                Some(TrackerCommand::DelaySend(amount)) => {
+                    send(NoteEvent::Send {
+                        channel,
+                        bus: SendBus::Delay,
+                        amount: Some(amount),
+                    });
+                }
+                Some(TrackerCommand::ReverbSend(amount)) => {
+                    send(NoteEvent::Send {
+                        channel,
+                        bus: SendBus::Reverb,
+                        amount: Some(amount),
+                    });
+                }
+                Some(TrackerCommand::MidiCc(slot, value)) => {
+                    send(NoteEvent::ControlChange {
+                        channel,
+                        instrument,
+                        slot,
+                        value,
+                        glide: 0,
+                    });
+                }
+                Some(TrackerCommand::MidiCcRamp(slot, value)) => {
+                    send(NoteEvent::ControlChange {
+                        channel,
+                        instrument,
+                        slot,
+                        value,
+                        glide: len,
+                    });
+                }
+                Some(TrackerCommand::PitchBend(bend)) => {
+                    send(NoteEvent::PitchBend {
+                        channel,
+                        instrument,
+                        bend,
+                    });
+                }
+                _ => {}
+            }
+
+            if !notes.is_empty() {
+                let velocity = match row.command {
+                    Some(TrackerCommand::Volume(volume)) => volume,
+                    _ => 1.0,
+                };
+
+                // undoes any fade of the notes before, so the new ones are heard at their velocity.
+                send(NoteEvent::Volume {
+                    channel,
+                    volume: 1.0,
+                });
+                send(NoteEvent::NoteOn {
+                    channel,
+                    instrument,
+                    notes: notes.clone(),
+                    velocity,
+                });
+                self.held[channel] = notes;
+                changed = true;
+            } else if row.command == Some(TrackerCommand::NoteOff())
+                && !self.held[channel].is_empty()
+            {
+                send(NoteEvent::NoteOff { channel });
+                self.held[channel].clear();
+                changed = true;
+            }
+
+            // sent after the note on so it applies to the rows own notes too.
+            if let Some(TrackerCommand::Cutoff(cutoff)) = row.command {
+                send(NoteEvent::Cutoff {
+                    channel,
+                    cutoff: Some(cutoff as f32),
+                });
+            }
+        }
+
+        changed
+    }
+
+    /// plays the next row, landing on frame `at`, and moves on to the row after it, landing `len`
+    /// frames later. returns true if notes were started or stopped.
+    fn play_row(
+        &mut self,
+        at: f64,
+        len: f64,
+        (song, chains, phrases): (&Song, &AllChains, &AllPhrases),
+        cursor: &PlaybackCursorWrapper,
+        note_events: &mut EventWriter<ScheduledNote>,
+        transport: &mut EventWriter<ScheduledTransport>,
+    ) -> bool {
+        let position = self.position(song, chains);
+
+        if self.next_row_at.is_none()
+            && let Some(position) = position
+        {
+            transport.send(ScheduledTransport {
+                at: at as u64,
+                event: Transport::Play { position },
+            });
+        }
+
+        let mut notes = Vec::new();
+        let changed = self.play_rows(at as u64, len as u64, song, chains, phrases, &mut notes);
+        note_events.send_batch(notes);
+        *cursor.0.lock().unwrap() = self.cursor(song, chains);
+
+        for channel in 0..N_CHANNELS {
+            if self.cursors[channel].active {
+                self.advance(channel, song, chains);
+            }
+        }
+
+        let next_at = at + len;
+        self.next_row_at = Some(next_at);
+
+        // looped back, or moved on to a channel that is somewhere else in the song.
+        let next_position = self.position(song, chains);
+
+        if let Some(next_position) = next_position
+            && position.and_then(|position| position.checked_add(1)) != Some(next_position)
+        {
+            transport.send(ScheduledTransport {
+                at: next_at as u64,
+                event: Transport::Play {
+                    position: next_position,
+                },
+            });
+        }
+
+        changed
+    }
+
+    /// the notes of the song played once through from the top, with the first row landing on
+    /// frame 0, and the frame the last row ends on. a channel is played through once it loops
+    /// back, and the song once every channel has. the notes still held are released at the end.
+    pub fn song_once(
+        tempo: &Tempo,
+        song: &Song,
+        chains: &AllChains,
+        phrases: &AllPhrases,
+        sample_rate: f64,
+    ) -> (Vec<ScheduledNote>, u64) {
+        let display_cursor = DisplayCursor::default();
+        let last_viewed = LastViewed::default();
+        let mut sequencer = start(&Screen::Song(), song, &display_cursor, &last_viewed, true);
+        let len = Self::row_len(tempo) as f64 * sample_rate;
+        let mut notes = Vec::new();
+        let mut at = 0.0;
+        let mut looped = sequencer.cursors.map(|cursor| !cursor.active);
+        let position =
+            |cursor: ChannelCursor| (cursor.song_row, cursor.chain_row, cursor.phrase_row);
+
+        // a channel can only move forward through the song so many rows, so this always ends.
+        while !looped.iter().all(|looped| *looped) {
+            sequencer.play_rows(at as u64, len as u64, song, chains, phrases, &mut notes);
+
+            for (channel, looped) in looped.iter_mut().enumerate() {
+                let before = sequencer.cursors[channel];
+
+                if !before.active {
+                    continue;
+                }
+
+                sequencer.advance(channel, song, chains);
+                let after = sequencer.cursors[channel];
+
+                if !after.active || position(after) <= position(before) {
+                    *looped = true;
+                }
+            }
+
+            at += len;
+        }
+
+        for channel in 0..N_CHANNELS {
+            if !sequencer.held[channel].is_empty() {
+                notes.push(ScheduledNote {
+                    at: at as u64,
+                    event: NoteEvent::NoteOff { channel },
+                });
+            }
+        }
+
+        (notes, at as u64)
+    }
+
+    /// the playback cursor, as its shown to the rest of the program.
+    fn cursor(&self, song: &Song, chains: &AllChains) -> PlaybackCursor {
+        let stack = |channel: usize| {
+            let cursor = self.cursors[channel];
+            let (chain, phrase) = self.chain_and_phrase(channel, song, chains);
+            let mut stack = Vec::with_capacity(2);
+
+            if let Some(chain_i) = chain {
+                stack.push((Screen::EditChain(chain_i), cursor.chain_row));
+            }
+
+            if let Some(phrase_i) = phrase {
+                stack.push((Screen::EditPhrase(phrase_i), cursor.phrase_row));
+            }
+
+            stack
+        };
+
+        match self.source {
+            PlaySource::Song => PlaybackCursor::FullSong {
+                lead_1: stack(0),
+                lead_2: stack(1),
+                bass: stack(2),
+                perc: stack(3),
+                row: self.cursors[0].song_row,
+            },
+            _ => PlaybackCursor::NotFull {
+                from_screen: stack(self.channel),
+                row: self.cursors[self.channel].phrase_row,
+            },
+        }
+    }
+}
+
+/// starts and stops playback from the screen being edited.
+fn toggle_playback(
+    buttons: Res<ButtonInput<GamepadButton>>,
+    my_gamepad: Option<Res<MyGamepad>>,
+    gamepads: Res<Gamepads>,
+    playing: Res<State<PlayingState>>,
+    mut next_playing: ResMut<NextState<PlayingState>>,
+    mut sequencer: ResMut<Sequencer>,
+    screen: Res<Screen>,
+    song: Res<Song>,
+    display_cursor: Res<DisplayCursor>,
+    last_viewed: Res<LastViewed>,
+) {
+    let Some(&MyGamepad(gamepad)) = my_gamepad.as_deref() else {
+        // no gamepad is connected
+        return;
+    };
+
+    // which ever of start and select is not used to change screens.
+    let play_button = if let Some(name) = gamepads.name(gamepad)
+        && name.starts_with("PS5")
+    {
+        GamepadButton {
+            gamepad,
+            button_type: GamepadButtonType::Select,
+        }
+    } else {
+        GamepadButton {
+            gamepad,
+            button_type: GamepadButtonType::Start,
+        }
+    };
+
+    if !buttons.just_released(play_button) {
+        return;
+    }
+
+    if **playing == PlayingState::Playing {
+        info!("stopping playback");
+        next_playing.set(PlayingState::NotPlaying);
+
+        return;
+    }
+
+    *sequencer = start(&screen, &song, &display_cursor, &last_viewed, false);
+    next_playing.set(PlayingState::Playing);
+}
+
+/// a sequencer that plays from the screen being edited, from the top of the song if `from_top`,
+/// or else from the song row the cursor is on.
+fn start(
+    screen: &Screen,
+    song: &Song,
+    display_cursor: &DisplayCursor,
+    last_viewed: &LastViewed,
+    from_top: bool,
+) -> Sequencer {
+    let (source, channel, song_row) = match *screen {
+        Screen::Song() if from_top => (PlaySource::Song, display_cursor.col, 0),
+        Screen::Song() => (PlaySource::Song, display_cursor.col, display_cursor.row),
+        Screen::EditChain(chain_i) => (PlaySource::Chain(chain_i), last_viewed.channel, 0),
+        Screen::EditPhrase(phrase_i) => (PlaySource::Phrase(phrase_i), last_viewed.channel, 0),
+        _ => (PlaySource::Song, 0, 0),
+    };
+
+    info!("starting playback of {source:?}");
+
+    let mut cursors = [ChannelCursor {
+        song_row,
+        ..default()
+    }; N_CHANNELS];
+
+    for (i, cursor) in cursors.iter_mut().enumerate() {
+        cursor.active = match source {
+            PlaySource::Song => song.rows[song_row][i].is_some(),
+            _ => i == channel,
+        };
+    }
+
+    Sequencer {
+        source,
+        channel,
+        cursors,
+        next_row_in: 0.0,
+        ..default()
+    }
+}
+
+/// advances playback by one row every time a row is due. rows are spaced out on the audio clock,
+/// so they land a steady distance apart however late this runs.
+fn step(
+    time: Res<Time>,
+    clock: Res<AudioClock>,
+    tempo: Res<Tempo>,
+    mut sequencer: ResMut<Sequencer>,
+    song: Res<Song>,
+    chains: Res<AllChains>,
+    phrases: Res<AllPhrases>,
+    cursor: Res<PlaybackCursorWrapper>,
+    mut note_events: EventWriter<ScheduledNote>,
+    mut transport: EventWriter<ScheduledTransport>,
+    mut state_updated: EventWriter<StateUpdated>,
+) {
+    let sample_rate = clock.sample_rate() as f64;
+    let now = clock.now() as f64;
+    let ahead = SCHEDULE_AHEAD as f64 * sample_rate;
+    sequencer.next_row_in -= time.delta_seconds();
+
+    while sequencer.next_row_in <= 0.0 {
+        // starts the schedule again if it has fallen behind the audio or run too far ahead.
+        let at = sequencer
+            .next_row_at
+            .filter(|at| (now..=now + ahead * 2.0).contains(at))
+            .unwrap_or(now + ahead);
+        let len = Sequencer::row_len(&tempo) as f64 * sample_rate;
+
+        if sequencer.play_row(
+            at,
+            len,
+            (&song, &chains, &phrases),
+            &cursor,
+            &mut note_events,
+            &mut transport,
+        ) {
+            state_updated.send_default();
+        }
+
+        sequencer.next_row_in += Sequencer::row_len(&tempo);
+    }
+}
+
+/// plays along with an external clock, starting and stopping with it, and playing a row on each
+/// of its rows. its rows land `SCHEDULE_AHEAD` after they came in, like those of `step`.
+fn follow_clock(
+    mut clock_events: EventReader<ClockEvent>,
+    clock: Res<AudioClock>,
+    playing: Res<State<PlayingState>>,
+    mut next_playing: ResMut<NextState<PlayingState>>,
+    mut sequencer: ResMut<Sequencer>,
+    (screen, display_cursor, last_viewed): (Res<Screen>, Res<DisplayCursor>, Res<LastViewed>),
+    (song, chains, phrases): (Res<Song>, Res<AllChains>, Res<AllPhrases>),
+    cursor: Res<PlaybackCursorWrapper>,
+    mut note_events: EventWriter<ScheduledNote>,
+    mut transport: EventWriter<ScheduledTransport>,
+    mut state_updated: EventWriter<StateUpdated>,
+) {
+    let sample_rate = clock.sample_rate() as f64;
+    let ahead = SCHEDULE_AHEAD as f64 * sample_rate;
+    // whether playback is going, as of the events read so far. the state only changes next frame.
+    let mut following = **playing == PlayingState::Playing;
+
+    for event in clock_events.read() {
+        match *event {
+            ClockEvent::Start | ClockEvent::Continue => {
+                // the state may not leave playing to release them, so the notes held from before
+                // are released here.
+                let at = sequencer.next_row_at.map_or(0, |at| at as u64);
+
+                for channel in 0..N_CHANNELS {
+                    if !sequencer.held[channel].is_empty() {
+                        note_events.send(ScheduledNote {
+                            at,
+                            event: NoteEvent::NoteOff { channel },
+                        });
+                    }
+                }
+
+                let from_top = *event == ClockEvent::Start;
+                *sequencer = start(&screen, &song, &display_cursor, &last_viewed, from_top);
+                next_playing.set(PlayingState::Playing);
+                following = true;
+            }
+            ClockEvent::Stop if following => {
+                info!("stopping playback");
+                next_playing.set(PlayingState::NotPlaying);
+                following = false;
+            }
+            ClockEvent::Stop => {}
+            ClockEvent::Row { at, len } if following => {
+                if sequencer.play_row(
+                    at + ahead,
+                    len,
+                    (&song, &chains, &phrases),
+                    &cursor,
+                    &mut note_events,
+                    &mut transport,
+                ) {
+                    state_updated.send_default();
+                }
+
+                // `step` picks up from here if the clock is lost.
+                sequencer.next_row_in = (len / sample_rate) as f32;
+            }
+            ClockEvent::Row { .. } => {}
+        }
+    }
+}
+
+/// releases every held note and resets the channel volumes, sends, and cutoffs when playback
+/// stops.
+fn stop_notes(
+    mut sequencer: ResMut<Sequencer>,
+    cursor: Res<PlaybackCursorWrapper>,
+    mut note_events: EventWriter<ScheduledNote>,
+    mut transport: EventWriter<ScheduledTransport>,
+    mut state_updated: EventWriter<StateUpdated>,
+) {
+    // lands with the row that would have come next, after the rows already scheduled.
+    let at = sequencer.next_row_at.map_or(0, |at| at as u64);
+    transport.send(ScheduledTransport {
+        at,
+        event: Transport::Stop,
+    });
+
+    let mut send = |event| {
+        note_events.send(ScheduledNote { at, event });
+    };
+
+    for channel in 0..N_CHANNELS {
+        if !sequencer.held[channel].is_empty() {
+            send(NoteEvent::NoteOff { channel });
+            sequencer.held[channel].clear();
+        }
+
+        send(NoteEvent::Volume {
+            channel,
+            volume: 1.0,
+        });
+        send(NoteEvent::Cutoff {
+            channel,
+            cutoff: None,
+        });
+
+        for bus in [SendBus::Delay, SendBus::Reverb] {
+            send(NoteEvent::Send {
+                channel,
+                bus,
+                amount: None,
+            });
+        }
+    }
+
+    *cursor.0.lock().unwrap() = PlaybackCursor::NotPlaying();
+    state_updated.send_default();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pygame_coms::{Chain, ChainRow, Phrase, PhraseRow};
+
+    /// a chain playing `phrases`, one after the other.
+    fn chain(phrases: &[Index]) -> Chain {
+        let mut chain = Chain::default();
+
+        for (row, phrase) in chain.rows.iter_mut().zip(phrases) {
+            *row = ChainRow {
+                phrase: Some(*phrase),
+            };
+        }
+
+        chain
+    }
+
+    fn playing(source: PlaySource, cursor: ChannelCursor) -> Sequencer {
+        let mut sequencer = Sequencer {
+            source,
+            ..default()
+        };
+        sequencer.cursors[0] = ChannelCursor {
+            active: true,
+            ..cursor
+        };
+
+        sequencer
+    }
+
+    /// advances channel 0 by `rows` rows.
+    fn advance(sequencer: &mut Sequencer, rows: usize, song: &Song, chains: &AllChains) {
+        for _ in 0..rows {
+            sequencer.advance(0, song, chains);
+        }
+    }
+
+    #[test]
+    fn a_phrase_loops() {
+        let (song, chains) = (Song::default(), AllChains::default());
+        let mut sequencer = playing(PlaySource::Phrase(0), ChannelCursor::default());
+
+        advance(&mut sequencer, 15, &song, &chains);
+        assert_eq!(sequencer.cursors[0].phrase_row, 15);
+
+        advance(&mut sequencer, 1, &song, &chains);
+        assert_eq!(sequencer.cursors[0].phrase_row, 0);
+        assert!(sequencer.cursors[0].active);
+    }
+
+    #[test]
+    fn a_chain_plays_till_its_first_empty_row() {
+        let song = Song::default();
+        let mut chains = AllChains::default();
+        chains.0[3] = Some(chain(&[0, 1]));
+        let mut sequencer = playing(PlaySource::Chain(3), ChannelCursor::default());
+
+        advance(&mut sequencer, 16, &song, &chains);
+        assert_eq!(sequencer.cursors[0].chain_row, 1);
+        assert_eq!(sequencer.cursors[0].phrase_row, 0);
+
+        advance(&mut sequencer, 16, &song, &chains);
+        assert_eq!(sequencer.cursors[0].chain_row, 0);
+        assert!(sequencer.cursors[0].active);
+    }
+
+    #[test]
+    fn the_song_loops_back_to_the_top_of_a_block() {
+        let mut song = Song::default();
+        let mut chains = AllChains::default();
+        chains.0[0] = Some(chain(&[0]));
+
+        // two blocks, rows 0 to 1 and rows 3 to 4.
+        for row in [0, 1, 3, 4] {
+            song.rows[row][0] = Some(0);
+        }
+
+        let mut sequencer = playing(PlaySource::Song, ChannelCursor::default());
+        advance(&mut sequencer, 16, &song, &chains);
+        assert_eq!(sequencer.cursors[0].song_row, 1);
+
+        advance(&mut sequencer, 16, &song, &chains);
+        assert_eq!(sequencer.cursors[0].song_row, 0);
+
+        let cursor = ChannelCursor {
+            song_row: 4,
+            ..default()
+        };
+        let mut sequencer = playing(PlaySource::Song, cursor);
+        advance(&mut sequencer, 16, &song, &chains);
+        assert_eq!(sequencer.cursors[0].song_row, 3);
+        assert!(sequencer.cursors[0].active);
+    }
+
+    #[test]
+    fn the_song_loops_at_its_last_row() {
+        let mut song = Song::default();
+        let mut chains = AllChains::default();
+        chains.0[0] = Some(chain(&[0]));
+        song.rows[14][0] = Some(0);
+        song.rows[15][0] = Some(0);
+
+        let cursor = ChannelCursor {
+            song_row: 15,
+            ..default()
+        };
+        let mut sequencer = playing(PlaySource::Song, cursor);
+        advance(&mut sequencer, 16, &song, &chains);
+        assert_eq!(sequencer.cursors[0].song_row, 14);
+        assert!(sequencer.cursors[0].active);
+    }
+
+    #[test]
+    fn the_song_is_played_once_through_by_its_longest_channel() {
+        let mut song = Song::default();
+        let mut chains = AllChains::default();
+        let mut phrases = AllPhrases::default();
+        let mut phrase = Phrase::default();
+        phrase.rows[0].note = Some(60);
+        phrases.0[0] = Some(phrase);
+        chains.0[0] = Some(chain(&[0]));
+        chains.0[1] = Some(chain(&[0, 0]));
+        song.rows[0].lead_1 = Some(0);
+        song.rows[0].bass = Some(1);
+
+        let tempo = Tempo(120);
+        let (notes, end) = Sequencer::song_once(&tempo, &song, &chains, &phrases, 48_000.0);
+        let row = (Sequencer::row_len(&tempo) as f64 * 48_000.0) as u64;
+
+        assert_eq!(end, row * 32);
+
+        let note_ons: Vec<_> = notes
+            .iter()
+            .filter_map(|note| match note.event {
+                NoteEvent::NoteOn { channel, .. } => Some((note.at, channel)),
+                _ => None,
+            })
+            .collect();
+        // the shorter channel loops while the longer one plays on.
+        assert_eq!(note_ons, [(0, 0), (0, 2), (row * 16, 0), (row * 16, 2)]);
+
+        // what is held is released at the end.
+        assert!(notes.contains(&ScheduledNote {
+            at: end,
+            event: NoteEvent::NoteOff { channel: 0 },
+        }));
+    }
+
+    #[test]
+    fn row_volume_is_the_velocity_of_its_notes() {
+        let song = Song::default();
+        let chains = AllChains::default();
+        let mut phrases = AllPhrases::default();
+        let mut phrase = Phrase::default();
+        let row = |note, volume| PhraseRow {
+            note,
+            instrument: None,
+            command: Some(TrackerCommand::Volume(volume)),
+        };
+        phrase.rows[0] = row(Some(60), 0.5);
+        // fades the held note.
+        phrase.rows[1] = row(None, 0.0);
+        phrase.rows[2] = row(Some(62), 0.75);
+        phrases.0[0] = Some(phrase);
+
+        let mut notes = Vec::new();
+        let mut sequencer = playing(PlaySource::Phrase(0), ChannelCursor::default());
+
+        for at in [0, 100, 200] {
+            sequencer.play_rows(at, 100, &song, &chains, &phrases, &mut notes);
+            sequencer.advance(0, &song, &chains);
+        }
+
+        let events: Vec<_> = notes
+            .into_iter()
+            .map(|note| note.event)
+            .filter(|event| !matches!(event, NoteEvent::Program { .. }))
+            .collect();
+
+        assert_eq!(
+            events,
+            [
+                NoteEvent::Volume {
+                    channel: 0,
+                    volume: 1.0,
+                },
+                NoteEvent::NoteOn {
+                    channel: 0,
+                    instrument: song.default_instrument[0],
+                    notes: vec![60],
+                    velocity: 0.5,
+                },
+                NoteEvent::Volume {
+                    channel: 0,
+                    volume: 0.0,
+                },
+                // the fade is over once the next note plays.
+                NoteEvent::Volume {
+                    channel: 0,
+                    volume: 1.0,
+                },
+                NoteEvent::NoteOn {
+                    channel: 0,
+                    instrument: song.default_instrument[0],
+                    notes: vec![62],
+                    velocity: 0.75,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use crate::{
+    config::{audio::AudioConfig, midi::MidiConfig},
+    controls::MyGamepad,
+    pygame_coms::DisplayCursor,
+    synth::export::ExportSong,
+    tracker_state::StateUpdated,
+    ExitMenuState, ScreenState,
+};
+use bevy::{log::*, prelude::*};
+
+/// the cursor of the screen the settings screen was entered from.
+#[derive(Debug, Clone, Default, Resource)]
+struct ReturnCursor(DisplayCursor);
+
+pub struct SettingsMenuPlugin;
+
+impl Plugin for SettingsMenuPlugin {
+    fn build(&self, app: &mut App) {
+        debug!("tracker_backend::settings_menu::SettingsMenuPlugin loaded");
+
+        app.init_resource::<ReturnCursor>()
+            .add_event::<EditParam>()
+            .add_systems(
+                Update,
+                movement
+                    .run_if(in_state(ScreenState::Settings))
+                    .run_if(not(in_state(ExitMenuState::Opened))),
+            )
+            .add_systems(
+                Update,
+                set_select
+                    .run_if(in_state(ScreenState::Settings))
+                    .run_if(not(in_state(ExitMenuState::Opened))),
+            )
+            .add_systems(
+                Update,
+                change_param
+                    .run_if(in_state(ScreenState::Settings))
+                    .run_if(not(in_state(ExitMenuState::Opened))),
+            )
+            .add_systems(
+                Update,
+                edit_param
+                    .run_if(in_state(ScreenState::Settings))
+                    .run_if(not(in_state(ExitMenuState::Opened))),
+            )
+            .add_systems(
+                Update,
+                export
+                    .run_if(in_state(ScreenState::Settings))
+                    .run_if(not(in_state(ExitMenuState::Opened))),
+            )
+            .add_systems(OnEnter(ScreenState::Settings), set_selected)
+            .add_systems(
+                OnEnter(ScreenState::Settings),
+                (save_cursor, set_cursor).chain(),
+            )
+            .add_systems(OnExit(ScreenState::Settings), restore_cursor);
+    }
+}
+
+#[derive(Event, Debug, Default)]
+struct EditParam {
+    /// how many steps to move the parameter under the cursor by.
+    delta: i32,
+}
+
+fn set_selected(mut display_cursor: ResMut<DisplayCursor>) {
+    display_cursor.selected = false;
+}
+
+fn save_cursor(display_cursor: Res<DisplayCursor>, mut return_cursor: ResMut<ReturnCursor>) {
+    return_cursor.0 = display_cursor.clone();
+}
+
+fn restore_cursor(mut display_cursor: ResMut<DisplayCursor>, return_cursor: Res<ReturnCursor>) {
+    display_cursor.row = return_cursor.0.row;
+    display_cursor.col = return_cursor.0.col;
+}
+
+fn set_cursor(mut display_cursor: ResMut<DisplayCursor>) {
+    display_cursor.row = 0;
+    display_cursor.col = 0;
+}
+
+fn set_select(
+    mut display_cursor: ResMut<DisplayCursor>,
+    mut state_updated: EventWriter<StateUpdated>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    my_gamepad: Option<Res<MyGamepad>>,
+) {
+    let Some(&MyGamepad(gamepad)) = my_gamepad.as_deref() else {
+        // no gamepad is connected
+        return;
+    };
+
+    let a_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::East,
+    };
+
+    if buttons.pressed(a_button) != display_cursor.selected {
+        display_cursor.selected = buttons.pressed(a_button);
+        state_updated.send_default();
+    }
+}
+
+fn change_param(
+    display_cursor: Res<DisplayCursor>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    my_gamepad: Option<Res<MyGamepad>>,
+    mut edit_param_event: EventWriter<EditParam>,
+) {
+    let Some(&MyGamepad(gamepad)) = my_gamepad.as_deref() else {
+        // no gamepad is connected
+        return;
+    };
+
+    let button = |button_type| GamepadButton {
+        gamepad,
+        button_type,
+    };
+
+    if !buttons.pressed(button(GamepadButtonType::East)) || !display_cursor.selected {
+        return;
+    }
+
+    for (button_type, delta) in [
+        (GamepadButtonType::DPadUp, 1),
+        (GamepadButtonType::DPadDown, -1),
+        (GamepadButtonType::DPadRight, 10),
+        (GamepadButtonType::DPadLeft, -10),
+    ] {
+        if buttons.just_released(button(button_type)) {
+            edit_param_event.send(EditParam { delta });
+        }
+    }
+}
+
+fn edit_param(
+    mut audio_config: ResMut<AudioConfig>,
+    mut midi_config: ResMut<MidiConfig>,
+    display_cursor: Res<DisplayCursor>,
+    mut events: EventReader<EditParam>,
+    mut state_updated: EventWriter<StateUpdated>,
+) {
+    let n_audio = audio_config.n_params();
+
+    for ev in events.read() {
+        // only the config being edited is borrowed mutably, so the other output isn't reopened.
+        let (mut params, row) = if display_cursor.row < n_audio {
+            (audio_config.params(), display_cursor.row)
+        } else {
+            (midi_config.params(), display_cursor.row - n_audio)
+        };
+
+        if let Some(param) = params.get_mut(row) {
+            param.shift(ev.delta);
+            state_updated.send_default();
+        } else {
+            error!(
+                "row {} is past the last settings parameter",
+                display_cursor.row
+            );
+        }
+    }
+}
+
+/// exports the song when B is pressed on its own.
+fn export(
+    buttons: Res<ButtonInput<GamepadButton>>,
+    my_gamepad: Option<Res<MyGamepad>>,
+    mut export_events: EventWriter<ExportSong>,
+) {
+    let Some(&MyGamepad(gamepad)) = my_gamepad.as_deref() else {
+        // no gamepad is connected
+        return;
+    };
+
+    let b_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::South,
+    };
+    let a_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::East,
+    };
+
+    if buttons.just_released(b_button) && !buttons.pressed(a_button) {
+        export_events.send_default();
+    }
+}
+
+fn movement(
+    buttons: Res<ButtonInput<GamepadButton>>,
+    my_gamepad: Option<Res<MyGamepad>>,
+    mut display_cursor: ResMut<DisplayCursor>,
+    mut state_updated: EventWriter<StateUpdated>,
+    gamepads: Res<Gamepads>,
+    audio_config: Res<AudioConfig>,
+    midi_config: Res<MidiConfig>,
+) {
+    if display_cursor.selected {
+        return;
+    }
+
+    let Some(&MyGamepad(gamepad)) = my_gamepad.as_deref() else {
+        // no gamepad is connected
+        return;
+    };
+
+    let button = |button_type| GamepadButton {
+        gamepad,
+        button_type,
+    };
+
+    let start_button = if let Some(name) = gamepads.name(gamepad)
+        && name.starts_with("PS5")
+    {
+        button(GamepadButtonType::Start)
+    } else {
+        button(GamepadButtonType::Select)
+    };
+
+    if buttons.pressed(start_button) {
+        return;
+    }
+
+    let n_rows = audio_config.n_params() + midi_config.n_params();
+    let mut row = display_cursor.row;
+
+    if buttons.just_released(button(GamepadButtonType::DPadUp)) {
+        row = (row + n_rows - 1) % n_rows;
+    }
+
+    if buttons.just_released(button(GamepadButtonType::DPadDown)) {
+        row = (row + 1) % n_rows;
+    }
+
+    if row != display_cursor.row {
+        display_cursor.row = row;
+        state_updated.send_default();
+    }
+}
@@ -0,0 +1,355 @@
+use super::filter::{Filter, FilterMode};
+use crate::{
+    params::{Param, Range},
+    pygame_coms::{Index, Note},
+};
+use pyo3::pyclass;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+/// the number of drums in a kit.
+pub const N_DRUMS: usize = 7;
+/// how long a choked hi-hat takes to fade out.
+const CHOKE_SECONDS: f32 = 0.005;
+/// once a drums envelope is below this it is silent and can be dropped.
+const SILENT: f32 = 1.0e-4;
+/// the frequencies of the square waves that make up the metallic sound of hats and cymbals, from
+/// the TR-808.
+const METAL_FREQS: [f32; 6] = [205.3, 304.4, 369.6, 522.7, 540.0, 800.0];
+
+/// the names of each drums tune, decay, tone, and level parameters.
+const PARAM_NAMES: [[&str; 4]; N_DRUMS] = [
+    ["KICK TUNE", "KICK DECAY", "KICK TONE", "KICK LEVEL"],
+    ["SNARE TUNE", "SNARE DECAY", "SNARE TONE", "SNARE LEVEL"],
+    ["CH TUNE", "CH DECAY", "CH TONE", "CH LEVEL"],
+    ["OH TUNE", "OH DECAY", "OH TONE", "OH LEVEL"],
+    ["CLAP TUNE", "CLAP DECAY", "CLAP TONE", "CLAP LEVEL"],
+    ["TOM TUNE", "TOM DECAY", "TOM TONE", "TOM LEVEL"],
+    ["CYM TUNE", "CYM DECAY", "CYM TONE", "CYM LEVEL"],
+];
+
+#[pyclass(module = "tracker_backend", eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum DrumVoice {
+    Kick,
+    Snare,
+    ClosedHat,
+    OpenHat,
+    Clap,
+    Tom,
+    Cymbal,
+}
+
+impl DrumVoice {
+    pub const ALL: [Self; N_DRUMS] = [
+        Self::Kick,
+        Self::Snare,
+        Self::ClosedHat,
+        Self::OpenHat,
+        Self::Clap,
+        Self::Tom,
+        Self::Cymbal,
+    ];
+
+    /// the drum a note plays. drums are on the white keys, starting with the kick on C. black keys
+    /// play the drum of the white key below them.
+    pub fn from_note(note: Note) -> Self {
+        match note % 12 {
+            0 | 1 => Self::Kick,
+            2 | 3 => Self::Snare,
+            4 => Self::Clap,
+            5 | 6 => Self::Tom,
+            7 | 8 => Self::ClosedHat,
+            9 | 10 => Self::OpenHat,
+            _ => Self::Cymbal,
+        }
+    }
+
+    pub fn is_hat(&self) -> bool {
+        matches!(self, Self::ClosedHat | Self::OpenHat)
+    }
+}
+
+/// parameters of one drum of a kit.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DrumParams {
+    /// semitones the drum is tuned up or down by.
+    pub tune: f32,
+    /// seconds for the drum to fade out.
+    pub decay: f32,
+    /// what this changes depends on the drum. (the click of a kick, the noise of a snare, etc)
+    pub tone: f32,
+    pub level: f32,
+}
+
+impl DrumParams {
+    const fn new(decay: f32) -> Self {
+        Self {
+            tune: 0.0,
+            decay,
+            tone: 0.5,
+            level: 0.8,
+        }
+    }
+}
+
+impl Default for DrumParams {
+    fn default() -> Self {
+        Self::new(0.4)
+    }
+}
+
+/// a synthesized drum kit, used by instruments with the percussion output.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DrumKit {
+    /// in the same order as `DrumVoice::ALL`.
+    pub drums: [DrumParams; N_DRUMS],
+}
+
+impl Default for DrumKit {
+    fn default() -> Self {
+        Self {
+            drums: [
+                DrumParams::new(0.4),
+                DrumParams::new(0.2),
+                DrumParams::new(0.05),
+                DrumParams::new(0.4),
+                DrumParams::new(0.25),
+                DrumParams::new(0.35),
+                DrumParams::new(1.2),
+            ],
+        }
+    }
+}
+
+impl DrumKit {
+    pub fn params(&mut self) -> Vec<Param<'_>> {
+        self.drums
+            .iter_mut()
+            .zip(PARAM_NAMES)
+            .flat_map(|(drum, names)| {
+                [
+                    Param::new(names[0], &mut drum.tune, Range::new(-24.0, 24.0, 1.0)),
+                    Param::new(names[1], &mut drum.decay, Range::new(0.01, 4.0, 0.01)),
+                    Param::new(names[2], &mut drum.tone, Range::new(0.0, 1.0, 0.05)),
+                    Param::new(names[3], &mut drum.level, Range::new(0.0, 1.0, 0.05)),
+                ]
+            })
+            .collect()
+    }
+
+    pub fn get(&self, voice: DrumVoice) -> &DrumParams {
+        &self.drums[voice as usize]
+    }
+}
+
+/// one hit of a drum. lives on the audio thread.
+#[derive(Debug, Clone)]
+pub struct Drum {
+    pub channel: usize,
+    pub instrument: Index,
+    pub voice: DrumVoice,
+    velocity: f32,
+    /// seconds since the drum was hit.
+    time: f32,
+    env: f32,
+    phase: f32,
+    metal_phases: [f32; 6],
+    seed: u32,
+    filter: Filter,
+    choked: bool,
+}
+
+impl Drum {
+    pub fn new(channel: usize, instrument: Index, voice: DrumVoice, velocity: f32) -> Self {
+        Self {
+            channel,
+            instrument,
+            voice,
+            velocity,
+            time: 0.0,
+            env: 1.0,
+            phase: 0.0,
+            metal_phases: [0.0; 6],
+            seed: 0x1234_5678,
+            filter: Filter::default(),
+            choked: false,
+        }
+    }
+
+    /// quickly fades the drum out, used when one hi-hat cuts off another.
+    pub fn choke(&mut self) {
+        self.choked = true;
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.env < SILENT
+    }
+
+    fn noise(&mut self) -> f32 {
+        // xorshift
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+
+        (self.seed as f32 / u32::MAX as f32) * 2.0 - 1.0
+    }
+
+    /// a sine wave at `freq`.
+    fn sine(&mut self, freq: f32, sample_rate: f32) -> f32 {
+        self.phase = (self.phase + freq / sample_rate) % 1.0;
+
+        (self.phase * TAU).sin()
+    }
+
+    /// the square waves of the hats and cymbal.
+    fn metal(&mut self, tune: f32, sample_rate: f32) -> f32 {
+        let mut sample = 0.0;
+
+        for (phase, freq) in self.metal_phases.iter_mut().zip(METAL_FREQS) {
+            *phase = (*phase + freq * tune / sample_rate) % 1.0;
+            sample += if *phase < 0.5 { 1.0 } else { -1.0 };
+        }
+
+        sample / METAL_FREQS.len() as f32
+    }
+
+    /// generates the next sample of the drum.
+    pub fn next(&mut self, params: &DrumParams, sample_rate: f32) -> f32 {
+        let t = self.time;
+        let tune = 2.0_f32.powf(params.tune / 12.0);
+        let tone = params.tone;
+
+        let sample = match self.voice {
+            DrumVoice::Kick => {
+                let freq = 50.0 * tune * (1.0 + 3.0 * (-t / 0.03).exp());
+                let click = self.noise() * (-t / 0.003).exp() * tone;
+
+                self.sine(freq, sample_rate) + click
+            }
+            DrumVoice::Tom => {
+                let freq = 110.0 * tune * (1.0 + 0.5 * (-t / 0.05).exp());
+                let noise = self.noise() * (-t / 0.02).exp() * tone * 0.3;
+
+                self.sine(freq, sample_rate) + noise
+            }
+            DrumVoice::Snare => {
+                let body = self.sine(185.0 * tune, sample_rate) * (-t / 0.08).exp();
+                let noise = self.noise();
+                let noise = self.filter.process(
+                    noise,
+                    FilterMode::HighPass,
+                    90.0 + params.tune,
+                    0.0,
+                    sample_rate,
+                );
+
+                body * (1.0 - tone) + noise * tone
+            }
+            DrumVoice::Clap => {
+                let noise = self.noise();
+                let noise = self.filter.process(
+                    noise,
+                    FilterMode::BandPass,
+                    88.0 + params.tune,
+                    0.3,
+                    sample_rate,
+                );
+                // three quick claps before the tail.
+                let burst = if t < 0.03 {
+                    (-(t % 0.01) / 0.002).exp()
+                } else {
+                    1.0
+                };
+
+                noise * burst * (0.5 + tone)
+            }
+            DrumVoice::ClosedHat | DrumVoice::OpenHat | DrumVoice::Cymbal => {
+                let metal = self.metal(tune, sample_rate);
+                let noise = self.noise();
+                let cutoff = if self.voice == DrumVoice::Cymbal {
+                    100.0
+                } else {
+                    112.0
+                };
+
+                self.filter.process(
+                    metal * (1.0 - tone) + noise * tone,
+                    FilterMode::HighPass,
+                    cutoff + params.tune,
+                    0.2,
+                    sample_rate,
+                )
+            }
+        };
+
+        let sample = sample * self.env * params.level * self.velocity;
+
+        let decay = if self.choked {
+            CHOKE_SECONDS
+        } else {
+            params.decay.max(0.005)
+        };
+        // falls by 60 dB over the decay time.
+        self.env *= (-6.9 / (decay * sample_rate)).exp();
+        self.time += 1.0 / sample_rate;
+
+        sample
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48_000.0;
+
+    #[test]
+    fn decays_to_silence() {
+        let kit = DrumKit::default();
+
+        for voice in DrumVoice::ALL {
+            let params = kit.get(voice);
+            let mut drum = Drum::new(0, 0, voice, 1.0);
+            let samples: Vec<f32> = (0..(params.decay * 1.5 * SAMPLE_RATE) as usize)
+                .map(|_| drum.next(params, SAMPLE_RATE))
+                .collect();
+
+            assert!(
+                samples.iter().any(|s| s.abs() > 0.05),
+                "{voice:?} is silent"
+            );
+            assert!(samples.iter().all(|s| s.is_finite()));
+            assert!(drum.is_done(), "{voice:?} never finishes");
+        }
+    }
+
+    #[test]
+    fn choke() {
+        let kit = DrumKit::default();
+        let params = kit.get(DrumVoice::OpenHat);
+        let mut drum = Drum::new(0, 0, DrumVoice::OpenHat, 1.0);
+
+        drum.next(params, SAMPLE_RATE);
+        drum.choke();
+
+        for _ in 0..(CHOKE_SECONDS * 1.5 * SAMPLE_RATE) as usize {
+            drum.next(params, SAMPLE_RATE);
+        }
+
+        assert!(drum.is_done());
+    }
+
+    #[test]
+    fn note_mapping() {
+        assert_eq!(DrumVoice::from_note(48), DrumVoice::Kick);
+        assert_eq!(DrumVoice::from_note(50), DrumVoice::Snare);
+        assert_eq!(DrumVoice::from_note(55), DrumVoice::ClosedHat);
+        assert_eq!(DrumVoice::from_note(58), DrumVoice::OpenHat);
+        assert_eq!(DrumVoice::from_note(59), DrumVoice::Cymbal);
+    }
+}
@@ -0,0 +1,325 @@
+use crate::params::{Choice, Param, Range};
+use pyo3::{pyclass, pymethods};
+use serde::{Deserialize, Serialize};
+
+/// the channel the sidechain listens to, the percussion channel.
+pub const SIDECHAIN_SOURCE: usize = 3;
+/// levels are never taken below this, in dB, so silence doesn't turn into -infinity.
+const FLOOR_DB: f32 = -120.0;
+
+fn to_db(level: f32) -> f32 {
+    (20.0 * level.log10()).max(FLOOR_DB)
+}
+
+fn from_db(db: f32) -> f32 {
+    10.0_f32.powf(db / 20.0)
+}
+
+/// how much of the gap to a target a one pole smoother closes each sample, for a time in seconds.
+fn smoothing(seconds: f32, sample_rate: f32) -> f32 {
+    (-1.0 / (seconds.max(1.0e-4) * sample_rate)).exp()
+}
+
+/// the channels ducked by the percussion channel.
+#[pyclass(module = "tracker_backend", eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum SidechainTarget {
+    #[default]
+    Off,
+    Bass,
+    Leads,
+    /// both leads and the bass.
+    All,
+}
+
+impl Choice for SidechainTarget {
+    const ALL: &'static [Self] = &[Self::Off, Self::Bass, Self::Leads, Self::All];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Off => "OFF",
+            Self::Bass => "BASS",
+            Self::Leads => "LEADS",
+            Self::All => "ALL",
+        }
+    }
+}
+
+impl SidechainTarget {
+    /// true if `channel` is ducked.
+    pub fn ducks(&self, channel: usize) -> bool {
+        match self {
+            Self::Off => false,
+            Self::Bass => channel == 2,
+            Self::Leads => channel < 2,
+            Self::All => channel < SIDECHAIN_SOURCE,
+        }
+    }
+}
+
+/// the settings of the master bus compressor, limiter, and sidechain ducking. saved with the
+/// song.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Dynamics {
+    pub compressor: bool,
+    /// the level, in dB, the compressor starts working above.
+    pub threshold: f32,
+    /// how many dB over the threshold the input has to go for the output to go 1 dB over.
+    pub ratio: f32,
+    /// attack and release times are in seconds.
+    pub attack: f32,
+    pub release: f32,
+    /// gain, in dB, added after the compressor.
+    pub makeup: f32,
+    pub limiter: bool,
+    /// the level, in dB, the limiter never lets the output go over.
+    pub ceiling: f32,
+    pub sidechain: SidechainTarget,
+    /// how far the ducked channels are turned down when the percussion is at full level, from
+    /// 0.0 to 1.0.
+    pub duck: f32,
+    /// how long, in seconds, the ducked channels take to come back up.
+    pub duck_release: f32,
+}
+
+impl Default for Dynamics {
+    fn default() -> Self {
+        Self {
+            compressor: true,
+            threshold: -6.0,
+            ratio: 2.0,
+            attack: 0.005,
+            release: 0.15,
+            makeup: 0.0,
+            limiter: true,
+            ceiling: -0.3,
+            sidechain: SidechainTarget::Off,
+            duck: 0.5,
+            duck_release: 0.2,
+        }
+    }
+}
+
+impl Dynamics {
+    pub fn params(&mut self) -> Vec<Param<'_>> {
+        vec![
+            Param::choice("COMP", &mut self.compressor),
+            Param::new(
+                "COMP THRESH",
+                &mut self.threshold,
+                Range::new(-40.0, 0.0, 1.0),
+            ),
+            Param::new("COMP RATIO", &mut self.ratio, Range::new(1.0, 20.0, 0.5)),
+            Param::new("COMP ATTACK", &mut self.attack, Range::new(0.0, 0.1, 0.001)),
+            Param::new(
+                "COMP RELEASE",
+                &mut self.release,
+                Range::new(0.01, 1.0, 0.01),
+            ),
+            Param::new("COMP MAKEUP", &mut self.makeup, Range::new(0.0, 24.0, 1.0)),
+            Param::choice("LIMITER", &mut self.limiter),
+            Param::new(
+                "LIM CEILING",
+                &mut self.ceiling,
+                Range::new(-12.0, 0.0, 0.1),
+            ),
+            Param::choice("SIDECHAIN", &mut self.sidechain),
+            Param::new("DUCK", &mut self.duck, Range::new(0.0, 1.0, 0.05)),
+            Param::new(
+                "DUCK RELEASE",
+                &mut self.duck_release,
+                Range::new(0.01, 1.0, 0.01),
+            ),
+        ]
+    }
+
+    pub fn n_params(&self) -> usize {
+        let mut dynamics = *self;
+
+        dynamics.params().len()
+    }
+}
+
+#[pymethods]
+impl Dynamics {
+    /// the name and value of each parameter, for display on the effects screen.
+    fn rows(&self) -> Vec<(String, String)> {
+        let mut dynamics = *self;
+
+        dynamics.params().iter().map(Param::row).collect()
+    }
+}
+
+/// follows the level of the percussion channel and works out how far to duck the others.
+/// lives on the audio thread.
+#[derive(Debug, Clone, Default)]
+pub struct Ducker {
+    env: f32,
+    /// the gain of the ducked channels for each frame of the last buffer.
+    gains: Vec<f32>,
+}
+
+impl Ducker {
+    /// follows the interleaved stereo `sidechain`, returning the ducked gain for each frame.
+    pub fn process(&mut self, params: &Dynamics, sidechain: &[f32], sample_rate: f32) -> &[f32] {
+        let release = smoothing(params.duck_release, sample_rate);
+        self.gains.clear();
+
+        for frame in sidechain.chunks_exact(2) {
+            let level = frame[0].abs().max(frame[1].abs()).min(1.0);
+
+            // hits duck straight away, so the duck lands with the kick.
+            self.env = if level > self.env {
+                level
+            } else {
+                level + (self.env - level) * release
+            };
+
+            self.gains.push(1.0 - self.env * params.duck);
+        }
+
+        &self.gains
+    }
+}
+
+/// the master bus compressor and limiter. lives on the audio thread.
+#[derive(Debug, Clone)]
+pub struct MasterDynamics {
+    sample_rate: f32,
+    /// the compressors gain reduction, in dB.
+    comp_env: f32,
+    /// the limiters gain, as a multiplier.
+    limit_gain: f32,
+}
+
+impl MasterDynamics {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            comp_env: 0.0,
+            limit_gain: 1.0,
+        }
+    }
+
+    /// compresses then limits `out`, interleaved stereo, in place. returns the most gain
+    /// reduction, in dB, over the buffer.
+    pub fn process(&mut self, params: &Dynamics, out: &mut [f32]) -> f32 {
+        let attack = smoothing(params.attack, self.sample_rate);
+        let release = smoothing(params.release, self.sample_rate);
+        // the limiter lets go slowly so it doesn't pump.
+        let limit_release = smoothing(0.1, self.sample_rate);
+        let slope = 1.0 - 1.0 / params.ratio.max(1.0);
+        let makeup = from_db(params.makeup);
+        let ceiling = from_db(params.ceiling);
+        let mut most_reduction = 0.0_f32;
+
+        for frame in out.chunks_exact_mut(2) {
+            let mut gain = 1.0;
+            let mut reduction = 0.0;
+
+            if params.compressor {
+                let level = to_db(frame[0].abs().max(frame[1].abs()));
+                let target = (level - params.threshold).max(0.0) * slope;
+                let coefficient = if target > self.comp_env {
+                    attack
+                } else {
+                    release
+                };
+
+                self.comp_env = target + (self.comp_env - target) * coefficient;
+                gain = from_db(-self.comp_env) * makeup;
+                reduction += self.comp_env;
+            }
+
+            if params.limiter {
+                let peak = frame[0].abs().max(frame[1].abs()) * gain;
+                let needed = if peak > ceiling { ceiling / peak } else { 1.0 };
+
+                self.limit_gain = if needed < self.limit_gain {
+                    needed
+                } else {
+                    needed + (self.limit_gain - needed) * limit_release
+                };
+                gain *= self.limit_gain;
+                reduction -= to_db(self.limit_gain);
+            }
+
+            most_reduction = most_reduction.max(reduction);
+
+            frame[0] *= gain;
+            frame[1] *= gain;
+
+            if params.limiter {
+                // anything the gain didn't catch is clipped, so the ceiling is never crossed.
+                frame[0] = frame[0].clamp(-ceiling, ceiling);
+                frame[1] = frame[1].clamp(-ceiling, ceiling);
+            }
+        }
+
+        most_reduction
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48_000.0;
+
+    #[test]
+    fn limiter_holds_the_ceiling() {
+        let params = Dynamics {
+            compressor: false,
+            ..Dynamics::default()
+        };
+        let mut dynamics = MasterDynamics::new(SAMPLE_RATE);
+        let mut out: Vec<f32> = (0..2048).map(|i| (i as f32 * 0.05).sin() * 3.0).collect();
+
+        let reduction = dynamics.process(&params, &mut out);
+
+        assert!(out.iter().all(|s| s.abs() <= from_db(params.ceiling)));
+        assert!(reduction > 9.0);
+    }
+
+    #[test]
+    fn compressor_reduces_loud_signals() {
+        let params = Dynamics {
+            limiter: false,
+            threshold: -12.0,
+            ratio: 4.0,
+            ..Dynamics::default()
+        };
+        let mut dynamics = MasterDynamics::new(SAMPLE_RATE);
+        let mut quiet = vec![0.1; 4800];
+        let mut loud = vec![1.0; 4800];
+
+        assert_eq!(dynamics.process(&params, &mut quiet), 0.0);
+
+        let reduction = dynamics.process(&params, &mut loud);
+
+        // 12 dB over the threshold at 4:1 is 9 dB of reduction.
+        assert!((reduction - 9.0).abs() < 0.1);
+        assert!((loud[4799] - from_db(-9.0)).abs() < 0.01);
+    }
+
+    #[test]
+    fn ducker_recovers() {
+        let params = Dynamics {
+            sidechain: SidechainTarget::Bass,
+            duck: 1.0,
+            ..Dynamics::default()
+        };
+        let mut ducker = Ducker::default();
+        // two seconds, ten times the duck release.
+        let mut sidechain = vec![0.0; SAMPLE_RATE as usize * 4];
+        sidechain[0] = 1.0;
+
+        let gains = ducker.process(&params, &sidechain, SAMPLE_RATE);
+
+        assert_eq!(gains[0], 0.0);
+        assert!(gains[gains.len() - 1] > 0.99);
+        assert!(params.sidechain.ducks(2) && !params.sidechain.ducks(0));
+    }
+}
@@ -0,0 +1,381 @@
+use super::{
+    filter::{DENORMAL, MAX_CUTOFF},
+    note_freq,
+};
+use crate::{
+    config::ui::Bpm,
+    params::{Choice, Param, Range},
+    sequencer::ROWS_PER_BEAT,
+};
+use pyo3::{pyclass, pymethods};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+/// the longest the delay can be, in seconds. longer delay times are cut short.
+const MAX_DELAY: f32 = 4.0;
+/// the lengths, in samples at 44.1 kHz, of the reverbs comb filters. the same as Freeverb.
+const COMB_TUNINGS: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+/// the lengths, in samples at 44.1 kHz, of the reverbs all pass filters.
+const ALLPASS_TUNINGS: [usize; 4] = [556, 441, 341, 225];
+/// how many samples longer the right sides filters are than the left, so the reverb is wide.
+const STEREO_SPREAD: usize = 23;
+/// the reverb sums a lot of combs, so its input is turned down a lot to make up for it.
+const REVERB_GAIN: f32 = 0.045;
+
+/// what the delay time is counted in.
+#[pyclass(module = "tracker_backend", eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum DelaySync {
+    /// phrase rows.
+    #[default]
+    Rows,
+    Beats,
+}
+
+impl Choice for DelaySync {
+    const ALL: &'static [Self] = &[Self::Rows, Self::Beats];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Rows => "ROWS",
+            Self::Beats => "BEATS",
+        }
+    }
+}
+
+/// the settings of the send effects, saved with the song.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Effects {
+    /// the delay time, in `delay_sync` units.
+    pub delay_time: u8,
+    pub delay_sync: DelaySync,
+    /// how much of each echo is fed back into the delay, from 0.0 to 0.95.
+    pub delay_feedback: f32,
+    /// the cutoff, as a MIDI note, of the high pass filter the echoes go through.
+    pub delay_high_pass: f32,
+    /// the cutoff, as a MIDI note, of the low pass filter the echoes go through.
+    pub delay_low_pass: f32,
+    /// how loud the echoes are mixed back in.
+    pub delay_mix: f32,
+    /// how long the reverb rings, from 0.0 to 1.0.
+    pub reverb_size: f32,
+    /// how quickly the highs of the reverb die away, from 0.0 to 1.0.
+    pub reverb_damping: f32,
+    /// how loud the reverb is mixed back in.
+    pub reverb_mix: f32,
+}
+
+impl Default for Effects {
+    fn default() -> Self {
+        Self {
+            delay_time: 3,
+            delay_sync: DelaySync::Rows,
+            delay_feedback: 0.4,
+            delay_high_pass: 40.0,
+            delay_low_pass: 110.0,
+            delay_mix: 0.5,
+            reverb_size: 0.6,
+            reverb_damping: 0.5,
+            reverb_mix: 0.5,
+        }
+    }
+}
+
+impl Effects {
+    pub fn params(&mut self) -> Vec<Param<'_>> {
+        let level = Range::new(0.0, 1.0, 0.05);
+        let cutoff = Range::new(0.0, MAX_CUTOFF, 1.0);
+
+        vec![
+            Param::new("DLY TIME", &mut self.delay_time, Range::new(1.0, 16.0, 1.0)),
+            Param::choice("DLY SYNC", &mut self.delay_sync),
+            Param::new(
+                "DLY FEEDBACK",
+                &mut self.delay_feedback,
+                Range::new(0.0, 0.95, 0.05),
+            ),
+            Param::new("DLY HIGH PASS", &mut self.delay_high_pass, cutoff),
+            Param::new("DLY LOW PASS", &mut self.delay_low_pass, cutoff),
+            Param::new("DLY MIX", &mut self.delay_mix, level),
+            Param::new("REV SIZE", &mut self.reverb_size, level),
+            Param::new("REV DAMPING", &mut self.reverb_damping, level),
+            Param::new("REV MIX", &mut self.reverb_mix, level),
+        ]
+    }
+
+    pub fn n_params(&self) -> usize {
+        let mut effects = *self;
+
+        effects.params().len()
+    }
+
+    /// the delay time in seconds, at `tempo`.
+    pub fn delay_seconds(&self, tempo: Bpm) -> f32 {
+        let beats = match self.delay_sync {
+            DelaySync::Rows => self.delay_time as f32 / ROWS_PER_BEAT,
+            DelaySync::Beats => self.delay_time as f32,
+        };
+
+        beats * 60.0 / tempo.max(1) as f32
+    }
+}
+
+#[pymethods]
+impl Effects {
+    /// the name and value of each parameter, for display on the effects screen.
+    fn rows(&self) -> Vec<(String, String)> {
+        let mut effects = *self;
+
+        effects.params().iter().map(Param::row).collect()
+    }
+}
+
+/// keeps denormals out of the feedback loops, they are very slow on some ARM CPUs.
+pub(super) fn flush(x: f32) -> f32 {
+    if x.abs() < DENORMAL {
+        0.0
+    } else {
+        x
+    }
+}
+
+/// a 6 dB per octave filter.
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct OnePole {
+    z: f32,
+}
+
+impl OnePole {
+    /// the coefficient for a cutoff, as a MIDI note.
+    pub(super) fn coefficient(cutoff: f32, sample_rate: f32) -> f32 {
+        1.0 - (-TAU * note_freq(cutoff) / sample_rate).exp()
+    }
+
+    pub(super) fn low_pass(&mut self, x: f32, coefficient: f32) -> f32 {
+        self.z = flush(self.z + coefficient * (x - self.z));
+        self.z
+    }
+
+    pub(super) fn high_pass(&mut self, x: f32, coefficient: f32) -> f32 {
+        x - self.low_pass(x, coefficient)
+    }
+}
+
+/// a stereo, tempo synced, delay. lives on the audio thread.
+#[derive(Debug, Clone)]
+pub struct Delay {
+    sample_rate: f32,
+    buffers: [Vec<f32>; 2],
+    pos: usize,
+    high_pass: [OnePole; 2],
+    low_pass: [OnePole; 2],
+}
+
+impl Delay {
+    pub fn new(sample_rate: f32) -> Self {
+        let len = (MAX_DELAY * sample_rate) as usize;
+
+        Self {
+            sample_rate,
+            buffers: [vec![0.0; len], vec![0.0; len]],
+            pos: 0,
+            high_pass: [OnePole::default(); 2],
+            low_pass: [OnePole::default(); 2],
+        }
+    }
+
+    /// adds the echoes of `input` to `out`. both are interleaved stereo.
+    pub fn process(&mut self, params: &Effects, tempo: Bpm, input: &[f32], out: &mut [f32]) {
+        let len = self.buffers[0].len();
+        let delay = ((params.delay_seconds(tempo) * self.sample_rate) as usize).clamp(1, len - 1);
+        let high_pass = OnePole::coefficient(params.delay_high_pass, self.sample_rate);
+        let low_pass = OnePole::coefficient(params.delay_low_pass, self.sample_rate);
+
+        for (input, out) in input.chunks_exact(2).zip(out.chunks_exact_mut(2)) {
+            let read = (self.pos + len - delay) % len;
+
+            for side in 0..2 {
+                let echo = self.buffers[side][read];
+                let echo = self.high_pass[side].high_pass(echo, high_pass);
+                let echo = self.low_pass[side].low_pass(echo, low_pass);
+
+                self.buffers[side][self.pos] = input[side] + echo * params.delay_feedback;
+                out[side] += echo * params.delay_mix;
+            }
+
+            self.pos = (self.pos + 1) % len;
+        }
+    }
+}
+
+/// a feedback comb filter with a low pass in its loop.
+#[derive(Debug, Clone)]
+struct Comb {
+    buffer: Vec<f32>,
+    pos: usize,
+    damped: f32,
+}
+
+impl Comb {
+    fn new(len: usize) -> Self {
+        Self {
+            buffer: vec![0.0; len.max(1)],
+            pos: 0,
+            damped: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: f32, feedback: f32, damping: f32) -> f32 {
+        let out = self.buffer[self.pos];
+
+        self.damped = flush(out * (1.0 - damping) + self.damped * damping);
+        self.buffer[self.pos] = input + self.damped * feedback;
+        self.pos = (self.pos + 1) % self.buffer.len();
+
+        out
+    }
+}
+
+/// a Schroeder all pass filter, it smears the echoes of the combs together.
+#[derive(Debug, Clone)]
+struct Allpass {
+    buffer: Vec<f32>,
+    pos: usize,
+}
+
+impl Allpass {
+    fn new(len: usize) -> Self {
+        Self {
+            buffer: vec![0.0; len.max(1)],
+            pos: 0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let delayed = self.buffer[self.pos];
+
+        self.buffer[self.pos] = flush(input + delayed * 0.5);
+        self.pos = (self.pos + 1) % self.buffer.len();
+
+        delayed - input
+    }
+}
+
+/// an algorithmic reverb, laid out like Freeverb. lives on the audio thread.
+#[derive(Debug, Clone)]
+pub struct Reverb {
+    combs: [Vec<Comb>; 2],
+    allpasses: [Vec<Allpass>; 2],
+}
+
+impl Reverb {
+    pub fn new(sample_rate: f32) -> Self {
+        let len = |tuning: usize, side: usize| {
+            ((tuning + STEREO_SPREAD * side) as f32 * sample_rate / 44_100.0) as usize
+        };
+        let combs = |side| COMB_TUNINGS.map(|tuning| Comb::new(len(tuning, side)));
+        let allpasses = |side| ALLPASS_TUNINGS.map(|tuning| Allpass::new(len(tuning, side)));
+
+        Self {
+            combs: [combs(0).into(), combs(1).into()],
+            allpasses: [allpasses(0).into(), allpasses(1).into()],
+        }
+    }
+
+    /// adds the reverb of `input` to `out`. both are interleaved stereo.
+    pub fn process(&mut self, params: &Effects, input: &[f32], out: &mut [f32]) {
+        let feedback = 0.7 + params.reverb_size.clamp(0.0, 1.0) * 0.28;
+        let damping = params.reverb_damping.clamp(0.0, 1.0) * 0.4;
+
+        for (input, out) in input.chunks_exact(2).zip(out.chunks_exact_mut(2)) {
+            let mono = (input[0] + input[1]) * REVERB_GAIN;
+
+            for ((out, combs), allpasses) in out
+                .iter_mut()
+                .zip(self.combs.iter_mut())
+                .zip(self.allpasses.iter_mut())
+            {
+                let mut wet = combs
+                    .iter_mut()
+                    .map(|comb| comb.process(mono, feedback, damping))
+                    .sum::<f32>();
+
+                for allpass in allpasses.iter_mut() {
+                    wet = allpass.process(wet);
+                }
+
+                *out += wet * params.reverb_mix;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48_000.0;
+
+    /// an impulse followed by `len` frames of silence.
+    fn impulse(len: usize) -> Vec<f32> {
+        let mut input = vec![0.0; len * 2];
+        input[0] = 1.0;
+        input[1] = 1.0;
+
+        input
+    }
+
+    #[test]
+    fn delay_follows_tempo() {
+        let params = Effects {
+            delay_time: 2,
+            delay_sync: DelaySync::Beats,
+            delay_high_pass: 0.0,
+            delay_low_pass: MAX_CUTOFF,
+            ..Effects::default()
+        };
+        let mut delay = Delay::new(SAMPLE_RATE);
+        let input = impulse(SAMPLE_RATE as usize);
+        let mut out = vec![0.0; input.len()];
+
+        // two beats at 120 BPM is a second, so the echo is just past the end.
+        delay.process(&params, 120, &input, &mut out);
+        assert!(out.iter().all(|s| *s == 0.0));
+
+        let input = impulse(SAMPLE_RATE as usize);
+        let mut out = vec![0.0; input.len()];
+        delay.process(&params, 240, &input, &mut out);
+        let first = out.iter().position(|s| s.abs() > 0.1).unwrap();
+
+        assert_eq!(first / 2, SAMPLE_RATE as usize / 2);
+    }
+
+    #[test]
+    fn reverb_rings_and_fades() {
+        let params = Effects::default();
+        let mut reverb = Reverb::new(SAMPLE_RATE);
+        let input = impulse(SAMPLE_RATE as usize * 4);
+        let mut out = vec![0.0; input.len()];
+
+        reverb.process(&params, &input, &mut out);
+        let peak = |samples: &[f32]| samples.iter().fold(0.0_f32, |peak, s| peak.max(s.abs()));
+        let second = SAMPLE_RATE as usize * 2;
+
+        assert!(peak(&out[..second]) > 0.01);
+        assert!(peak(&out[out.len() - second..]) < peak(&out[..second]) * 0.1);
+    }
+
+    #[test]
+    fn silence_in_silence_out() {
+        let params = Effects::default();
+        let input = vec![0.0; 2048];
+        let mut out = vec![0.0; 2048];
+
+        Delay::new(SAMPLE_RATE).process(&params, 120, &input, &mut out);
+        Reverb::new(SAMPLE_RATE).process(&params, &input, &mut out);
+
+        assert!(out.iter().all(|s| *s == 0.0));
+    }
+}
@@ -0,0 +1,277 @@
+use crate::params::{Choice, Param, Range};
+use pyo3::pyclass;
+use serde::{Deserialize, Serialize};
+
+/// the shortest time any stage of an envelope can take, keeps stages from clicking.
+const MIN_STAGE_SECONDS: f32 = 0.001;
+
+/// attack, decay, and release times are in seconds. sustain is a level from 0.0 to 1.0.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Adsr {
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+}
+
+impl Default for Adsr {
+    fn default() -> Self {
+        Self {
+            attack: 0.005,
+            decay: 0.2,
+            sustain: 0.7,
+            release: 0.1,
+        }
+    }
+}
+
+impl Adsr {
+    /// `names` are the names of the attack, decay, sustain, and release parameters.
+    pub fn params(&mut self, names: [&'static str; 4]) -> Vec<Param<'_>> {
+        let time = Range::new(0.0, 10.0, 0.005);
+
+        vec![
+            Param::new(names[0], &mut self.attack, time),
+            Param::new(names[1], &mut self.decay, time),
+            Param::new(names[2], &mut self.sustain, Range::new(0.0, 1.0, 0.05)),
+            Param::new(names[3], &mut self.release, time),
+        ]
+    }
+}
+
+/// what a modulation envelope is routed to.
+#[pyclass(module = "tracker_backend", eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum ModEnvTarget {
+    #[default]
+    Off,
+    /// bends the pitch by up to two octaves.
+    Pitch,
+    /// widens or narrows the pulse wave.
+    PulseWidth,
+    /// moves the filter cutoff by up to five octaves.
+    Cutoff,
+}
+
+impl Choice for ModEnvTarget {
+    const ALL: &'static [Self] = &[Self::Off, Self::Pitch, Self::PulseWidth, Self::Cutoff];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Off => "OFF",
+            Self::Pitch => "PITCH",
+            Self::PulseWidth => "PULSE W",
+            Self::Cutoff => "CUTOFF",
+        }
+    }
+}
+
+/// an envelope that modulates some other part of the synth.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModEnvelope {
+    pub adsr: Adsr,
+    pub target: ModEnvTarget,
+    /// how much the envelope moves the target, from -1.0 to 1.0.
+    pub amount: f32,
+}
+
+impl ModEnvelope {
+    pub fn params(&mut self) -> Vec<Param<'_>> {
+        let mut params = vec![
+            Param::choice("MOD ENV", &mut self.target),
+            Param::new("M AMOUNT", &mut self.amount, Range::new(-1.0, 1.0, 0.05)),
+        ];
+        params.append(
+            &mut self
+                .adsr
+                .params(["M ATTACK", "M DECAY", "M SUSTAIN", "M RELEASE"]),
+        );
+
+        params
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Stage {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+    #[default]
+    Off,
+}
+
+/// the running state of an `Adsr` for a single voice.
+#[derive(Debug, Clone, Default)]
+pub struct Envelope {
+    stage: Stage,
+    level: f32,
+    /// how much the level drops each sample during the release stage.
+    release_step: f32,
+}
+
+impl Envelope {
+    /// opens the gate. the attack starts from the current level so retriggers don't click.
+    pub fn gate_on(&mut self) {
+        self.stage = Stage::Attack;
+    }
+
+    /// closes the gate, starting the release stage.
+    pub fn gate_off(&mut self, adsr: &Adsr, sample_rate: f32) {
+        if self.stage == Stage::Off {
+            return;
+        }
+
+        self.stage = Stage::Release;
+        self.release_step = self.level / (adsr.release.max(MIN_STAGE_SECONDS) * sample_rate);
+    }
+
+    pub fn stage(&self) -> Stage {
+        self.stage
+    }
+
+    /// true while the gate is open.
+    pub fn is_held(&self) -> bool {
+        matches!(self.stage, Stage::Attack | Stage::Decay | Stage::Sustain)
+    }
+
+    /// the level the envelope is at, from 0.0 to 1.0.
+    pub fn level(&self) -> f32 {
+        self.level
+    }
+
+    /// true once the release stage has finished.
+    pub fn is_done(&self) -> bool {
+        self.stage == Stage::Off
+    }
+
+    /// moves the envelope forward one sample and returns its level, from 0.0 to 1.0.
+    pub fn next(&mut self, adsr: &Adsr, sample_rate: f32) -> f32 {
+        match self.stage {
+            Stage::Attack => {
+                self.level += 1.0 / (adsr.attack.max(MIN_STAGE_SECONDS) * sample_rate);
+
+                if self.level >= 1.0 {
+                    self.level = 1.0;
+                    self.stage = Stage::Decay;
+                }
+            }
+            Stage::Decay => {
+                self.level -=
+                    (1.0 - adsr.sustain) / (adsr.decay.max(MIN_STAGE_SECONDS) * sample_rate);
+
+                if self.level <= adsr.sustain {
+                    self.level = adsr.sustain;
+                    self.stage = Stage::Sustain;
+                }
+            }
+            Stage::Sustain => self.level = adsr.sustain,
+            Stage::Release => {
+                self.level -= self.release_step;
+
+                if self.level <= 0.0 {
+                    self.level = 0.0;
+                    self.stage = Stage::Off;
+                }
+            }
+            Stage::Off => self.level = 0.0,
+        }
+
+        self.level
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pygame_coms::{Instrument, InstrumentOutput},
+        synth::{Synth, SynthCmd},
+    };
+
+    const SAMPLE_RATE: f32 = 1000.0;
+
+    fn render(env: &mut Envelope, adsr: &Adsr, n: usize) -> Vec<f32> {
+        (0..n).map(|_| env.next(adsr, SAMPLE_RATE)).collect()
+    }
+
+    #[test]
+    fn stages() {
+        let adsr = Adsr {
+            attack: 0.01,
+            decay: 0.01,
+            sustain: 0.5,
+            release: 0.02,
+        };
+        let mut env = Envelope::default();
+        env.gate_on();
+
+        let attack = render(&mut env, &adsr, 10);
+        assert!(attack.windows(2).all(|w| w[1] > w[0]));
+        assert_eq!(attack[9], 1.0);
+        assert_eq!(env.stage(), Stage::Decay);
+
+        let decay = render(&mut env, &adsr, 10);
+        assert!((decay[4] - 0.75).abs() < 1e-4);
+        assert_eq!(decay[9], 0.5);
+        assert_eq!(env.stage(), Stage::Sustain);
+
+        assert!(render(&mut env, &adsr, 100).iter().all(|&s| s == 0.5));
+
+        env.gate_off(&adsr, SAMPLE_RATE);
+        let release = render(&mut env, &adsr, 20);
+        assert!((release[9] - 0.25).abs() < 1e-4);
+        assert_eq!(release[19], 0.0);
+        assert!(env.is_done());
+    }
+
+    #[test]
+    fn release_during_attack() {
+        let adsr = Adsr {
+            attack: 0.1,
+            ..Adsr::default()
+        };
+        let mut env = Envelope::default();
+        env.gate_on();
+
+        let level = *render(&mut env, &adsr, 50).last().unwrap();
+        env.gate_off(&adsr, SAMPLE_RATE);
+        let release = render(&mut env, &adsr, 1);
+
+        assert!(release[0] < level);
+    }
+
+    #[test]
+    fn synth_follows_gate() {
+        let mut synth = Synth::new(48_000.0);
+        let mut instrument = Instrument::new(0);
+        instrument.output = InstrumentOutput::Synth;
+        instrument.amp_env = Adsr {
+            attack: 0.001,
+            decay: 0.001,
+            sustain: 1.0,
+            release: 0.001,
+        };
+        synth.handle(SynthCmd::SetInstrument(0, Box::new(instrument)));
+        synth.handle(SynthCmd::NoteOn {
+            channel: 0,
+            instrument: 0,
+            notes: vec![60],
+            velocity: 1.0,
+        });
+
+        let mut buf = vec![0.0; 512];
+        synth.render(&mut buf);
+        assert!(buf.iter().any(|s| s.abs() > 0.01));
+
+        synth.handle(SynthCmd::NoteOff { channel: 0 });
+        synth.render(&mut buf);
+        synth.render(&mut buf);
+        assert!(buf.iter().all(|&s| s == 0.0));
+        assert!(synth.voices.is_empty());
+    }
+}
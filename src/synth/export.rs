@@ -0,0 +1,214 @@
+use super::{note_cmd, output, AudioClock, Synth, SynthCmd, SynthHandle};
+use crate::{
+    config::audio::AudioConfig,
+    pygame_coms::Song,
+    sequencer::{ExternalTempo, Sequencer},
+    tracker_state::{AllChains, AllPhrases, Tempo},
+};
+use anyhow::Result;
+use bevy::{log::*, prelude::*};
+use std::path::Path;
+
+/// seconds rendered after the last row of an export, so releases and effect tails ring out.
+pub const EXPORT_TAIL: f64 = 4.0;
+
+/// exports the song once through to `AudioConfig::export_path`.
+#[derive(Event, Debug, Default)]
+pub struct ExportSong;
+
+impl Synth {
+    /// a synth with the same instruments, wavetables, and samples, with nothing playing and the
+    /// effects empty.
+    pub(super) fn offline(&self) -> Self {
+        Self {
+            instruments: self.instruments.clone(),
+            wavetables: self.wavetables.clone(),
+            samples: self.samples.clone(),
+            kits: self.kits.clone(),
+            ..Self::new(self.sample_rate)
+        }
+    }
+
+    /// renders `frames` frames with each of `cmds` landing on its frame, handing each buffer to
+    /// `write` as it is rendered. buffers are `buffer_frames` long, like those of the audio
+    /// output, as the mixer ramps gain changes over a buffer. so what is rendered is what the
+    /// audio output would have played.
+    pub fn render_offline(
+        &mut self,
+        cmds: Vec<(u64, SynthCmd)>,
+        frames: u64,
+        buffer_frames: usize,
+        mut write: impl FnMut(&[f32]) -> Result<()>,
+    ) -> Result<()> {
+        for (at, cmd) in cmds {
+            self.schedule(at, cmd);
+        }
+
+        let mut buf = vec![0.0; buffer_frames * 2];
+        let mut left = frames;
+
+        while left > 0 {
+            let len = left.min(buffer_frames as u64) as usize;
+            self.render(&mut buf[..len * 2]);
+            write(&buf[..len * 2])?;
+            left -= len as u64;
+        }
+
+        Ok(())
+    }
+
+    /// renders into a WAV file at `path`, as fast as it can.
+    pub fn export(
+        &mut self,
+        cmds: Vec<(u64, SynthCmd)>,
+        frames: u64,
+        buffer_frames: usize,
+        path: &Path,
+    ) -> Result<()> {
+        let mut writer = output::wav_writer(path, self.sample_rate as u32)?;
+
+        self.render_offline(cmds, frames, buffer_frames, |samples| {
+            for sample in samples {
+                writer.write_sample(output::to_i16(*sample))?;
+            }
+
+            Ok(())
+        })?;
+
+        writer.finalize()?;
+
+        Ok(())
+    }
+}
+
+/// the commands that play `song` once through from the top, from frame 0, and the frames it
+/// takes with its tail.
+pub fn song_cmds(
+    tempo: &Tempo,
+    song: &Song,
+    chains: &AllChains,
+    phrases: &AllPhrases,
+    sample_rate: f64,
+) -> (Vec<(u64, SynthCmd)>, u64) {
+    let (notes, end) = Sequencer::song_once(tempo, song, chains, phrases, sample_rate);
+    let settings = [
+        SynthCmd::SetTempo(tempo.0),
+        SynthCmd::SetMixer(song.mixer),
+        SynthCmd::SetEffects(song.effects),
+        SynthCmd::SetDynamics(song.dynamics),
+    ];
+
+    let cmds = settings
+        .into_iter()
+        .map(|cmd| (0, cmd))
+        .chain(
+            notes
+                .into_iter()
+                .filter_map(|note| Some((note.at, note_cmd(note.event)?))),
+        )
+        .collect();
+
+    (cmds, end + (EXPORT_TAIL * sample_rate) as u64)
+}
+
+/// hands the song to the audio thread to export, with the instruments and samples it has.
+pub(super) fn export_song(
+    mut events: EventReader<ExportSong>,
+    (tempo, external): (Res<Tempo>, Res<ExternalTempo>),
+    (song, chains, phrases): (Res<Song>, Res<AllChains>, Res<AllPhrases>),
+    clock: Res<AudioClock>,
+    config: Res<AudioConfig>,
+    synth: Res<SynthHandle>,
+) {
+    for _ in events.read() {
+        let tempo = Tempo(external.bpm(&tempo));
+        let (cmds, frames) =
+            song_cmds(&tempo, &song, &chains, &phrases, clock.sample_rate() as f64);
+
+        info!("exporting the song to {}", config.export_path.display());
+        synth.send(SynthCmd::Export {
+            path: config.export_path.clone(),
+            cmds,
+            frames,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pygame_coms::{Chain, ChainRow, Instrument, Phrase, PhraseRow, TrackerCommand},
+        synth::{BUFFER_FRAMES, SAMPLE_RATE},
+    };
+
+    #[test]
+    fn an_export_is_what_the_audio_output_would_have_played() {
+        let mut song = Song::default();
+        let mut chains = AllChains::default();
+        let mut phrases = AllPhrases::default();
+        let mut phrase = Phrase::default();
+        phrase.rows[0].note = Some(48);
+        phrase.rows[4] = PhraseRow {
+            note: Some(55),
+            instrument: None,
+            command: Some(TrackerCommand::DelaySend(0.8)),
+        };
+        phrase.rows[8] = PhraseRow {
+            note: None,
+            instrument: None,
+            command: Some(TrackerCommand::Volume(0.25)),
+        };
+        phrase.rows[12].command = Some(TrackerCommand::NoteOff());
+        phrases.0[0] = Some(phrase);
+        let mut chain = Chain::default();
+        chain.rows[0] = ChainRow { phrase: Some(0) };
+        chains.0[0] = Some(chain);
+        song.rows[0].lead_1 = Some(0);
+        song.effects.reverb_mix = 0.5;
+        song.mixer.channels[0].reverb = 0.5;
+        song.dynamics.limiter = true;
+
+        let mut synth = Synth::new(SAMPLE_RATE as f32);
+        let instrument = Instrument::new(song.default_instrument[0]);
+        synth.handle(SynthCmd::SetInstrument(
+            song.default_instrument[0],
+            Box::new(instrument),
+        ));
+
+        let (cmds, frames) = song_cmds(&Tempo(150), &song, &chains, &phrases, SAMPLE_RATE as f64);
+
+        let mut exported = Vec::new();
+        synth
+            .offline()
+            .render_offline(cmds.clone(), frames, BUFFER_FRAMES, |samples| {
+                exported.extend_from_slice(samples);
+                Ok(())
+            })
+            .unwrap();
+
+        // the audio thread gets each command a little before its frame, and renders a buffer at a
+        // time.
+        let mut live = synth.offline();
+        let mut cmds = cmds.into_iter().peekable();
+        let mut played = Vec::new();
+        let mut buf = vec![0.0; BUFFER_FRAMES * 2];
+
+        while (played.len() as u64) < frames * 2 {
+            let ahead = live.frame + BUFFER_FRAMES as u64 * 4;
+
+            while let Some((at, cmd)) = cmds.next_if(|(at, _)| *at < ahead) {
+                live.schedule(at, cmd);
+            }
+
+            live.render(&mut buf);
+            played.extend_from_slice(&buf);
+        }
+
+        played.truncate(exported.len());
+
+        assert_eq!(exported.len() as u64, frames * 2);
+        assert!(exported.iter().any(|s| *s != 0.0));
+        assert!(exported == played);
+    }
+}
@@ -0,0 +1,291 @@
+use super::note_freq;
+use crate::params::{Choice, Param, Range};
+use pyo3::pyclass;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+/// the highest cutoff, as a MIDI note. a little under 20 kHz.
+pub const MAX_CUTOFF: f32 = 135.0;
+/// the lowest damping the filter is allowed. keeps it from ringing forever at full resonance.
+const MIN_DAMPING: f32 = 0.02;
+/// how far, in semitones, the cutoff has to move before the coefficients are worked out again.
+/// small enough to not be heard, big enough that a sweeping cutoff isn't recalculated every sample.
+const CUTOFF_TOLERANCE: f32 = 0.05;
+/// state smaller than this is flushed to zero, denormals are very slow on some ARM CPUs.
+pub(super) const DENORMAL: f32 = 1.0e-18;
+
+#[pyclass(module = "tracker_backend", eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum FilterMode {
+    #[default]
+    Off,
+    LowPass,
+    HighPass,
+    BandPass,
+    Notch,
+}
+
+impl Choice for FilterMode {
+    const ALL: &'static [Self] = &[
+        Self::Off,
+        Self::LowPass,
+        Self::HighPass,
+        Self::BandPass,
+        Self::Notch,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Off => "OFF",
+            Self::LowPass => "LOW PASS",
+            Self::HighPass => "HIGH PASS",
+            Self::BandPass => "BAND PASS",
+            Self::Notch => "NOTCH",
+        }
+    }
+}
+
+/// parameters of a synth instruments filter.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilterParams {
+    pub mode: FilterMode,
+    /// the cutoff frequency as a (possibly fractional) MIDI note, so it can be modulated in
+    /// semitones.
+    pub cutoff: f32,
+    /// from 0.0 to 1.0.
+    pub resonance: f32,
+    /// how much the cutoff follows the note being played. at 1.0 the cutoff moves a semitone for
+    /// every semitone the note is above or below middle C.
+    pub key_track: f32,
+}
+
+impl Default for FilterParams {
+    fn default() -> Self {
+        Self {
+            mode: FilterMode::default(),
+            cutoff: MAX_CUTOFF,
+            resonance: 0.0,
+            key_track: 0.0,
+        }
+    }
+}
+
+impl FilterParams {
+    pub fn params(&mut self) -> Vec<Param<'_>> {
+        vec![
+            Param::choice("FILTER", &mut self.mode),
+            Param::new("CUTOFF", &mut self.cutoff, Range::new(0.0, MAX_CUTOFF, 1.0)),
+            Param::new("RESONANCE", &mut self.resonance, Range::new(0.0, 1.0, 0.05)),
+            Param::new("KEY TRACK", &mut self.key_track, Range::new(0.0, 1.0, 0.05)),
+        ]
+    }
+
+    /// the cutoff, as a MIDI note, for a given note before any modulation.
+    pub fn cutoff_for(&self, note: f32) -> f32 {
+        self.cutoff + (note - 60.0) * self.key_track
+    }
+}
+
+/// a state variable filter, the trapezoidal integrated one from Andrew Simper's "Solving the
+/// continuous SVF equations using trapezoidal integration and equivalent currents". unlike the
+/// classic Chamberlin filter it stays stable with any cutoff below nyquist at any resonance.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    ic1eq: f32,
+    ic2eq: f32,
+    /// the cutoff and resonance the coefficients were worked out for.
+    last: Option<(f32, f32)>,
+    a1: f32,
+    a2: f32,
+    a3: f32,
+    k: f32,
+}
+
+impl Filter {
+    /// works out the coefficients, only when the cutoff or resonance has changed since `tan` is
+    /// costly.
+    fn set(&mut self, cutoff: f32, resonance: f32, sample_rate: f32) {
+        if let Some((last_cutoff, last_resonance)) = self.last
+            && (last_cutoff - cutoff).abs() < CUTOFF_TOLERANCE
+            && last_resonance == resonance
+        {
+            return;
+        }
+
+        let freq = note_freq(cutoff.clamp(0.0, MAX_CUTOFF)).min(sample_rate * 0.45);
+        let g = (PI * freq / sample_rate).tan();
+
+        self.k = (2.0 - 2.0 * resonance.clamp(0.0, 1.0)).max(MIN_DAMPING);
+        self.a1 = 1.0 / (1.0 + g * (g + self.k));
+        self.a2 = g * self.a1;
+        self.a3 = g * self.a2;
+        self.last = Some((cutoff, resonance));
+    }
+
+    /// filters one sample. `cutoff` is a MIDI note, see `FilterParams::cutoff`.
+    pub fn process(
+        &mut self,
+        input: f32,
+        mode: FilterMode,
+        cutoff: f32,
+        resonance: f32,
+        sample_rate: f32,
+    ) -> f32 {
+        if mode == FilterMode::Off {
+            return input;
+        }
+
+        self.set(cutoff, resonance, sample_rate);
+
+        let v3 = input - self.ic2eq;
+        let v1 = self.a1 * self.ic1eq + self.a2 * v3;
+        let v2 = self.ic2eq + self.a2 * self.ic1eq + self.a3 * v3;
+
+        self.ic1eq = 2.0 * v1 - self.ic1eq;
+        self.ic2eq = 2.0 * v2 - self.ic2eq;
+
+        if self.ic1eq.abs() < DENORMAL {
+            self.ic1eq = 0.0;
+        }
+
+        if self.ic2eq.abs() < DENORMAL {
+            self.ic2eq = 0.0;
+        }
+
+        match mode {
+            FilterMode::Off => input,
+            FilterMode::LowPass => v2,
+            FilterMode::HighPass => input - self.k * v1 - v2,
+            FilterMode::BandPass => self.k * v1,
+            FilterMode::Notch => input - self.k * v1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate test;
+
+    use super::*;
+    use crate::synth::BUFFER_FRAMES;
+    use std::f32::consts::TAU;
+    use test::{black_box, Bencher};
+
+    const SAMPLE_RATE: f32 = 48_000.0;
+
+    /// white noise between -1.0 and 1.0.
+    fn noise(n: usize) -> Vec<f32> {
+        let mut seed = 0x1234_5678_u32;
+
+        (0..n)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+
+                (seed as f32 / u32::MAX as f32) * 2.0 - 1.0
+            })
+            .collect()
+    }
+
+    /// the peak level of a sine wave, at `freq` Hz, after it goes through the filter.
+    fn gain(mode: FilterMode, cutoff: f32, freq: f32) -> f32 {
+        let mut filter = Filter::default();
+
+        (0..SAMPLE_RATE as usize / 2)
+            .map(|i| (i as f32 / SAMPLE_RATE * freq * TAU).sin())
+            .map(|s| filter.process(s, mode, cutoff, 0.0, SAMPLE_RATE))
+            .skip(SAMPLE_RATE as usize / 4)
+            .fold(0.0, |peak, s: f32| peak.max(s.abs()))
+    }
+
+    #[test]
+    fn modes() {
+        // middle C is 261.6 Hz.
+        let cutoff = 60.0;
+
+        assert!(gain(FilterMode::LowPass, cutoff, 50.0) > 0.9);
+        assert!(gain(FilterMode::LowPass, cutoff, 5_000.0) < 0.05);
+        assert!(gain(FilterMode::HighPass, cutoff, 50.0) < 0.1);
+        assert!(gain(FilterMode::HighPass, cutoff, 5_000.0) > 0.9);
+        assert!(gain(FilterMode::BandPass, cutoff, 261.6) > 0.9);
+        assert!(gain(FilterMode::BandPass, cutoff, 5_000.0) < 0.15);
+        assert!(gain(FilterMode::Notch, cutoff, 261.6) < 0.05);
+        assert!(gain(FilterMode::Notch, cutoff, 5_000.0) > 0.9);
+        assert_eq!(
+            gain(FilterMode::Off, cutoff, 5_000.0),
+            gain(FilterMode::Off, 0.0, 5_000.0)
+        );
+    }
+
+    #[test]
+    fn stable_at_full_resonance() {
+        for mode in FilterMode::ALL {
+            for cutoff in [0.0, 40.0, 90.0, 120.0, MAX_CUTOFF, 200.0] {
+                let mut filter = Filter::default();
+
+                for s in noise(SAMPLE_RATE as usize) {
+                    let out = filter.process(s, *mode, cutoff, 1.0, SAMPLE_RATE);
+                    assert!(out.is_finite() && out.abs() < 100.0, "{mode:?} at {cutoff}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stable_while_sweeping() {
+        let mut filter = Filter::default();
+
+        for (i, s) in noise(SAMPLE_RATE as usize * 2).into_iter().enumerate() {
+            // sweeps the whole range 50 times a second.
+            let cutoff = ((i as f32 / SAMPLE_RATE * 50.0 * TAU).sin() * 0.5 + 0.5) * MAX_CUTOFF;
+            let out = filter.process(s, FilterMode::LowPass, cutoff, 1.0, SAMPLE_RATE);
+
+            assert!(out.is_finite() && out.abs() < 100.0);
+        }
+    }
+
+    #[test]
+    fn decays_to_silence() {
+        let mut filter = Filter::default();
+
+        for s in noise(1_000) {
+            filter.process(s, FilterMode::BandPass, 100.0, 1.0, SAMPLE_RATE);
+        }
+
+        let tail = (0..SAMPLE_RATE as usize)
+            .map(|_| filter.process(0.0, FilterMode::BandPass, 100.0, 1.0, SAMPLE_RATE))
+            .last()
+            .unwrap();
+
+        assert_eq!(tail, 0.0);
+    }
+
+    #[bench]
+    fn fixed_cutoff(b: &mut Bencher) {
+        let input = noise(BUFFER_FRAMES);
+        let mut filter = Filter::default();
+
+        b.iter(|| {
+            for s in input.iter() {
+                black_box(filter.process(*s, FilterMode::LowPass, 80.0, 0.9, SAMPLE_RATE));
+            }
+        });
+    }
+
+    #[bench]
+    fn modulated_cutoff(b: &mut Bencher) {
+        let input = noise(BUFFER_FRAMES);
+        let mut filter = Filter::default();
+
+        b.iter(|| {
+            for (i, s) in input.iter().enumerate() {
+                // about as fast as a short cutoff envelope sweeps.
+                let cutoff = 40.0 + i as f32 * 0.01;
+                black_box(filter.process(*s, FilterMode::LowPass, cutoff, 0.9, SAMPLE_RATE));
+            }
+        });
+    }
+}
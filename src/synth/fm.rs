@@ -0,0 +1,428 @@
+use super::{
+    envelope::{Adsr, Envelope},
+    note_freq,
+    voices::{Allocated, StealFade},
+};
+use crate::{
+    params::{Choice, Param, Range},
+    pygame_coms::{Index, Note},
+};
+use pyo3::pyclass;
+use serde::{Deserialize, Serialize};
+use std::{array, f32::consts::TAU};
+
+/// the number of operators in an FM instrument.
+pub const N_OPERATORS: usize = 4;
+/// how far, in radians, a modulator at full level shifts the phase of the operator it modulates.
+const MOD_DEPTH: f32 = TAU;
+
+/// the names of each operators ratio, level, attack, decay, sustain, and release parameters.
+const PARAM_NAMES: [[&str; 6]; N_OPERATORS] = [
+    [
+        "OP1 RATIO",
+        "OP1 LEVEL",
+        "OP1 ATTACK",
+        "OP1 DECAY",
+        "OP1 SUSTAIN",
+        "OP1 RELEASE",
+    ],
+    [
+        "OP2 RATIO",
+        "OP2 LEVEL",
+        "OP2 ATTACK",
+        "OP2 DECAY",
+        "OP2 SUSTAIN",
+        "OP2 RELEASE",
+    ],
+    [
+        "OP3 RATIO",
+        "OP3 LEVEL",
+        "OP3 ATTACK",
+        "OP3 DECAY",
+        "OP3 SUSTAIN",
+        "OP3 RELEASE",
+    ],
+    [
+        "OP4 RATIO",
+        "OP4 LEVEL",
+        "OP4 ATTACK",
+        "OP4 DECAY",
+        "OP4 SUSTAIN",
+        "OP4 RELEASE",
+    ],
+];
+
+/// how the operators are connected, the same eight algorithms as four operator Yamaha chips.
+/// operator 4 is always at the top of a stack and is the one with feedback.
+#[pyclass(module = "tracker_backend", eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum FmAlgorithm {
+    /// 4 > 3 > 2 > 1
+    #[default]
+    Alg1,
+    /// (3 + 4) > 2 > 1
+    Alg2,
+    /// (4 + (3 > 2)) > 1
+    Alg3,
+    /// ((4 > 3) + 2) > 1
+    Alg4,
+    /// 4 > 3, 2 > 1
+    Alg5,
+    /// 4 > (1, 2, 3)
+    Alg6,
+    /// 4 > 3, 2, 1
+    Alg7,
+    /// 1, 2, 3, 4
+    Alg8,
+}
+
+impl Choice for FmAlgorithm {
+    const ALL: &'static [Self] = &[
+        Self::Alg1,
+        Self::Alg2,
+        Self::Alg3,
+        Self::Alg4,
+        Self::Alg5,
+        Self::Alg6,
+        Self::Alg7,
+        Self::Alg8,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Alg1 => "1",
+            Self::Alg2 => "2",
+            Self::Alg3 => "3",
+            Self::Alg4 => "4",
+            Self::Alg5 => "5",
+            Self::Alg6 => "6",
+            Self::Alg7 => "7",
+            Self::Alg8 => "8",
+        }
+    }
+}
+
+impl FmAlgorithm {
+    /// a bit mask, for each operator, of the operators that modulate it. operators are only ever
+    /// modulated by higher operators.
+    const fn modulators(&self) -> [u8; N_OPERATORS] {
+        match self {
+            Self::Alg1 => [0b0010, 0b0100, 0b1000, 0],
+            Self::Alg2 => [0b0010, 0b1100, 0, 0],
+            Self::Alg3 => [0b1010, 0b0100, 0, 0],
+            Self::Alg4 => [0b0110, 0, 0b1000, 0],
+            Self::Alg5 => [0b0010, 0, 0b1000, 0],
+            Self::Alg6 => [0b1000, 0b1000, 0b1000, 0],
+            Self::Alg7 => [0, 0, 0b1000, 0],
+            Self::Alg8 => [0; N_OPERATORS],
+        }
+    }
+
+    /// a bit mask of the operators that are heard.
+    const fn carriers(&self) -> u8 {
+        match self {
+            Self::Alg1 | Self::Alg2 | Self::Alg3 | Self::Alg4 => 0b0001,
+            Self::Alg5 => 0b0101,
+            Self::Alg6 | Self::Alg7 => 0b0111,
+            Self::Alg8 => 0b1111,
+        }
+    }
+}
+
+/// one sine wave oscillator of an FM instrument.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FmOperator {
+    /// the operators frequency as a multiple of the notes frequency.
+    pub ratio: f32,
+    /// the loudness of a carrier or the depth of a modulator, from 0.0 to 1.0.
+    pub level: f32,
+    pub env: Adsr,
+}
+
+impl FmOperator {
+    fn params(&mut self, names: [&'static str; 6]) -> Vec<Param<'_>> {
+        let mut params = vec![
+            Param::new(names[0], &mut self.ratio, Range::new(0.25, 16.0, 0.25)),
+            Param::new(names[1], &mut self.level, Range::new(0.0, 1.0, 0.05)),
+        ];
+        params.append(&mut self.env.params([names[2], names[3], names[4], names[5]]));
+
+        params
+    }
+}
+
+impl Default for FmOperator {
+    /// silent, at the pitch of the note.
+    fn default() -> Self {
+        Self {
+            ratio: 1.0,
+            level: 0.0,
+            env: Adsr::default(),
+        }
+    }
+}
+
+/// parameters of an FM instrument.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FmParams {
+    pub algorithm: FmAlgorithm,
+    /// how much operator 4 modulates itself, from 0.0 to 1.0.
+    pub feedback: f32,
+    /// semitones the instrument is transposed by.
+    pub transpose: i8,
+    pub volume: f32,
+    pub operators: [FmOperator; N_OPERATORS],
+}
+
+impl Default for FmParams {
+    fn default() -> Self {
+        // a simple electric piano like tone from a single modulator.
+        let operator = |ratio, level| FmOperator {
+            ratio,
+            level,
+            env: Adsr {
+                attack: 0.002,
+                decay: 0.8,
+                sustain: 0.3,
+                release: 0.3,
+            },
+        };
+
+        Self {
+            algorithm: FmAlgorithm::default(),
+            feedback: 0.0,
+            transpose: 0,
+            volume: 0.8,
+            operators: [
+                operator(1.0, 1.0),
+                operator(1.0, 0.35),
+                operator(2.0, 0.0),
+                operator(1.0, 0.0),
+            ],
+        }
+    }
+}
+
+impl FmParams {
+    pub fn params(&mut self) -> Vec<Param<'_>> {
+        let mut params = vec![
+            Param::choice("ALGORITHM", &mut self.algorithm),
+            Param::new("FEEDBACK", &mut self.feedback, Range::new(0.0, 1.0, 0.05)),
+            Param::new(
+                "TRANSPOSE",
+                &mut self.transpose,
+                Range::new(-48.0, 48.0, 1.0),
+            ),
+            Param::new("VOLUME", &mut self.volume, Range::new(0.0, 1.0, 0.05)),
+        ];
+
+        for (operator, names) in self.operators.iter_mut().zip(PARAM_NAMES) {
+            params.append(&mut operator.params(names));
+        }
+
+        params
+    }
+}
+
+/// a single note played by an FM instrument. lives on the audio thread.
+#[derive(Debug, Clone)]
+pub struct FmVoice {
+    pub channel: usize,
+    pub instrument: Index,
+    note: Note,
+    velocity: f32,
+    phases: [f32; N_OPERATORS],
+    envs: [Envelope; N_OPERATORS],
+    /// the last two outputs of operator 4, averaged for its feedback.
+    feedback: [f32; 2],
+    /// semitones the note is bent by.
+    pub bend: f32,
+    pub fade: StealFade,
+}
+
+impl FmVoice {
+    pub fn new(channel: usize, instrument: Index, note: Note, velocity: f32) -> Self {
+        let mut envs: [Envelope; N_OPERATORS] = array::from_fn(|_| Envelope::default());
+        envs.iter_mut().for_each(Envelope::gate_on);
+
+        Self {
+            channel,
+            instrument,
+            note,
+            velocity,
+            phases: [0.0; N_OPERATORS],
+            envs,
+            feedback: [0.0; 2],
+            bend: 0.0,
+            fade: StealFade::default(),
+        }
+    }
+
+    pub fn gate_off(&mut self, params: &FmParams, sample_rate: f32) {
+        for (env, operator) in self.envs.iter_mut().zip(&params.operators) {
+            env.gate_off(&operator.env, sample_rate);
+        }
+    }
+
+    /// true once every carrier has finished its release.
+    pub fn is_done(&self, params: &FmParams) -> bool {
+        let carriers = params.algorithm.carriers();
+
+        self.fade.is_done()
+            || self
+                .envs
+                .iter()
+                .enumerate()
+                .all(|(i, env)| carriers & (1 << i) == 0 || env.is_done())
+    }
+
+    pub fn next(&mut self, params: &FmParams, sample_rate: f32) -> f32 {
+        let freq = note_freq(self.note as f32 + params.transpose as f32 + self.bend);
+        let modulators = params.algorithm.modulators();
+        let carriers = params.algorithm.carriers();
+        let mut outputs = [0.0; N_OPERATORS];
+
+        // higher operators only modulate lower ones, so running them from the top down means
+        // every modulator has its output ready in time.
+        for i in (0..N_OPERATORS).rev() {
+            let operator = &params.operators[i];
+            let env = self.envs[i].next(&operator.env, sample_rate);
+
+            let modulation = if i == N_OPERATORS - 1 {
+                (self.feedback[0] + self.feedback[1]) * 0.5 * params.feedback * MOD_DEPTH
+            } else {
+                (i + 1..N_OPERATORS)
+                    .filter(|j| modulators[i] & (1 << j) != 0)
+                    .map(|j| outputs[j])
+                    .sum::<f32>()
+                    * MOD_DEPTH
+            };
+
+            outputs[i] = (self.phases[i] * TAU + modulation).sin() * operator.level * env;
+            self.phases[i] = (self.phases[i] + freq * operator.ratio / sample_rate).fract();
+        }
+
+        self.feedback = [self.feedback[1], outputs[N_OPERATORS - 1]];
+
+        let (sum, n) = outputs
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| carriers & (1 << i) != 0)
+            .fold((0.0, 0), |(sum, n), (_, out)| (sum + out, n + 1));
+
+        sum / n as f32 * self.velocity
+    }
+}
+
+impl Allocated for FmVoice {
+    fn channel(&self) -> usize {
+        self.channel
+    }
+
+    fn instrument(&self) -> Index {
+        self.instrument
+    }
+
+    fn note(&self) -> Note {
+        self.note
+    }
+
+    /// the loudest operator, the voice doesn't know which are carriers.
+    fn level(&self) -> f32 {
+        self.envs.iter().map(Envelope::level).fold(0.0, f32::max) * self.velocity
+    }
+
+    fn is_held(&self) -> bool {
+        self.envs.iter().any(Envelope::is_held)
+    }
+
+    fn set_note(&mut self, note: Note) -> bool {
+        self.note = note;
+        true
+    }
+
+    fn steal(&mut self) {
+        self.fade.steal();
+    }
+
+    fn is_stolen(&self) -> bool {
+        self.fade.is_stolen()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48_000.0;
+
+    fn render(params: &FmParams, n: usize) -> Vec<f32> {
+        let mut voice = FmVoice::new(0, 0, 69, 1.0);
+
+        (0..n).map(|_| voice.next(params, SAMPLE_RATE)).collect()
+    }
+
+    fn sustained() -> FmParams {
+        let mut params = FmParams::default();
+
+        for operator in params.operators.iter_mut() {
+            operator.env = Adsr {
+                attack: 0.0,
+                decay: 0.0,
+                sustain: 1.0,
+                release: 0.01,
+            };
+        }
+
+        params
+    }
+
+    #[test]
+    fn unmodulated_carrier_is_a_sine() {
+        let mut params = sustained();
+        params.operators[1].level = 0.0;
+
+        let out = render(&params, 1000);
+        let phase = 440.0 / SAMPLE_RATE * 500.0;
+
+        assert!((out[500] - (phase * TAU).sin()).abs() < 1e-3);
+    }
+
+    #[test]
+    fn modulation_adds_harmonics() {
+        let mut params = sustained();
+        params.operators[1].level = 0.0;
+        let sine = render(&params, 1000);
+
+        params.operators[1].level = 1.0;
+        let fm = render(&params, 1000);
+
+        assert!(sine.iter().zip(&fm).any(|(a, b)| (a - b).abs() > 0.1));
+        assert!(fm.iter().all(|s| s.abs() <= 1.0));
+    }
+
+    #[test]
+    fn parallel_carriers_are_mixed() {
+        let mut params = sustained();
+        params.algorithm = FmAlgorithm::Alg8;
+        params.operators.iter_mut().for_each(|op| op.level = 1.0);
+
+        assert!(render(&params, 1000).iter().all(|s| s.abs() <= 1.0));
+    }
+
+    #[test]
+    fn finishes_after_release() {
+        let params = sustained();
+        let mut voice = FmVoice::new(0, 0, 60, 1.0);
+        (0..100).for_each(|_| _ = voice.next(&params, SAMPLE_RATE));
+
+        voice.gate_off(&params, SAMPLE_RATE);
+        (0..SAMPLE_RATE as usize / 50).for_each(|_| _ = voice.next(&params, SAMPLE_RATE));
+
+        assert!(voice.is_done(&params));
+    }
+}
@@ -0,0 +1,627 @@
+use super::{
+    note_freq,
+    voices::{Allocated, StealFade},
+    wavetable::Wavetable,
+};
+use crate::{
+    params::{Choice, Param, Range},
+    pygame_coms::{Index, Note},
+};
+use pyo3::pyclass;
+use serde::{Deserialize, Serialize};
+
+/// the number of 4-bit steps in the wave channels wave.
+pub const WAVE_STEPS: usize = 32;
+/// the highest value of the 4-bit volume envelope and wave samples.
+const MAX_LEVEL: u8 = 15;
+/// how often, in Hz, the volume envelope, sweep, and length counter are clocked.
+const ENV_CLOCK: f32 = 64.0;
+const SWEEP_CLOCK: f32 = 128.0;
+const LENGTH_CLOCK: f32 = 256.0;
+/// the highest value of the 11-bit frequency register.
+const MAX_FREQ_REG: u16 = 2047;
+/// the noise channel is clocked this many times faster than the frequency of the note played.
+const NOISE_CLOCK_PER_HZ: f32 = 64.0;
+/// how much charge the output capacitor keeps each sample at 1 Hz. it removes the DC offset of the
+/// channels the same way it does on the hardware.
+const CAPACITOR_CHARGE: f64 = 0.999958;
+const GB_CLOCK: f64 = 4_194_304.0;
+
+/// how much charge the output capacitor keeps each sample at `sample_rate`.
+fn capacitor_charge(sample_rate: f32) -> f32 {
+    CAPACITOR_CHARGE.powf(GB_CLOCK / sample_rate as f64) as f32
+}
+
+/// which of the Game Boys sound channels an instrument plays through.
+#[pyclass(module = "tracker_backend", eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum GbChannel {
+    /// a pulse wave with a frequency sweep.
+    #[default]
+    Pulse1,
+    /// a pulse wave without a sweep.
+    Pulse2,
+    /// plays 32 4-bit samples of a wavetable.
+    Wave,
+    /// noise from a linear feedback shift register.
+    Noise,
+}
+
+impl Choice for GbChannel {
+    const ALL: &'static [Self] = &[Self::Pulse1, Self::Pulse2, Self::Wave, Self::Noise];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Pulse1 => "PULSE 1",
+            Self::Pulse2 => "PULSE 2",
+            Self::Wave => "WAVE",
+            Self::Noise => "NOISE",
+        }
+    }
+}
+
+/// the duty cycle of the pulse channels.
+#[pyclass(module = "tracker_backend", eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum GbDuty {
+    Eighth,
+    Quarter,
+    #[default]
+    Half,
+    ThreeQuarters,
+}
+
+impl Choice for GbDuty {
+    const ALL: &'static [Self] = &[Self::Eighth, Self::Quarter, Self::Half, Self::ThreeQuarters];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Eighth => "12.5%",
+            Self::Quarter => "25%",
+            Self::Half => "50%",
+            Self::ThreeQuarters => "75%",
+        }
+    }
+}
+
+impl GbDuty {
+    /// the 8 step waveform of the duty cycle, as on the hardware.
+    const fn pattern(&self) -> u8 {
+        match self {
+            Self::Eighth => 0b0000_0001,
+            Self::Quarter => 0b1000_0001,
+            Self::Half => 0b1000_0111,
+            Self::ThreeQuarters => 0b0111_1110,
+        }
+    }
+}
+
+/// the output level of the wave channel.
+#[pyclass(module = "tracker_backend", eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum GbWaveVolume {
+    Mute,
+    #[default]
+    Full,
+    Half,
+    Quarter,
+}
+
+impl Choice for GbWaveVolume {
+    const ALL: &'static [Self] = &[Self::Mute, Self::Full, Self::Half, Self::Quarter];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Mute => "0%",
+            Self::Full => "100%",
+            Self::Half => "50%",
+            Self::Quarter => "25%",
+        }
+    }
+}
+
+/// parameters of an instrument modelled on the Game Boy sound hardware. like the hardware,
+/// changes only apply to the next note played.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GbParams {
+    pub channel: GbChannel,
+    pub duty: GbDuty,
+    /// the starting volume of the envelope, from 0 to 15.
+    pub env_volume: u8,
+    pub env_up: bool,
+    /// envelope steps happen every `env_period` 64ths of a second, 0 holds the volume.
+    pub env_period: u8,
+    /// sweep steps happen every `sweep_period` 128ths of a second, 0 turns the sweep off.
+    pub sweep_period: u8,
+    pub sweep_up: bool,
+    /// every sweep step moves the frequency by the frequency shifted right by this.
+    pub sweep_shift: u8,
+    /// the wavetable played by the wave channel.
+    pub wave: u8,
+    pub wave_volume: GbWaveVolume,
+    /// uses a 7-bit shift register for the noise, which sounds more metallic.
+    pub short_noise: bool,
+    /// how long, in 256ths of a second, notes play for. 0 plays till the note is released.
+    pub length: u8,
+    /// semitones the instrument is transposed by.
+    pub transpose: i8,
+    pub volume: f32,
+}
+
+impl Default for GbParams {
+    fn default() -> Self {
+        Self {
+            channel: GbChannel::default(),
+            duty: GbDuty::default(),
+            env_volume: MAX_LEVEL,
+            env_up: false,
+            env_period: 3,
+            sweep_period: 0,
+            sweep_up: false,
+            sweep_shift: 0,
+            wave: 0,
+            wave_volume: GbWaveVolume::default(),
+            short_noise: false,
+            length: 0,
+            transpose: 0,
+            volume: 0.8,
+        }
+    }
+}
+
+impl GbParams {
+    /// only the parameters the selected channel uses are shown.
+    pub fn params(&mut self) -> Vec<Param<'_>> {
+        let channel = self.channel;
+        let register = Range::new(0.0, 7.0, 1.0);
+        let mut params = vec![Param::choice("GB CHAN", &mut self.channel)];
+
+        match channel {
+            GbChannel::Pulse1 | GbChannel::Pulse2 => {
+                params.push(Param::choice("DUTY", &mut self.duty))
+            }
+            GbChannel::Wave => {
+                params.push(Param::new(
+                    "WAVE",
+                    &mut self.wave,
+                    Range::new(0.0, 255.0, 1.0),
+                ));
+                params.push(Param::choice("WAVE VOL", &mut self.wave_volume));
+            }
+            GbChannel::Noise => params.push(Param::choice("SHORT NOISE", &mut self.short_noise)),
+        }
+
+        if channel != GbChannel::Wave {
+            params.append(&mut vec![
+                Param::new(
+                    "ENV VOL",
+                    &mut self.env_volume,
+                    Range::new(0.0, MAX_LEVEL as f32, 1.0),
+                ),
+                Param::choice("ENV UP", &mut self.env_up),
+                Param::new("ENV PERIOD", &mut self.env_period, register),
+            ]);
+        }
+
+        if channel == GbChannel::Pulse1 {
+            params.append(&mut vec![
+                Param::new("SWEEP PERIOD", &mut self.sweep_period, register),
+                Param::choice("SWEEP UP", &mut self.sweep_up),
+                Param::new("SWEEP SHIFT", &mut self.sweep_shift, register),
+            ]);
+        }
+
+        params.append(&mut vec![
+            Param::new("LENGTH", &mut self.length, Range::new(0.0, 64.0, 1.0)),
+            Param::new(
+                "TRANSPOSE",
+                &mut self.transpose,
+                Range::new(-48.0, 48.0, 1.0),
+            ),
+            Param::new("VOLUME", &mut self.volume, Range::new(0.0, 1.0, 0.05)),
+        ]);
+
+        params
+    }
+}
+
+/// the 11-bit frequency register value closest to `freq`, for a channel clocked at `clock` Hz.
+fn freq_reg(freq: f32, clock: f32) -> u16 {
+    (2048.0 - clock / freq)
+        .round()
+        .clamp(0.0, MAX_FREQ_REG as f32) as u16
+}
+
+/// how many times a second the noise channel steps its shift register. only the rates the
+/// hardwares divider and clock shift can make are used.
+fn noise_rate(freq: f32) -> f32 {
+    let target = freq * NOISE_CLOCK_PER_HZ;
+
+    (0..14)
+        .flat_map(|shift| {
+            (0..8).map(move |divider| {
+                let divider = if divider == 0 { 0.5 } else { divider as f32 };
+
+                524_288.0 / divider / 2.0_f32.powi(shift + 1)
+            })
+        })
+        .min_by(|a, b| (a / target).ln().abs().total_cmp(&(b / target).ln().abs()))
+        .unwrap_or(target)
+}
+
+/// quantizes the first frame of a wavetable to 32 4-bit steps.
+pub fn wave_ram(table: &Wavetable) -> [u8; WAVE_STEPS] {
+    std::array::from_fn(|i| {
+        let sample = table.sample(i as f32 / WAVE_STEPS as f32, 0.0);
+
+        ((sample.clamp(-1.0, 1.0) + 1.0) * 0.5 * MAX_LEVEL as f32).round() as u8
+    })
+}
+
+/// a single note played on a Game Boy channel. lives on the audio thread.
+#[derive(Debug, Clone)]
+pub struct GbVoice {
+    pub channel: usize,
+    pub instrument: Index,
+    note: Note,
+    /// the instruments parameters at the time the note was played.
+    params: GbParams,
+    wave: [u8; WAVE_STEPS],
+    /// the pulse and wave channels frequency register.
+    freq_reg: u16,
+    /// how many times a second the noise channels shift register steps.
+    noise_rate: f32,
+    /// position in the current waveform, in steps of the waveform.
+    phase: f32,
+    lfsr: u16,
+    level: u8,
+    env_timer: f32,
+    sweep_timer: f32,
+    length_timer: f32,
+    capacitor: f32,
+    /// the sample rate `capacitor_charge` was last worked out for, and what it came to, so it is
+    /// only worked out again when the rate changes.
+    charge: (f32, f32),
+    stopped: bool,
+    pub fade: StealFade,
+}
+
+impl GbVoice {
+    /// `velocity` scales the starting volume of the envelope, like setting it from a phrase.
+    pub fn new(
+        channel: usize,
+        instrument: Index,
+        params: GbParams,
+        wave: [u8; WAVE_STEPS],
+        note: Note,
+        velocity: f32,
+    ) -> Self {
+        let level = params.env_volume.min(MAX_LEVEL) as f32 * velocity.clamp(0.0, 1.0);
+        let mut voice = Self {
+            channel,
+            instrument,
+            note,
+            params,
+            wave,
+            freq_reg: 0,
+            noise_rate: 0.0,
+            phase: 0.0,
+            lfsr: 0x7FFF,
+            level: level.round() as u8,
+            env_timer: 0.0,
+            sweep_timer: 0.0,
+            length_timer: 0.0,
+            capacitor: 0.0,
+            charge: (0.0, 0.0),
+            stopped: false,
+            fade: StealFade::default(),
+        };
+        voice.tune(note);
+
+        voice
+    }
+
+    /// sets the frequency register and noise rate for `note`.
+    fn tune(&mut self, note: Note) {
+        let freq = note_freq(note as f32 + self.params.transpose as f32);
+
+        self.note = note;
+        self.freq_reg = match self.params.channel {
+            GbChannel::Wave => freq_reg(freq, 65_536.0),
+            _ => freq_reg(freq, 131_072.0),
+        };
+        self.noise_rate = noise_rate(freq);
+    }
+
+    /// note offs cut the channel, there is no release on the hardware.
+    pub fn stop(&mut self) {
+        self.stopped = true;
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.stopped || self.fade.is_done()
+    }
+
+    /// the frequency, in Hz, of the pulse or wave channels frequency register.
+    fn freq(&self) -> f32 {
+        let clock = match self.params.channel {
+            GbChannel::Wave => 65_536.0,
+            _ => 131_072.0,
+        };
+
+        clock / (2048 - self.freq_reg) as f32
+    }
+
+    /// runs the length counter, sweep, and envelope for one sample.
+    fn clock(&mut self, sample_rate: f32) {
+        let params = &self.params;
+
+        if params.length > 0 {
+            self.length_timer += LENGTH_CLOCK / sample_rate;
+            self.stopped |= self.length_timer >= params.length as f32;
+        }
+
+        if params.channel == GbChannel::Pulse1 && params.sweep_period > 0 && params.sweep_shift > 0
+        {
+            self.sweep_timer += SWEEP_CLOCK / sample_rate;
+
+            if self.sweep_timer >= params.sweep_period as f32 {
+                self.sweep_timer -= params.sweep_period as f32;
+                let delta = self.freq_reg >> params.sweep_shift;
+
+                if params.sweep_up {
+                    self.freq_reg += delta;
+                    // the hardware turns the channel off when the sweep overflows.
+                    self.stopped |= self.freq_reg > MAX_FREQ_REG;
+                } else {
+                    self.freq_reg -= delta;
+                }
+            }
+        }
+
+        if params.channel != GbChannel::Wave && params.env_period > 0 {
+            self.env_timer += ENV_CLOCK / sample_rate;
+
+            if self.env_timer >= params.env_period as f32 {
+                self.env_timer -= params.env_period as f32;
+
+                self.level = if params.env_up {
+                    (self.level + 1).min(MAX_LEVEL)
+                } else {
+                    self.level.saturating_sub(1)
+                };
+            }
+        }
+    }
+
+    /// the channels 4-bit output for one sample, from 0 to 15.
+    fn next_digital(&mut self, sample_rate: f32) -> u8 {
+        if self.stopped {
+            return 0;
+        }
+
+        self.clock(sample_rate);
+
+        if self.stopped {
+            return 0;
+        }
+
+        match self.params.channel {
+            GbChannel::Pulse1 | GbChannel::Pulse2 => {
+                let step = self.phase as u8;
+                self.phase = (self.phase + self.freq() * 8.0 / sample_rate) % 8.0;
+
+                if self.params.duty.pattern() & (0x80 >> step) != 0 {
+                    self.level
+                } else {
+                    0
+                }
+            }
+            GbChannel::Wave => {
+                let sample = self.wave[self.phase as usize];
+                self.phase = (self.phase + self.freq() * WAVE_STEPS as f32 / sample_rate)
+                    % WAVE_STEPS as f32;
+
+                match self.params.wave_volume {
+                    GbWaveVolume::Mute => 0,
+                    GbWaveVolume::Full => sample,
+                    GbWaveVolume::Half => sample >> 1,
+                    GbWaveVolume::Quarter => sample >> 2,
+                }
+            }
+            GbChannel::Noise => {
+                self.phase += self.noise_rate / sample_rate;
+
+                while self.phase >= 1.0 {
+                    self.phase -= 1.0;
+                    let bit = (self.lfsr ^ (self.lfsr >> 1)) & 1;
+                    self.lfsr = (self.lfsr >> 1) | (bit << 14);
+
+                    if self.params.short_noise {
+                        self.lfsr = (self.lfsr & !(1 << 6)) | (bit << 6);
+                    }
+                }
+
+                if self.lfsr & 1 == 0 {
+                    self.level
+                } else {
+                    0
+                }
+            }
+        }
+    }
+
+    /// the next sample, with the DC offset removed like the hardwares output capacitor does.
+    pub fn next(&mut self, sample_rate: f32) -> f32 {
+        let input = self.next_digital(sample_rate) as f32 / MAX_LEVEL as f32;
+
+        if self.charge.0 != sample_rate {
+            self.charge = (sample_rate, capacitor_charge(sample_rate));
+        }
+
+        let charge = self.charge.1;
+        let out = input - self.capacitor;
+        self.capacitor = input - out * charge;
+
+        out * self.params.volume
+    }
+}
+
+impl Allocated for GbVoice {
+    fn channel(&self) -> usize {
+        self.channel
+    }
+
+    fn instrument(&self) -> Index {
+        self.instrument
+    }
+
+    fn note(&self) -> Note {
+        self.note
+    }
+
+    fn level(&self) -> f32 {
+        if self.stopped {
+            0.0
+        } else {
+            self.level as f32 / MAX_LEVEL as f32
+        }
+    }
+
+    fn is_held(&self) -> bool {
+        !self.stopped
+    }
+
+    /// a new note restarts the sweep from the new pitch, like writing the frequency registers
+    /// without retriggering the channel.
+    fn set_note(&mut self, note: Note) -> bool {
+        self.tune(note);
+        true
+    }
+
+    fn steal(&mut self) {
+        self.fade.steal();
+    }
+
+    fn is_stolen(&self) -> bool {
+        self.fade.is_stolen()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48_000.0;
+
+    fn voice(params: GbParams) -> GbVoice {
+        GbVoice::new(0, 0, params, [0; WAVE_STEPS], 57, 1.0)
+    }
+
+    fn digital(voice: &mut GbVoice, n: usize) -> Vec<u8> {
+        (0..n).map(|_| voice.next_digital(SAMPLE_RATE)).collect()
+    }
+
+    #[test]
+    fn duty_cycles() {
+        for (duty, expected) in [
+            (GbDuty::Eighth, 0.125),
+            (GbDuty::Quarter, 0.25),
+            (GbDuty::Half, 0.5),
+            (GbDuty::ThreeQuarters, 0.75),
+        ] {
+            let mut voice = voice(GbParams {
+                duty,
+                env_period: 0,
+                ..GbParams::default()
+            });
+            let out = digital(&mut voice, 48_000);
+            let high = out.iter().filter(|&&s| s == MAX_LEVEL).count() as f32 / out.len() as f32;
+
+            assert!((high - expected).abs() < 0.01, "{duty:?} was {high}");
+        }
+    }
+
+    #[test]
+    fn the_capacitor_takes_out_the_dc_offset() {
+        let mut voice = voice(GbParams {
+            env_period: 0,
+            ..GbParams::default()
+        });
+        let out: Vec<f32> = (0..48_000).map(|_| voice.next(SAMPLE_RATE)).collect();
+        // once the capacitor has charged.
+        let mean = out[24_000..].iter().sum::<f32>() / 24_000.0;
+
+        assert!(mean.abs() < 0.01, "the offset was {mean}");
+
+        // worked out again when the sample rate changes.
+        voice.next(96_000.0);
+        assert_eq!(voice.charge, (96_000.0, capacitor_charge(96_000.0)));
+    }
+
+    #[test]
+    fn envelope_fades_out() {
+        let mut voice = voice(GbParams {
+            env_period: 1,
+            ..GbParams::default()
+        });
+        let out = digital(&mut voice, 48_000 / 3);
+
+        assert_eq!(out.iter().max(), Some(&MAX_LEVEL));
+        assert!(out[out.len() - 1000..].iter().all(|&s| s == 0));
+    }
+
+    #[test]
+    fn sweep_overflow_stops() {
+        let mut voice = voice(GbParams {
+            env_period: 0,
+            sweep_period: 1,
+            sweep_up: true,
+            sweep_shift: 1,
+            ..GbParams::default()
+        });
+        digital(&mut voice, 48_000);
+
+        assert!(voice.is_done());
+    }
+
+    #[test]
+    fn length_stops() {
+        let mut voice = voice(GbParams {
+            length: 64,
+            ..GbParams::default()
+        });
+        digital(&mut voice, 48_000 / 4 - 100);
+        assert!(!voice.is_done());
+
+        digital(&mut voice, 200);
+        assert!(voice.is_done());
+    }
+
+    #[test]
+    fn short_noise_repeats() {
+        let mut voice = voice(GbParams {
+            channel: GbChannel::Noise,
+            short_noise: true,
+            env_period: 0,
+            ..GbParams::default()
+        });
+        // one shift register step per sample makes the period easy to see.
+        voice.noise_rate = SAMPLE_RATE;
+        let out = digital(&mut voice, 1000);
+
+        assert_eq!(out[200..327], out[327..454]);
+        assert_ne!(out[200..263], out[263..326]);
+    }
+
+    #[test]
+    fn wave_ram_is_4_bit() {
+        let ram = wave_ram(&Wavetable::new(0));
+
+        assert!(ram.iter().all(|&s| s <= MAX_LEVEL));
+        assert_eq!(ram[8], MAX_LEVEL);
+        assert_eq!(ram[24], 0);
+    }
+}
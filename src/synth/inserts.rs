@@ -0,0 +1,481 @@
+use super::{
+    bus_channel,
+    effects::{flush, OnePole},
+    mixer::Buses,
+};
+use crate::{
+    params::{Choice, Param, Range},
+    pygame_coms::{Index, Instrument},
+};
+use pyo3::pyclass;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, f32::consts::TAU};
+
+/// the number of insert effects in an instruments chain.
+pub const N_INSERTS: usize = 3;
+/// the longest the chorus delay line gets, in seconds.
+const CHORUS_MAX_DELAY: f32 = 0.03;
+/// the chorus delay, in seconds, with the depth turned all the way down.
+const CHORUS_DELAY: f32 = 0.012;
+/// how far, in seconds, the chorus delay moves with the depth all the way up.
+const CHORUS_SWING: f32 = 0.008;
+/// the cutoffs, as MIDI notes, of the EQ band edges. about 250 Hz and 4 kHz.
+const EQ_LOW: f32 = 47.0;
+const EQ_HIGH: f32 = 95.0;
+
+/// the names of each slots type, bits, rate, drive, tone, depth, speed, low, mid, high, and mix
+/// parameters.
+const SLOT_NAMES: [[&str; 11]; N_INSERTS] = [
+    [
+        "FX1 TYPE",
+        "FX1 BITS",
+        "FX1 RATE",
+        "FX1 DRIVE",
+        "FX1 TONE",
+        "FX1 DEPTH",
+        "FX1 SPEED",
+        "FX1 LOW",
+        "FX1 MID",
+        "FX1 HIGH",
+        "FX1 MIX",
+    ],
+    [
+        "FX2 TYPE",
+        "FX2 BITS",
+        "FX2 RATE",
+        "FX2 DRIVE",
+        "FX2 TONE",
+        "FX2 DEPTH",
+        "FX2 SPEED",
+        "FX2 LOW",
+        "FX2 MID",
+        "FX2 HIGH",
+        "FX2 MIX",
+    ],
+    [
+        "FX3 TYPE",
+        "FX3 BITS",
+        "FX3 RATE",
+        "FX3 DRIVE",
+        "FX3 TONE",
+        "FX3 DEPTH",
+        "FX3 SPEED",
+        "FX3 LOW",
+        "FX3 MID",
+        "FX3 HIGH",
+        "FX3 MIX",
+    ],
+];
+
+/// the kind of effect in an insert slot.
+#[pyclass(module = "tracker_backend", eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum InsertType {
+    /// the slot is not used.
+    #[default]
+    Off,
+    /// cuts the bit depth.
+    Bitcrush,
+    /// holds each sample for a few samples, cutting the sample rate.
+    Downsample,
+    Overdrive,
+    Chorus,
+    /// a three band EQ.
+    Eq,
+}
+
+impl Choice for InsertType {
+    const ALL: &'static [Self] = &[
+        Self::Off,
+        Self::Bitcrush,
+        Self::Downsample,
+        Self::Overdrive,
+        Self::Chorus,
+        Self::Eq,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Off => "OFF",
+            Self::Bitcrush => "BITCRUSH",
+            Self::Downsample => "DOWNSAMPLE",
+            Self::Overdrive => "OVERDRIVE",
+            Self::Chorus => "CHORUS",
+            Self::Eq => "EQ",
+        }
+    }
+}
+
+/// one effect in an instruments insert chain. only the fields the effect type uses are shown.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InsertSlot {
+    pub effect: InsertType,
+    /// the bit depth of the bitcrusher.
+    pub bits: u8,
+    /// how many samples the downsampler holds each sample for.
+    pub rate: u8,
+    /// how hard the overdrive is pushed, from 0.0 to 1.0.
+    pub drive: f32,
+    /// the cutoff, as a MIDI note, of the low pass after the overdrive.
+    pub tone: f32,
+    /// how far the chorus sweeps, from 0.0 to 1.0.
+    pub depth: f32,
+    /// how fast the chorus sweeps, in Hz.
+    pub speed: f32,
+    /// the gain of each EQ band, in dB.
+    pub low: f32,
+    pub mid: f32,
+    pub high: f32,
+    /// how much of the effected signal is heard, from 0.0 to 1.0.
+    pub mix: f32,
+}
+
+impl Default for InsertSlot {
+    fn default() -> Self {
+        Self {
+            effect: InsertType::Off,
+            bits: 8,
+            rate: 4,
+            drive: 0.5,
+            tone: 100.0,
+            depth: 0.5,
+            speed: 0.8,
+            low: 0.0,
+            mid: 0.0,
+            high: 0.0,
+            mix: 1.0,
+        }
+    }
+}
+
+impl InsertSlot {
+    fn params(&mut self, names: [&'static str; 11]) -> Vec<Param<'_>> {
+        let effect = self.effect;
+        let gain = Range::new(-12.0, 12.0, 1.0);
+        let mut params = vec![Param::choice(names[0], &mut self.effect)];
+
+        match effect {
+            InsertType::Off => return params,
+            InsertType::Bitcrush => {
+                params.push(Param::new(
+                    names[1],
+                    &mut self.bits,
+                    Range::new(1.0, 16.0, 1.0),
+                ));
+            }
+            InsertType::Downsample => {
+                params.push(Param::new(
+                    names[2],
+                    &mut self.rate,
+                    Range::new(1.0, 32.0, 1.0),
+                ));
+            }
+            InsertType::Overdrive => {
+                params.push(Param::new(
+                    names[3],
+                    &mut self.drive,
+                    Range::new(0.0, 1.0, 0.05),
+                ));
+                params.push(Param::new(
+                    names[4],
+                    &mut self.tone,
+                    Range::new(40.0, 135.0, 1.0),
+                ));
+            }
+            InsertType::Chorus => {
+                params.push(Param::new(
+                    names[5],
+                    &mut self.depth,
+                    Range::new(0.0, 1.0, 0.05),
+                ));
+                params.push(Param::new(
+                    names[6],
+                    &mut self.speed,
+                    Range::new(0.05, 5.0, 0.05),
+                ));
+            }
+            InsertType::Eq => {
+                params.push(Param::new(names[7], &mut self.low, gain));
+                params.push(Param::new(names[8], &mut self.mid, gain));
+                params.push(Param::new(names[9], &mut self.high, gain));
+            }
+        }
+
+        params.push(Param::new(
+            names[10],
+            &mut self.mix,
+            Range::new(0.0, 1.0, 0.05),
+        ));
+
+        params
+    }
+}
+
+/// an instruments chain of insert effects, run in order.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Inserts {
+    pub slots: [InsertSlot; N_INSERTS],
+}
+
+impl Inserts {
+    pub fn params(&mut self) -> Vec<Param<'_>> {
+        self.slots
+            .iter_mut()
+            .zip(SLOT_NAMES)
+            .flat_map(|(slot, names)| slot.params(names))
+            .collect()
+    }
+}
+
+/// the running state of one insert slot.
+#[derive(Debug, Clone)]
+struct SlotState {
+    /// the sample the downsampler is holding, and for how many more samples.
+    held: [f32; 2],
+    hold_for: u8,
+    /// the overdrives tone filter.
+    tone: [OnePole; 2],
+    /// the chorus delay lines.
+    chorus: [Vec<f32>; 2],
+    chorus_pos: usize,
+    chorus_phase: f32,
+    /// the EQs band splitting filters.
+    eq_low: [OnePole; 2],
+    eq_high: [OnePole; 2],
+}
+
+impl SlotState {
+    fn new(sample_rate: f32) -> Self {
+        let len = (CHORUS_MAX_DELAY * sample_rate) as usize + 2;
+
+        Self {
+            held: [0.0; 2],
+            hold_for: 0,
+            tone: [OnePole::default(); 2],
+            chorus: [vec![0.0; len], vec![0.0; len]],
+            chorus_pos: 0,
+            chorus_phase: 0.0,
+            eq_low: [OnePole::default(); 2],
+            eq_high: [OnePole::default(); 2],
+        }
+    }
+
+    /// runs the effect over `buf`, interleaved stereo, in place.
+    fn process(&mut self, slot: &InsertSlot, buf: &mut [f32], sample_rate: f32) {
+        let tone = OnePole::coefficient(slot.tone, sample_rate);
+        let eq_low = OnePole::coefficient(EQ_LOW, sample_rate);
+        let eq_high = OnePole::coefficient(EQ_HIGH, sample_rate);
+        let levels = 2.0_f32.powi(slot.bits.clamp(1, 16) as i32 - 1);
+        let drive = 1.0 + slot.drive * 20.0;
+        let gains = [slot.low, slot.mid, slot.high].map(|db| 10.0_f32.powf(db / 20.0));
+        let len = self.chorus[0].len();
+
+        for frame in buf.chunks_exact_mut(2) {
+            let dry = [frame[0], frame[1]];
+
+            let wet = match slot.effect {
+                InsertType::Off => return,
+                InsertType::Bitcrush => dry.map(|x| (x * levels).round() / levels),
+                InsertType::Downsample => {
+                    if self.hold_for == 0 {
+                        self.held = dry;
+                        self.hold_for = slot.rate.max(1);
+                    }
+
+                    self.hold_for -= 1;
+                    self.held
+                }
+                InsertType::Overdrive => [0, 1].map(|side| {
+                    // turned down as it's driven so the level stays about the same.
+                    let driven = (dry[side] * drive).tanh() / drive.sqrt();
+
+                    self.tone[side].low_pass(driven, tone)
+                }),
+                InsertType::Chorus => {
+                    let wet = [0, 1].map(|side| {
+                        // the sides sweep a quarter turn apart, so the chorus is wide.
+                        let lfo = (TAU * (self.chorus_phase + side as f32 * 0.25)).sin();
+                        let delay = (CHORUS_DELAY + lfo * slot.depth * CHORUS_SWING) * sample_rate;
+                        let read = (self.chorus_pos + len) as f32 - delay;
+                        let i = read.floor() as usize;
+                        let frac = read.fract();
+                        let line = &self.chorus[side];
+
+                        line[i % len] * (1.0 - frac) + line[(i + 1) % len] * frac
+                    });
+
+                    for (line, dry) in self.chorus.iter_mut().zip(dry) {
+                        line[self.chorus_pos] = dry;
+                    }
+
+                    self.chorus_pos = (self.chorus_pos + 1) % len;
+                    self.chorus_phase = (self.chorus_phase + slot.speed / sample_rate).fract();
+                    wet
+                }
+                InsertType::Eq => [0, 1].map(|side| {
+                    let low = self.eq_low[side].low_pass(dry[side], eq_low);
+                    let high = dry[side] - self.eq_high[side].low_pass(dry[side], eq_high);
+                    let mid = dry[side] - low - high;
+
+                    flush(low * gains[0] + mid * gains[1] + high * gains[2])
+                }),
+            };
+
+            frame[0] = dry[0] + (wet[0] - dry[0]) * slot.mix;
+            frame[1] = dry[1] + (wet[1] - dry[1]) * slot.mix;
+        }
+    }
+}
+
+/// a buffer an instrument renders into on one channel, and the state of its inserts.
+#[derive(Debug, Clone)]
+struct InsertChain {
+    buf: Vec<f32>,
+    slots: [SlotState; N_INSERTS],
+}
+
+/// every instrument renders into its own buffer on each channel it plays on. the buffers go
+/// through the instruments inserts before they are added to the channel buses. lives on the
+/// audio thread.
+#[derive(Debug, Clone)]
+pub struct InsertBuses {
+    sample_rate: f32,
+    len: usize,
+    chains: HashMap<(usize, Index), InsertChain>,
+}
+
+impl InsertBuses {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            len: 0,
+            chains: HashMap::new(),
+        }
+    }
+
+    /// empties the buffers, ready to render `len` samples into.
+    pub fn clear(&mut self, len: usize) {
+        self.len = len;
+
+        for chain in self.chains.values_mut() {
+            chain.buf.clear();
+            chain.buf.resize(len, 0.0);
+        }
+    }
+
+    /// the buffer `instrument` renders into on `channel`.
+    pub fn bus(&mut self, channel: usize, instrument: Index) -> &mut [f32] {
+        let (sample_rate, len) = (self.sample_rate, self.len);
+
+        &mut self
+            .chains
+            .entry((channel, instrument))
+            .or_insert_with(|| InsertChain {
+                buf: vec![0.0; len],
+                slots: std::array::from_fn(|_| SlotState::new(sample_rate)),
+            })
+            .buf
+    }
+
+    /// runs each buffer through its instruments inserts and adds it to its channel bus.
+    pub fn process(&mut self, instruments: &[Option<Instrument>], buses: &mut Buses) {
+        for (&(channel, instrument), chain) in self.chains.iter_mut() {
+            let Some(Some(inst)) = instruments.get(instrument) else {
+                continue;
+            };
+            let Some(bus) = buses.bus(bus_channel(channel)) else {
+                continue;
+            };
+
+            for (state, slot) in chain.slots.iter_mut().zip(&inst.inserts.slots) {
+                state.process(slot, &mut chain.buf, self.sample_rate);
+            }
+
+            for (bus, sample) in bus.iter_mut().zip(&chain.buf) {
+                *bus += sample;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48_000.0;
+
+    fn run(slot: InsertSlot, input: &[f32]) -> Vec<f32> {
+        let mut state = SlotState::new(SAMPLE_RATE);
+        let mut buf = input.to_vec();
+        state.process(&slot, &mut buf, SAMPLE_RATE);
+
+        buf
+    }
+
+    fn sine(len: usize) -> Vec<f32> {
+        (0..len)
+            .flat_map(|i| {
+                let s = (TAU * 220.0 * i as f32 / SAMPLE_RATE).sin() * 0.5;
+                [s, s]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn off_and_dry_pass_through() {
+        let input = sine(512);
+
+        assert_eq!(run(InsertSlot::default(), &input), input);
+
+        let dry = InsertSlot {
+            effect: InsertType::Overdrive,
+            mix: 0.0,
+            ..InsertSlot::default()
+        };
+        assert_eq!(run(dry, &input), input);
+    }
+
+    #[test]
+    fn bitcrush_quantizes() {
+        let slot = InsertSlot {
+            effect: InsertType::Bitcrush,
+            bits: 2,
+            ..InsertSlot::default()
+        };
+
+        for s in run(slot, &sine(512)) {
+            assert!((s * 2.0 - (s * 2.0).round()).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn downsample_holds() {
+        let slot = InsertSlot {
+            effect: InsertType::Downsample,
+            rate: 4,
+            ..InsertSlot::default()
+        };
+        let out = run(slot, &sine(512));
+
+        for block in out.chunks_exact(8) {
+            assert!(block.iter().step_by(2).all(|s| (s - block[0]).abs() < 1e-6));
+        }
+    }
+
+    #[test]
+    fn flat_eq_is_transparent() {
+        let slot = InsertSlot {
+            effect: InsertType::Eq,
+            ..InsertSlot::default()
+        };
+        let input = sine(512);
+
+        for (out, input) in run(slot, &input).iter().zip(&input) {
+            assert!((out - input).abs() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use crate::{
+    config::ui::Bpm,
+    params::{Choice, Param, Range},
+    sequencer::ROWS_PER_BEAT,
+};
+use pyo3::pyclass;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+/// the number of LFOs each instrument has.
+pub const N_LFOS: usize = 2;
+
+#[pyclass(module = "tracker_backend", eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum LfoShape {
+    #[default]
+    Sine,
+    Triangle,
+    Square,
+    /// holds a new random value each cycle.
+    SampleHold,
+}
+
+impl Choice for LfoShape {
+    const ALL: &'static [Self] = &[Self::Sine, Self::Triangle, Self::Square, Self::SampleHold];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Sine => "SINE",
+            Self::Triangle => "TRI",
+            Self::Square => "SQUARE",
+            Self::SampleHold => "S&H",
+        }
+    }
+}
+
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LfoParams {
+    pub shape: LfoShape,
+    /// cycles per second, used when the LFO is not synced to the tempo.
+    pub rate: f32,
+    /// when true the LFO follows the tempo and `sync_rows` sets its speed instead of `rate`.
+    pub sync: bool,
+    /// how many phrase rows one cycle lasts when synced.
+    pub sync_rows: u8,
+}
+
+impl Default for LfoParams {
+    fn default() -> Self {
+        Self {
+            shape: LfoShape::default(),
+            rate: 4.0,
+            sync: false,
+            sync_rows: 16,
+        }
+    }
+}
+
+impl LfoParams {
+    /// `names` are the names of the shape, rate, sync, and sync rows parameters.
+    pub fn params(&mut self, names: [&'static str; 4]) -> Vec<Param<'_>> {
+        vec![
+            Param::choice(names[0], &mut self.shape),
+            Param::new(names[1], &mut self.rate, Range::new(0.05, 20.0, 0.05)),
+            Param::new(names[2], &mut self.sync, Range::new(0.0, 1.0, 1.0)),
+            Param::new(names[3], &mut self.sync_rows, Range::new(1.0, 128.0, 1.0)),
+        ]
+    }
+
+    /// cycles per second at `tempo`.
+    pub fn freq(&self, tempo: Bpm) -> f32 {
+        if self.sync {
+            tempo as f32 / 60.0 * ROWS_PER_BEAT / self.sync_rows.max(1) as f32
+        } else {
+            self.rate
+        }
+    }
+}
+
+/// the running state of an LFO for a single voice. restarts with every note.
+#[derive(Debug, Clone)]
+pub struct Lfo {
+    phase: f32,
+    /// the value held by the sample & hold shape, `None` till the first sample is taken.
+    held: Option<f32>,
+    seed: u32,
+}
+
+impl Lfo {
+    /// `seed` should differ between LFOs so sample & hold LFOs don't move together.
+    pub fn new(seed: u32) -> Self {
+        Self {
+            phase: 0.0,
+            held: None,
+            seed: seed.max(1),
+        }
+    }
+
+    fn random(&mut self) -> f32 {
+        // xorshift
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+
+        (self.seed as f32 / u32::MAX as f32) * 2.0 - 1.0
+    }
+
+    /// moves the LFO forward one sample and returns its value, from -1.0 to 1.0.
+    pub fn next(&mut self, params: &LfoParams, tempo: Bpm, sample_rate: f32) -> f32 {
+        let t = self.phase;
+
+        let value = match params.shape {
+            LfoShape::Sine => (t * TAU).sin(),
+            LfoShape::Triangle => 1.0 - 4.0 * (t - 0.5).abs(),
+            LfoShape::Square => {
+                if t < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            LfoShape::SampleHold => match self.held {
+                Some(held) => held,
+                None => {
+                    let held = self.random();
+                    self.held = Some(held);
+
+                    held
+                }
+            },
+        };
+
+        self.phase += params.freq(tempo) / sample_rate;
+
+        if self.phase >= 1.0 {
+            self.phase %= 1.0;
+            self.held = None;
+        }
+
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 1000.0;
+
+    #[test]
+    fn tempo_sync() {
+        let params = LfoParams {
+            shape: LfoShape::Square,
+            sync: true,
+            sync_rows: 16,
+            ..LfoParams::default()
+        };
+        let mut lfo = Lfo::new(1);
+
+        // 16 rows at 120 BPM is 2 seconds, so the square wave is high for the first second. the
+        // phase is a float so the edges are allowed to be a sample or two out.
+        let samples: Vec<f32> = (0..2000)
+            .map(|_| lfo.next(&params, 120, SAMPLE_RATE))
+            .collect();
+
+        assert!(samples[..998].iter().all(|&s| s == 1.0));
+        assert!(samples[1002..1998].iter().all(|&s| s == -1.0));
+        assert!((0..4).any(|_| lfo.next(&params, 120, SAMPLE_RATE) == 1.0));
+    }
+
+    #[test]
+    fn sample_hold() {
+        let params = LfoParams {
+            shape: LfoShape::SampleHold,
+            rate: 10.0,
+            ..LfoParams::default()
+        };
+        let mut lfo = Lfo::new(1);
+
+        let samples: Vec<f32> = (0..200)
+            .map(|_| lfo.next(&params, 120, SAMPLE_RATE))
+            .collect();
+
+        assert!(samples[..98].iter().all(|&s| s == samples[0]));
+        assert!(samples[102..198].iter().all(|&s| s == samples[102]));
+        assert_ne!(samples[0], samples[102]);
+        assert!(samples.iter().all(|s| (-1.0..=1.0).contains(s)));
+    }
+}
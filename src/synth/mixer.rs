@@ -0,0 +1,516 @@
+use super::{
+    dynamics::{Ducker, Dynamics, MasterDynamics, SIDECHAIN_SOURCE},
+    effects::{Delay, Effects, Reverb},
+    scope::{ScopeTap, SCOPE_DECIMATION},
+    spectrum::{Analyzer, SpectrumTap},
+};
+use crate::{
+    config::ui::Bpm,
+    params::{Param, Range},
+    sequencer::N_CHANNELS,
+};
+use bevy::prelude::Resource;
+use pyo3::{pyclass, pymethods};
+use serde::{Deserialize, Serialize};
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+/// one meter per channel and one for the master.
+pub const N_METERS: usize = N_CHANNELS + 1;
+/// how much a meter falls each buffer, so peaks stay up long enough to be seen.
+const METER_DECAY: f32 = 0.93;
+/// how long, in seconds, the loudest peak is held before it starts to fall.
+const PEAK_HOLD: f32 = 1.5;
+/// how long, in seconds, the RMS level is averaged over.
+const RMS_TIME: f32 = 0.3;
+/// the number of send effects.
+pub const N_SENDS: usize = 2;
+
+/// the send effects a channel can be sent to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendBus {
+    Delay,
+    Reverb,
+}
+
+/// the mixer settings of a single song channel.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MixerChannel {
+    pub volume: f32,
+    /// from -1.0 (left) to 1.0 (right).
+    pub pan: f32,
+    pub mute: bool,
+    /// how much of the channel is sent to the delay.
+    pub delay: f32,
+    /// how much of the channel is sent to the reverb.
+    pub reverb: f32,
+}
+
+impl Default for MixerChannel {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            pan: 0.0,
+            mute: false,
+            delay: 0.0,
+            reverb: 0.0,
+        }
+    }
+}
+
+impl MixerChannel {
+    pub fn send(&self, bus: SendBus) -> f32 {
+        match bus {
+            SendBus::Delay => self.delay,
+            SendBus::Reverb => self.reverb,
+        }
+    }
+}
+
+/// the mixer settings, saved with the song.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Mixer {
+    pub channels: [MixerChannel; N_CHANNELS],
+    pub master: f32,
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Self {
+            channels: [MixerChannel::default(); N_CHANNELS],
+            master: 0.8,
+        }
+    }
+}
+
+impl Mixer {
+    /// the parameters of a column of the mixer screen. there is a column for each channel, then
+    /// one for the master.
+    pub fn params(&mut self, col: usize) -> Vec<Param<'_>> {
+        let volume = Range::new(0.0, 1.0, 0.05);
+
+        match self.channels.get_mut(col) {
+            Some(channel) => vec![
+                Param::new("VOLUME", &mut channel.volume, volume),
+                Param::new("PAN", &mut channel.pan, Range::new(-1.0, 1.0, 0.05)),
+                Param::choice("MUTE", &mut channel.mute),
+                Param::new("DELAY", &mut channel.delay, volume),
+                Param::new("REVERB", &mut channel.reverb, volume),
+            ],
+            None => vec![Param::new("VOLUME", &mut self.master, volume)],
+        }
+    }
+}
+
+#[pymethods]
+impl Mixer {
+    /// the name and value of each parameter in a column, for display on the mixer screen.
+    fn rows(&self, col: usize) -> Vec<(String, String)> {
+        let mut mixer = *self;
+
+        mixer.params(col).iter().map(Param::row).collect()
+    }
+}
+
+/// the reading of one meter. levels are linear, 1.0 is full scale, so a hold over 1.0 means the
+/// signal clipped.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Level {
+    pub peak: f32,
+    pub rms: f32,
+    /// the loudest recent peak.
+    pub hold: f32,
+}
+
+/// the levels shown on the mixer screen and in the side bar.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct MixerLevels {
+    pub channels: [Level; N_CHANNELS],
+    pub master: Level,
+}
+
+/// one meter, shared between the threads.
+#[derive(Debug, Default)]
+struct Meter {
+    peak: AtomicU32,
+    /// the mean square, smoothed over `RMS_TIME`.
+    mean_square: AtomicU32,
+    hold: AtomicU32,
+    /// seconds since the hold was last set.
+    held_for: AtomicU32,
+}
+
+/// levels and the master bus gain reduction, written by the audio thread and read by the main
+/// loop. the values are `f32`s stored as bits so neither side ever waits on a lock.
+#[derive(Debug, Clone, Default, Resource)]
+pub struct Meters {
+    levels: Arc<[Meter; N_METERS]>,
+    /// in dB.
+    gain_reduction: Arc<AtomicU32>,
+}
+
+impl Meters {
+    /// feeds the latest buffer, `seconds` long, into meter `i`.
+    fn update(&self, i: usize, peak: f32, mean_square: f32, seconds: f32) {
+        let meter = &self.levels[i];
+        let load = |value: &AtomicU32| f32::from_bits(value.load(Ordering::Relaxed));
+        let store = |value: &AtomicU32, level: f32| value.store(level.to_bits(), Ordering::Relaxed);
+        let averaged = load(&meter.mean_square);
+        let hold = load(&meter.hold);
+        let held_for = load(&meter.held_for) + seconds;
+
+        store(&meter.peak, peak.max(load(&meter.peak) * METER_DECAY));
+        store(
+            &meter.mean_square,
+            averaged + (mean_square - averaged) * (1.0 - (-seconds / RMS_TIME).exp()),
+        );
+
+        if peak >= hold {
+            store(&meter.hold, peak);
+            store(&meter.held_for, 0.0);
+        } else if held_for > PEAK_HOLD {
+            store(&meter.hold, peak.max(hold * METER_DECAY));
+            store(&meter.held_for, held_for);
+        } else {
+            store(&meter.held_for, held_for);
+        }
+    }
+
+    pub fn levels(&self) -> MixerLevels {
+        let level = |i: usize| {
+            let meter = &self.levels[i];
+            let load = |value: &AtomicU32| f32::from_bits(value.load(Ordering::Relaxed));
+
+            Level {
+                peak: load(&meter.peak),
+                rms: load(&meter.mean_square).sqrt(),
+                hold: load(&meter.hold),
+            }
+        };
+
+        MixerLevels {
+            channels: std::array::from_fn(level),
+            master: level(N_CHANNELS),
+        }
+    }
+
+    /// the most the master bus was turned down, in dB, by the compressor and limiter over the
+    /// latest buffer.
+    pub fn gain_reduction(&self) -> f32 {
+        f32::from_bits(self.gain_reduction.load(Ordering::Relaxed))
+    }
+}
+
+/// how far through a buffer of `frames` frames a ramp from `start` to `target` is at frame `i`.
+fn ramp(start: f32, target: f32, i: usize, frames: f32) -> f32 {
+    start + (target - start) * (i + 1) as f32 / frames
+}
+
+/// the channel buses voices are rendered into, the send effects, and the mixer that sums them.
+/// lives on the audio thread.
+#[derive(Debug, Clone)]
+pub struct Buses {
+    pub settings: Mixer,
+    pub effects: Effects,
+    pub dynamics: Dynamics,
+    /// set by the phrase volume command, multiplies the channels volume.
+    pub row_volume: [f32; N_CHANNELS],
+    /// set by the phrase send commands, replaces the channels send levels.
+    pub row_sends: [[Option<f32>; N_SENDS]; N_CHANNELS],
+    /// the gain each channel ended the last buffer on. gains are ramped over a buffer so changes
+    /// don't click.
+    gains: [f32; N_CHANNELS],
+    send_gains: [[f32; N_SENDS]; N_CHANNELS],
+    master_gain: f32,
+    /// interleaved stereo, like the output buffer.
+    buses: [Vec<f32>; N_CHANNELS],
+    /// what is sent to each effect, also interleaved stereo.
+    sends: [Vec<f32>; N_SENDS],
+    delay: Delay,
+    reverb: Reverb,
+    ducker: Ducker,
+    master_dynamics: MasterDynamics,
+    sample_rate: f32,
+    pub meters: Meters,
+    pub scope: ScopeTap,
+    pub spectrum: SpectrumTap,
+    analyzer: Analyzer,
+    /// the points of the latest buffer kept for the scope, for each channel then the master.
+    scope_points: [Vec<f32>; N_METERS],
+    /// frames since the last point was kept for the scope.
+    scope_phase: usize,
+}
+
+impl Buses {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            settings: Mixer::default(),
+            effects: Effects::default(),
+            dynamics: Dynamics::default(),
+            row_volume: [1.0; N_CHANNELS],
+            row_sends: [[None; N_SENDS]; N_CHANNELS],
+            gains: [0.0; N_CHANNELS],
+            send_gains: [[0.0; N_SENDS]; N_CHANNELS],
+            master_gain: 0.0,
+            buses: Default::default(),
+            sends: Default::default(),
+            delay: Delay::new(sample_rate),
+            reverb: Reverb::new(sample_rate),
+            ducker: Ducker::default(),
+            master_dynamics: MasterDynamics::new(sample_rate),
+            sample_rate,
+            meters: Meters::default(),
+            scope: ScopeTap::default(),
+            spectrum: SpectrumTap::default(),
+            analyzer: Analyzer::new(sample_rate),
+            scope_points: Default::default(),
+            scope_phase: 0,
+        }
+    }
+
+    /// buses running at `sample_rate` with the same settings and taps. what the effects are
+    /// holding is dropped.
+    pub fn resampled(self, sample_rate: f32) -> Self {
+        Self {
+            settings: self.settings,
+            effects: self.effects,
+            dynamics: self.dynamics,
+            row_volume: self.row_volume,
+            row_sends: self.row_sends,
+            meters: self.meters,
+            scope: self.scope,
+            spectrum: self.spectrum,
+            ..Self::new(sample_rate)
+        }
+    }
+
+    /// empties the buses, ready to render `len` samples into.
+    pub fn clear(&mut self, len: usize) {
+        for bus in self.buses.iter_mut().chain(self.sends.iter_mut()) {
+            bus.clear();
+            bus.resize(len, 0.0);
+        }
+    }
+
+    /// the bus of `channel`, `None` if there is no such channel.
+    pub fn bus(&mut self, channel: usize) -> Option<&mut [f32]> {
+        self.buses.get_mut(channel).map(Vec::as_mut_slice)
+    }
+
+    /// mixes the buses, and the effects they are sent to, down into `out`, adding to what is
+    /// there.
+    pub fn mix(&mut self, out: &mut [f32], tempo: Bpm) {
+        let frames = (out.len() / 2).max(1) as f32;
+        let seconds = frames / self.sample_rate;
+        let [delay_send, reverb_send] = &mut self.sends;
+        let phase = self.scope_phase;
+        // true for the frames that are kept for the scope.
+        let keep = |j: usize| (phase + j).is_multiple_of(SCOPE_DECIMATION);
+        self.scope_points.iter_mut().for_each(Vec::clear);
+        let ducks = self.ducker.process(
+            &self.dynamics,
+            &self.buses[SIDECHAIN_SOURCE],
+            self.sample_rate,
+        );
+
+        for (i, bus) in self.buses.iter().enumerate() {
+            let channel = &self.settings.channels[i];
+            let (target, send_targets) = if channel.mute {
+                (0.0, [0.0; N_SENDS])
+            } else {
+                let sends = [SendBus::Delay, SendBus::Reverb]
+                    .map(|send| self.row_sends[i][send as usize].unwrap_or(channel.send(send)));
+
+                (channel.volume * self.row_volume[i], sends)
+            };
+            let start = self.gains[i];
+            let send_starts = self.send_gains[i];
+            let pan = channel.pan.clamp(-1.0, 1.0);
+            let ducked = self.dynamics.sidechain.ducks(i);
+            let mut peak = 0.0_f32;
+            let mut square_sum = 0.0;
+
+            for (j, (((frame, bus), delay), reverb)) in out
+                .chunks_exact_mut(2)
+                .zip(bus.chunks_exact(2))
+                .zip(delay_send.chunks_exact_mut(2))
+                .zip(reverb_send.chunks_exact_mut(2))
+                .enumerate()
+            {
+                let duck = if ducked { ducks[j] } else { 1.0 };
+                let gain = ramp(start, target, j, frames) * duck;
+                let left = bus[0] * gain * (1.0 - pan).min(1.0);
+                let right = bus[1] * gain * (1.0 + pan).min(1.0);
+
+                frame[0] += left;
+                frame[1] += right;
+                peak = peak.max(left.abs()).max(right.abs());
+                square_sum += left * left + right * right;
+
+                if keep(j) {
+                    self.scope_points[i].push((left + right) * 0.5);
+                }
+
+                // sends are post fader, so muting or fading a channel fades its effects too.
+                for (send, k) in [(delay, 0), (reverb, 1)] {
+                    let send_gain = ramp(send_starts[k], send_targets[k], j, frames);
+
+                    send[0] += left * send_gain;
+                    send[1] += right * send_gain;
+                }
+            }
+
+            self.gains[i] = target;
+            self.send_gains[i] = send_targets;
+            self.meters
+                .update(i, peak, square_sum / (frames * 2.0), seconds);
+        }
+
+        self.delay.process(&self.effects, tempo, delay_send, out);
+        self.reverb.process(&self.effects, reverb_send, out);
+
+        let start = self.master_gain;
+        let target = self.settings.master;
+
+        for (j, frame) in out.chunks_exact_mut(2).enumerate() {
+            let gain = ramp(start, target, j, frames);
+
+            frame[0] *= gain;
+            frame[1] *= gain;
+        }
+
+        self.master_gain = target;
+
+        let reduction = self.master_dynamics.process(&self.dynamics, out);
+        let peak = out.iter().fold(0.0_f32, |peak, s| peak.max(s.abs()));
+        let square_sum: f32 = out.iter().map(|s| s * s).sum();
+
+        for (_, frame) in out.chunks_exact(2).enumerate().filter(|(j, _)| keep(*j)) {
+            self.scope_points[N_CHANNELS].push((frame[0] + frame[1]) * 0.5);
+        }
+
+        self.scope.push(&self.scope_points);
+        self.analyzer.push(out, &self.spectrum);
+        self.scope_phase = (phase + out.len() / 2) % SCOPE_DECIMATION;
+
+        self.meters
+            .gain_reduction
+            .store(reduction.to_bits(), Ordering::Relaxed);
+        self.meters
+            .update(N_CHANNELS, peak, square_sum / (frames * 2.0), seconds);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// buses with the master dynamics off, so levels come through untouched.
+    fn buses() -> Buses {
+        let mut buses = Buses::new(48_000.0);
+        buses.dynamics.compressor = false;
+        buses.dynamics.limiter = false;
+
+        buses
+    }
+
+    fn mix(buses: &mut Buses, input: f32) -> Vec<f32> {
+        let mut out = vec![0.0; 8];
+        buses.clear(out.len());
+
+        for channel in 0..N_CHANNELS {
+            buses.bus(channel).unwrap().fill(input);
+        }
+
+        buses.mix(&mut out, 120);
+        out
+    }
+
+    #[test]
+    fn volume_pan_and_mute() {
+        let mut buses = buses();
+        buses.settings.master = 1.0;
+        buses.settings.channels[1].mute = true;
+        buses.settings.channels[2].pan = -1.0;
+        buses.settings.channels[3].volume = 0.5;
+
+        // the first buffer ramps up from silence.
+        mix(&mut buses, 0.25);
+        let out = mix(&mut buses, 0.25);
+
+        assert_eq!(out[0], 0.25 + 0.25 + 0.125);
+        assert_eq!(out[1], 0.25 + 0.125);
+    }
+
+    #[test]
+    fn row_volume_ramps() {
+        let mut buses = buses();
+        buses.settings.master = 1.0;
+        mix(&mut buses, 0.25);
+
+        buses.row_volume = [0.0; N_CHANNELS];
+        let out = mix(&mut buses, 0.25);
+
+        assert!(out
+            .chunks(2)
+            .map(|frame| frame[0])
+            .is_sorted_by(|a, b| a >= b));
+        assert_eq!(out[6], 0.0);
+        assert!(buses.meters.levels().master.peak > 0.0);
+    }
+    #[test]
+    fn sends_are_post_fader() {
+        let mut buses = buses();
+        buses.settings.master = 1.0;
+        buses.effects.delay_mix = 0.0;
+        buses
+            .settings
+            .channels
+            .iter_mut()
+            .for_each(|channel| channel.mute = true);
+        buses.settings.channels[0] = MixerChannel {
+            volume: 0.0,
+            ..MixerChannel::default()
+        };
+        buses.row_sends[0][SendBus::Reverb as usize] = Some(1.0);
+
+        // the reverbs shortest comb is ~1200 samples long, so give it time to come back around.
+        let ring = |buses: &mut Buses| (0..1000).map(|_| mix(buses, 0.25)).last().unwrap();
+
+        assert!(ring(&mut buses).iter().all(|s| *s == 0.0));
+
+        buses.settings.channels[0].volume = 1.0;
+
+        assert!(ring(&mut buses).iter().any(|s| (s - 0.25).abs() > 1e-3));
+    }
+
+    #[test]
+    fn peaks_are_held() {
+        let meters = Meters::default();
+        // a full scale sine has an RMS of 1/sqrt(2).
+        meters.update(0, 1.0, 0.5, 10.0);
+
+        assert!((meters.levels().channels[0].rms - 0.5_f32.sqrt()).abs() < 1e-3);
+
+        for _ in 0..10 {
+            meters.update(0, 0.0, 0.0, 0.1);
+        }
+
+        let level = meters.levels().channels[0];
+
+        assert!(level.peak < 0.5);
+        assert_eq!(level.hold, 1.0);
+
+        meters.update(0, 0.0, 0.0, 1.0);
+
+        assert!(meters.levels().channels[0].hold < 1.0);
+    }
+}
@@ -0,0 +1,1468 @@
+use crate::{
+    config::{
+        audio::AudioConfig,
+        ui::{get_config, Bpm},
+    },
+    params::{Choice, Param, Range},
+    pygame_coms::{Index, Instrument, InstrumentOutput, Instruments, Note, Song},
+    sequencer::{ExternalTempo, NoteEvent, ScheduledNote, N_CHANNELS},
+    tracker_state::{AllInstruments, AllWavetables, Tempo},
+};
+use bevy::{log::*, prelude::*};
+use crossbeam::channel::{unbounded, Receiver, Sender, TryRecvError};
+use drums::{Drum, DrumVoice};
+use dynamics::Dynamics;
+use effects::Effects;
+use envelope::{Envelope, ModEnvTarget};
+use filter::Filter;
+use fm::FmVoice;
+use gameboy::{GbVoice, WAVE_STEPS};
+use inserts::InsertBuses;
+use lfo::{Lfo, N_LFOS};
+use mixer::{Buses, Meters, Mixer, SendBus};
+use mod_matrix::ModSource;
+use osc::{OscType, Oscillator};
+use output::Backend;
+use pyo3::pyclass;
+use sampler::{Sample, SampleLoader, SamplerMode, SamplerVoice};
+use scope::ScopeTap;
+use serde::{Deserialize, Serialize};
+use spectrum::SpectrumTap;
+use std::{
+    array,
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc,
+    },
+    thread::spawn,
+};
+use voices::{Allocated, Polyphony, StealFade, VoiceMode};
+use wavetable::Wavetable;
+
+pub mod drums;
+pub mod dynamics;
+pub mod effects;
+pub mod envelope;
+pub mod export;
+pub mod filter;
+pub mod fm;
+pub mod gameboy;
+pub mod inserts;
+pub mod lfo;
+pub mod mixer;
+pub mod mod_matrix;
+pub mod osc;
+pub mod output;
+pub mod sampler;
+pub mod scope;
+pub mod spectrum;
+pub mod voices;
+pub mod wav;
+pub mod wavetable;
+
+pub const SAMPLE_RATE: u32 = 48_000;
+/// the number of frames rendered at a time, unless the audio config picks another.
+pub const BUFFER_FRAMES: usize = 256;
+/// the most voices that can sound at once, the oldest voice is dropped past this.
+pub const MAX_VOICES: usize = 32;
+/// how far ahead, in seconds, rows are scheduled, so they reach the audio thread before the
+/// frame they land on is rendered.
+pub const SCHEDULE_AHEAD: f32 = 0.04;
+/// how far, in semitones, a pitch modulation at full amount bends a note.
+const PITCH_MOD_RANGE: f32 = 24.0;
+/// how far a pulse width modulation at full amount moves the pulse width.
+const PULSE_WIDTH_MOD_RANGE: f32 = 0.45;
+/// how far, in semitones, a cutoff modulation at full amount moves the filter cutoff.
+const CUTOFF_MOD_RANGE: f32 = 60.0;
+/// how far, in semitones, the pitch wheel bends live notes.
+const PITCH_BEND_RANGE: f32 = 2.0;
+
+/// the channel live notes played on mixer channel `channel` are kept on, so they aren't cut
+/// off by the notes the song plays there.
+pub fn live_channel(channel: usize) -> usize {
+    N_CHANNELS + channel
+}
+
+/// true for the channels of live notes.
+fn is_live(channel: usize) -> bool {
+    channel >= N_CHANNELS
+}
+
+/// the mixer channel a voice on `channel` is heard on.
+pub fn bus_channel(channel: usize) -> usize {
+    channel % N_CHANNELS
+}
+
+/// true if `voice` is playing on `channel` and is `note`, or any note if `note` is `None`.
+fn is_playing<V: Allocated>(voice: &V, channel: usize, note: Option<Note>) -> bool {
+    voice.channel() == channel && note.is_none_or(|note| voice.note() == note)
+}
+
+/// the frequency, in Hz, of a (possibly fractional) MIDI note.
+pub fn note_freq(note: f32) -> f32 {
+    440.0 * 2.0_f32.powf((note - 69.0) / 12.0)
+}
+
+/// parameters of the built-in synth, one set per instrument.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SynthParams {
+    pub osc: OscType,
+    /// the width of the pulse wave, 0.5 is a square wave.
+    pub pulse_width: f32,
+    /// semitones the instrument is transposed by.
+    pub transpose: i8,
+    /// fine tuning in cents.
+    pub fine_tune: f32,
+    pub volume: f32,
+    /// the wavetable played when `osc` is `OscType::Wavetable`.
+    pub wavetable: u8,
+    /// how far through the wavetables frames to play, from 0.0 to 1.0.
+    pub wave_pos: f32,
+}
+
+impl Default for SynthParams {
+    fn default() -> Self {
+        Self {
+            osc: OscType::default(),
+            pulse_width: 0.5,
+            transpose: 0,
+            fine_tune: 0.0,
+            volume: 0.8,
+            wavetable: 0,
+            wave_pos: 0.0,
+        }
+    }
+}
+
+impl SynthParams {
+    pub fn params(&mut self) -> Vec<Param<'_>> {
+        vec![
+            Param::choice("OSC", &mut self.osc),
+            Param::new(
+                "PULSE W",
+                &mut self.pulse_width,
+                Range::new(0.05, 0.95, 0.05),
+            ),
+            Param::new(
+                "TRANSPOSE",
+                &mut self.transpose,
+                Range::new(-48.0, 48.0, 1.0),
+            ),
+            Param::new("FINE", &mut self.fine_tune, Range::new(-50.0, 50.0, 1.0)),
+            Param::new("VOLUME", &mut self.volume, Range::new(0.0, 1.0, 0.05)),
+            Param::new("WAVE", &mut self.wavetable, Range::new(0.0, 255.0, 1.0)),
+            Param::new("WAVE POS", &mut self.wave_pos, Range::new(0.0, 1.0, 0.05)),
+        ]
+    }
+}
+
+/// messages sent to the audio thread.
+#[derive(Debug, Clone)]
+pub enum SynthCmd {
+    NoteOn {
+        channel: usize,
+        instrument: Index,
+        notes: Vec<Note>,
+        /// from 0.0 to 1.0.
+        velocity: f32,
+    },
+    NoteOff {
+        channel: usize,
+    },
+    /// overrides the filter cutoff, as a MIDI note, of the voices playing on a channel and the
+    /// voices it starts after. `None` goes back to the instruments cutoff.
+    SetCutoff {
+        channel: usize,
+        cutoff: Option<f32>,
+    },
+    /// replaces the audio threads copy of an instrument.
+    SetInstrument(Index, Box<Instrument>),
+    /// the tempo, tempo synced LFOs follow it.
+    SetTempo(Bpm),
+    /// replaces the audio threads copy of a wavetable.
+    SetWavetable(Index, Arc<Wavetable>),
+    /// the mixer settings of the song.
+    SetMixer(Mixer),
+    /// sets the gain of a channel, from the phrase volume command.
+    SetChannelVolume {
+        channel: usize,
+        volume: f32,
+    },
+    /// the send effects settings of the song.
+    SetEffects(Effects),
+    /// the master bus compressor, limiter, and sidechain settings of the song.
+    SetDynamics(Dynamics),
+    /// overrides how much of a channel is sent to an effect, from the phrase send commands.
+    /// `None` goes back to the mixers send level.
+    SetSend {
+        channel: usize,
+        bus: SendBus,
+        amount: Option<f32>,
+    },
+    /// a sample, loaded by the loader thread, from the WAV file at the path.
+    SetSample(PathBuf, Arc<Sample>),
+    /// the WAV files in a kit folder, in the order they are mapped to notes.
+    SetKit(PathBuf, Vec<PathBuf>),
+    /// closes the audio output and opens the one in the config. handled by the audio thread,
+    /// not the synth.
+    SetOutput(AudioConfig),
+    /// renders `frames` frames of `cmds` into a WAV file at `path`, on a thread of its own, with
+    /// a copy of the synth. handled by the audio thread, not the synth.
+    Export {
+        path: PathBuf,
+        cmds: Vec<(u64, SynthCmd)>,
+        frames: u64,
+    },
+    /// plays a note from the MIDI input, on top of whatever the song plays on `channel`.
+    LiveNoteOn {
+        channel: usize,
+        instrument: Index,
+        note: Note,
+        /// from 0.0 to 1.0.
+        velocity: f32,
+    },
+    /// releases a note from the MIDI input.
+    LiveNoteOff {
+        channel: usize,
+        note: Note,
+    },
+    /// bends live notes, from -1.0 to 1.0.
+    SetPitchBend(f32),
+    /// the mod wheel, a modulation source of live notes, from 0.0 to 1.0.
+    SetModWheel(f32),
+}
+
+/// used to send commands to the audio thread. each command goes with the frame it lands on.
+#[derive(Debug, Clone, Resource)]
+pub struct SynthHandle(pub Sender<(u64, SynthCmd)>);
+
+impl SynthHandle {
+    /// runs `cmd` as soon as it arrives.
+    pub fn send(&self, cmd: SynthCmd) {
+        self.send_at(0, cmd);
+    }
+
+    /// runs `cmd` on frame `at` of the `AudioClock`, or as soon as it arrives if that frame has
+    /// already been rendered.
+    pub fn send_at(&self, at: u64, cmd: SynthCmd) {
+        if let Err(e) = self.0.send((at, cmd)) {
+            error!("failed to send command to the audio thread: {e}");
+        }
+    }
+}
+
+/// the number of frames the audio thread has rendered, and the rate it renders them at. the
+/// sequencer schedules rows against it.
+#[derive(Debug, Clone, Default, Resource)]
+pub struct AudioClock {
+    frames: Arc<AtomicU64>,
+    sample_rate: Arc<AtomicU32>,
+    underruns: Arc<AtomicU64>,
+}
+
+impl AudioClock {
+    pub fn now(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    /// `SAMPLE_RATE` till the audio output has been opened.
+    pub fn sample_rate(&self) -> f32 {
+        match self.sample_rate.load(Ordering::Relaxed) {
+            0 => SAMPLE_RATE as f32,
+            rate => rate as f32,
+        }
+    }
+
+    /// the times the audio output has run dry since the app started.
+    pub fn underruns(&self) -> u64 {
+        self.underruns.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Voice {
+    channel: usize,
+    instrument: Index,
+    note: Note,
+    osc: Oscillator,
+    amp_env: Envelope,
+    mod_env: Envelope,
+    lfos: [Lfo; N_LFOS],
+    velocity: f32,
+    filter: Filter,
+    /// set by a cutoff command, replaces the instruments cutoff.
+    cutoff: Option<f32>,
+    fade: StealFade,
+}
+
+impl Allocated for Voice {
+    fn channel(&self) -> usize {
+        self.channel
+    }
+
+    fn instrument(&self) -> Index {
+        self.instrument
+    }
+
+    fn note(&self) -> Note {
+        self.note
+    }
+
+    fn level(&self) -> f32 {
+        self.amp_env.level() * self.velocity
+    }
+
+    fn is_held(&self) -> bool {
+        self.amp_env.is_held()
+    }
+
+    fn set_note(&mut self, note: Note) -> bool {
+        self.note = note;
+        true
+    }
+    fn steal(&mut self) {
+        self.fade.steal();
+    }
+
+    fn is_stolen(&self) -> bool {
+        self.fade.is_stolen()
+    }
+}
+
+/// the synth engine. lives on the audio thread.
+#[derive(Debug, Clone)]
+pub struct Synth {
+    sample_rate: f32,
+    tempo: Bpm,
+    instruments: Vec<Option<Instrument>>,
+    wavetables: Vec<Option<Arc<Wavetable>>>,
+    samples: HashMap<PathBuf, Arc<Sample>>,
+    kits: HashMap<PathBuf, Vec<PathBuf>>,
+    voices: Vec<Voice>,
+    /// the cutoff set by the last cutoff command of each channel, for the voices it starts.
+    cutoffs: [Option<f32>; N_CHANNELS],
+    drums: Vec<Drum>,
+    samplers: Vec<SamplerVoice>,
+    fm_voices: Vec<FmVoice>,
+    gb_voices: Vec<GbVoice>,
+    inserts: InsertBuses,
+    buses: Buses,
+    /// semitones live notes are bent by.
+    pitch_bend: f32,
+    mod_wheel: f32,
+    /// frames rendered so far.
+    frame: u64,
+    /// commands waiting for their frame, soonest on top.
+    pending: BinaryHeap<Reverse<Pending>>,
+    /// the number of commands scheduled so far, to keep commands for the same frame in order.
+    scheduled: u64,
+}
+
+/// a command waiting for its frame. ordered by frame, then by when it was scheduled.
+#[derive(Debug, Clone)]
+struct Pending {
+    at: u64,
+    order: u64,
+    cmd: SynthCmd,
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        (self.at, self.order) == (other.at, other.order)
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.at, self.order).cmp(&(other.at, other.order))
+    }
+}
+
+impl Synth {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            tempo: 120,
+            instruments: Vec::with_capacity(256),
+            wavetables: Vec::with_capacity(256),
+            samples: HashMap::new(),
+            kits: HashMap::new(),
+            // with room for as many stolen voices again, fading out.
+            voices: Vec::with_capacity(MAX_VOICES * 2),
+            cutoffs: [None; N_CHANNELS],
+            drums: Vec::with_capacity(MAX_VOICES),
+            samplers: Vec::with_capacity(MAX_VOICES * 2),
+            fm_voices: Vec::with_capacity(MAX_VOICES * 2),
+            gb_voices: Vec::with_capacity(MAX_VOICES * 2),
+            inserts: InsertBuses::new(sample_rate),
+            buses: Buses::new(sample_rate),
+            pitch_bend: 0.0,
+            mod_wheel: 0.0,
+            frame: 0,
+            pending: BinaryHeap::new(),
+            scheduled: 0,
+        }
+    }
+
+    /// a synth running at `sample_rate` with the same instruments, samples, and settings. the
+    /// voices playing now are dropped.
+    fn resampled(self, sample_rate: f32) -> Self {
+        Self {
+            tempo: self.tempo,
+            instruments: self.instruments,
+            wavetables: self.wavetables,
+            samples: self.samples,
+            kits: self.kits,
+            cutoffs: self.cutoffs,
+            buses: self.buses.resampled(sample_rate),
+            pitch_bend: self.pitch_bend,
+            mod_wheel: self.mod_wheel,
+            frame: self.frame,
+            pending: self.pending,
+            scheduled: self.scheduled,
+            ..Self::new(sample_rate)
+        }
+    }
+
+    /// runs `cmd` once `at` frames have been rendered, or straight away if they already have.
+    pub fn schedule(&mut self, at: u64, cmd: SynthCmd) {
+        if at <= self.frame {
+            self.handle(cmd);
+            return;
+        }
+
+        // after any commands for the same frame, so they run in the order they were sent.
+        self.pending.push(Reverse(Pending {
+            at,
+            order: self.scheduled,
+            cmd,
+        }));
+        self.scheduled += 1;
+    }
+
+    /// the polyphony settings of `instrument`, the defaults if it isn't known.
+    fn polyphony(&self, instrument: Index) -> Polyphony {
+        self.instruments
+            .get(instrument)
+            .and_then(Option::as_ref)
+            .map_or_else(Polyphony::default, |inst| inst.polyphony)
+    }
+
+    /// moves a held voice of `instrument` on `channel` to `note` instead of starting a new one.
+    /// false if there is no voice to move.
+    fn glide(
+        &mut self,
+        channel: usize,
+        instrument: Index,
+        output: InstrumentOutput,
+        note: Note,
+    ) -> bool {
+        match output {
+            InstrumentOutput::Synth => voices::glide(&mut self.voices, channel, instrument, note),
+            InstrumentOutput::Sampler => {
+                voices::glide(&mut self.samplers, channel, instrument, note)
+            }
+            InstrumentOutput::Fm => voices::glide(&mut self.fm_voices, channel, instrument, note),
+            InstrumentOutput::GameBoy => {
+                voices::glide(&mut self.gb_voices, channel, instrument, note)
+            }
+            InstrumentOutput::Percusion | InstrumentOutput::UsbMidi => false,
+        }
+    }
+
+    pub fn handle(&mut self, cmd: SynthCmd) {
+        match cmd {
+            SynthCmd::NoteOn {
+                channel,
+                instrument,
+                notes,
+                velocity,
+            } => {
+                if channel >= N_CHANNELS {
+                    warn!("channel {channel} has no bus on the mixer");
+                    return;
+                }
+
+                let Some(Some(inst)) = self.instruments.get(instrument) else {
+                    warn!("instrument {instrument} is not known to the synth");
+                    self.release_notes(channel, None);
+                    return;
+                };
+                let output = inst.output;
+                let polyphony = inst.polyphony;
+                let notes = match output {
+                    InstrumentOutput::Percusion => notes,
+                    _ => polyphony.notes(notes),
+                };
+
+                if polyphony.mode == VoiceMode::Legato
+                    && let [note] = notes[..]
+                    && self.glide(channel, instrument, output, note)
+                {
+                    return;
+                }
+
+                self.release_notes(channel, None);
+
+                for note in notes {
+                    self.start_note(channel, instrument, output, note, velocity);
+                }
+            }
+            SynthCmd::NoteOff { channel } => self.release_notes(channel, None),
+            SynthCmd::SetCutoff { channel, cutoff } => {
+                if let Some(channel_cutoff) = self.cutoffs.get_mut(channel) {
+                    *channel_cutoff = cutoff;
+                }
+
+                // voices already sounding keep the last cutoff they were given.
+                if cutoff.is_some() {
+                    self.voices
+                        .iter_mut()
+                        .filter(|voice| voice.channel == channel)
+                        .for_each(|voice| voice.cutoff = cutoff);
+                }
+            }
+            SynthCmd::SetInstrument(i, instrument) => {
+                if self.instruments.len() <= i {
+                    self.instruments.resize(i + 1, None);
+                }
+
+                self.instruments[i] = Some(*instrument);
+            }
+            SynthCmd::SetTempo(tempo) => self.tempo = tempo,
+            SynthCmd::SetWavetable(i, wavetable) => {
+                if self.wavetables.len() <= i {
+                    self.wavetables.resize(i + 1, None);
+                }
+
+                self.wavetables[i] = Some(wavetable);
+            }
+            SynthCmd::SetMixer(mixer) => self.buses.settings = mixer,
+            SynthCmd::SetChannelVolume { channel, volume } => {
+                if let Some(row_volume) = self.buses.row_volume.get_mut(channel) {
+                    *row_volume = volume;
+                }
+            }
+            SynthCmd::SetEffects(effects) => self.buses.effects = effects,
+            SynthCmd::SetDynamics(dynamics) => self.buses.dynamics = dynamics,
+            SynthCmd::SetSend {
+                channel,
+                bus,
+                amount,
+            } => {
+                if let Some(row_sends) = self.buses.row_sends.get_mut(channel) {
+                    row_sends[bus as usize] = amount;
+                }
+            }
+            SynthCmd::SetSample(path, sample) => {
+                self.samples.insert(path, sample);
+            }
+            SynthCmd::SetKit(dir, kit) => {
+                self.kits.insert(dir, kit);
+            }
+            SynthCmd::SetOutput(_) | SynthCmd::Export { .. } => {}
+            SynthCmd::LiveNoteOn {
+                channel,
+                instrument,
+                note,
+                velocity,
+            } => {
+                let Some(Some(inst)) = self.instruments.get(instrument) else {
+                    warn!("instrument {instrument} is not known to the synth");
+                    return;
+                };
+                let output = inst.output;
+                let mode = inst.polyphony.mode;
+                let channel = live_channel(channel);
+
+                if mode == VoiceMode::Legato && self.glide(channel, instrument, output, note) {
+                    return;
+                }
+
+                if mode != VoiceMode::Poly {
+                    self.release_notes(channel, None);
+                }
+
+                self.start_note(channel, instrument, output, note, velocity);
+            }
+            SynthCmd::LiveNoteOff { channel, note } => {
+                self.release_notes(live_channel(channel), Some(note))
+            }
+            SynthCmd::SetPitchBend(bend) => self.pitch_bend = bend * PITCH_BEND_RANGE,
+            SynthCmd::SetModWheel(amount) => self.mod_wheel = amount,
+        }
+    }
+
+    fn start_note(
+        &mut self,
+        channel: usize,
+        instrument: Index,
+        output: InstrumentOutput,
+        note: Note,
+        velocity: f32,
+    ) {
+        match output {
+            InstrumentOutput::Synth => self.start_voice(channel, instrument, note, velocity),
+            InstrumentOutput::Percusion => self.hit_drum(channel, instrument, note, velocity),
+            InstrumentOutput::Sampler => self.play_sample(channel, instrument, note, velocity),
+            InstrumentOutput::Fm => self.start_fm_voice(channel, instrument, note, velocity),
+            InstrumentOutput::GameBoy => self.start_gb_voice(channel, instrument, note, velocity),
+            InstrumentOutput::UsbMidi => {}
+        }
+    }
+
+    fn start_voice(&mut self, channel: usize, instrument: Index, note: Note, velocity: f32) {
+        let polyphony = self.polyphony(instrument);
+        voices::make_room(&mut self.voices, &polyphony, instrument, note);
+
+        let mut voice = Voice {
+            channel,
+            instrument,
+            note,
+            osc: Oscillator::default(),
+            amp_env: Envelope::default(),
+            mod_env: Envelope::default(),
+            lfos: array::from_fn(|i| Lfo::new(note as u32 * N_LFOS as u32 + i as u32)),
+            velocity,
+            filter: Filter::default(),
+            cutoff: self.cutoffs.get(channel).copied().flatten(),
+            fade: StealFade::default(),
+        };
+        voice.amp_env.gate_on();
+        voice.mod_env.gate_on();
+
+        self.voices.push(voice);
+    }
+
+    /// drums are one shots, they play till they fade out and ignore note offs.
+    fn hit_drum(&mut self, channel: usize, instrument: Index, note: Note, velocity: f32) {
+        let voice = DrumVoice::from_note(note);
+
+        if voice.is_hat() {
+            self.drums
+                .iter_mut()
+                .filter(|drum| drum.instrument == instrument && drum.voice.is_hat())
+                .for_each(Drum::choke);
+        }
+
+        if self.drums.len() >= MAX_VOICES {
+            self.drums.remove(0);
+        }
+
+        self.drums
+            .push(Drum::new(channel, instrument, voice, velocity));
+    }
+
+    fn play_sample(&mut self, channel: usize, instrument: Index, note: Note, velocity: f32) {
+        let Some(Some(inst)) = self.instruments.get(instrument) else {
+            return;
+        };
+        let params = &inst.sampler;
+
+        let path = match (params.mode, &params.sample) {
+            (_, None) => return,
+            (SamplerMode::Kit, Some(sample)) => sample
+                .parent()
+                .and_then(|dir| self.kits.get(dir))
+                .and_then(|kit| kit.get(note.checked_sub(params.root)? as usize)),
+            (_, Some(sample)) => Some(sample),
+        };
+        let Some(path) = path else {
+            return;
+        };
+
+        let Some(sample) = self.samples.get(path) else {
+            warn!("{} has not been loaded yet", path.display());
+            return;
+        };
+
+        let Some(voice) = SamplerVoice::new(
+            channel,
+            instrument,
+            params,
+            sample.clone(),
+            note,
+            velocity,
+            self.sample_rate,
+        ) else {
+            return;
+        };
+
+        voices::make_room(&mut self.samplers, &inst.polyphony, instrument, note);
+        self.samplers.push(voice);
+    }
+
+    fn start_fm_voice(&mut self, channel: usize, instrument: Index, note: Note, velocity: f32) {
+        let polyphony = self.polyphony(instrument);
+        voices::make_room(&mut self.fm_voices, &polyphony, instrument, note);
+
+        self.fm_voices
+            .push(FmVoice::new(channel, instrument, note, velocity));
+    }
+
+    fn start_gb_voice(&mut self, channel: usize, instrument: Index, note: Note, velocity: f32) {
+        let Some(Some(inst)) = self.instruments.get(instrument) else {
+            return;
+        };
+        let params = inst.gameboy;
+        let wave = self
+            .wavetables
+            .get(params.wave as usize)
+            .and_then(Option::as_deref)
+            .map_or([0; WAVE_STEPS], gameboy::wave_ram);
+
+        voices::make_room(&mut self.gb_voices, &inst.polyphony, instrument, note);
+
+        self.gb_voices.push(GbVoice::new(
+            channel, instrument, params, wave, note, velocity,
+        ));
+    }
+
+    /// releases `note` on `channel`, or every note on it if `note` is `None`.
+    fn release_notes(&mut self, channel: usize, note: Option<Note>) {
+        for voice in self
+            .voices
+            .iter_mut()
+            .filter(|voice| is_playing(*voice, channel, note))
+        {
+            let Some(Some(inst)) = self.instruments.get(voice.instrument) else {
+                continue;
+            };
+
+            voice.amp_env.gate_off(&inst.amp_env, self.sample_rate);
+            voice.mod_env.gate_off(&inst.mod_env.adsr, self.sample_rate);
+        }
+
+        for voice in self
+            .samplers
+            .iter_mut()
+            .filter(|voice| is_playing(*voice, channel, note))
+        {
+            if let Some(Some(inst)) = self.instruments.get(voice.instrument) {
+                voice.amp_env.gate_off(&inst.amp_env, self.sample_rate);
+            }
+        }
+
+        for voice in self
+            .fm_voices
+            .iter_mut()
+            .filter(|voice| is_playing(*voice, channel, note))
+        {
+            if let Some(Some(inst)) = self.instruments.get(voice.instrument) {
+                voice.gate_off(&inst.fm, self.sample_rate);
+            }
+        }
+
+        self.gb_voices
+            .iter_mut()
+            .filter(|voice| is_playing(*voice, channel, note))
+            .for_each(GbVoice::stop);
+    }
+
+    /// renders interleaved stereo samples into `out`. the buffer is split at the frames pending
+    /// commands land on, so notes start on the frame they were scheduled for.
+    pub fn render(&mut self, out: &mut [f32]) {
+        let mut rendered = 0;
+
+        while rendered < out.len() {
+            while let Some(Reverse(pending)) = self.pending.peek()
+                && pending.at <= self.frame
+            {
+                if let Some(Reverse(Pending { cmd, .. })) = self.pending.pop() {
+                    self.handle(cmd);
+                }
+            }
+
+            let frames = self.pending.peek().map_or(usize::MAX, |Reverse(pending)| {
+                usize::try_from(pending.at - self.frame).unwrap_or(usize::MAX)
+            });
+            let end = rendered + (out.len() - rendered).min(frames.saturating_mul(2));
+
+            self.render_frames(&mut out[rendered..end]);
+            self.frame += (end - rendered) as u64 / 2;
+            rendered = end;
+        }
+    }
+
+    /// renders `out` with no commands landing part way through it.
+    fn render_frames(&mut self, out: &mut [f32]) {
+        out.fill(0.0);
+        self.inserts.clear(out.len());
+        self.buses.clear(out.len());
+
+        // the pitch and mod wheels only move live notes.
+        let (pitch_bend, wheel) = (self.pitch_bend, self.mod_wheel);
+        let bend = |channel| if is_live(channel) { pitch_bend } else { 0.0 };
+        let mod_wheel = |channel| if is_live(channel) { wheel } else { 0.0 };
+
+        for voice in self.voices.iter_mut() {
+            let Some(Some(inst)) = self.instruments.get(voice.instrument) else {
+                // the instrument is gone, so the voice can never finish its release.
+                voice.amp_env = Envelope::default();
+                continue;
+            };
+
+            let params = &inst.synth;
+            let mod_env = &inst.mod_env;
+            let note = voice.note as f32
+                + params.transpose as f32
+                + params.fine_tune / 100.0
+                + bend(voice.channel);
+            let mod_wheel = mod_wheel(voice.channel);
+            let freq = note_freq(note);
+            let filter = &inst.filter;
+            let cutoff = voice.cutoff.unwrap_or(filter.cutoff_for(note));
+            let wavetable = self
+                .wavetables
+                .get(params.wavetable as usize)
+                .and_then(Option::as_deref);
+
+            let bus = self.inserts.bus(voice.channel, voice.instrument);
+
+            for frame in bus.chunks_exact_mut(2) {
+                let amp = voice.amp_env.next(&inst.amp_env, self.sample_rate);
+                let env = voice.mod_env.next(&mod_env.adsr, self.sample_rate);
+                let lfos: [f32; N_LFOS] = array::from_fn(|i| {
+                    voice.lfos[i].next(&inst.lfos[i], self.tempo, self.sample_rate)
+                });
+                let velocity = voice.velocity;
+
+                let modulation = inst.mod_matrix.apply(|source| match source {
+                    ModSource::Off => 0.0,
+                    ModSource::Lfo1 => lfos[0],
+                    ModSource::Lfo2 => lfos[1],
+                    ModSource::ModEnv => env,
+                    ModSource::AmpEnv => amp,
+                    ModSource::Velocity => velocity,
+                    ModSource::Note => (note - 60.0) / 60.0,
+                    ModSource::ModWheel => mod_wheel,
+                });
+
+                let mut pitch = modulation.pitch;
+                let mut pulse_width = modulation.pulse_width;
+                let mut cutoff_mod = modulation.cutoff;
+
+                match mod_env.target {
+                    ModEnvTarget::Off => {}
+                    ModEnvTarget::Pitch => pitch += env * mod_env.amount,
+                    ModEnvTarget::PulseWidth => pulse_width += env * mod_env.amount,
+                    ModEnvTarget::Cutoff => cutoff_mod += env * mod_env.amount,
+                }
+
+                let freq = if pitch != 0.0 {
+                    freq * 2.0_f32.powf(pitch * PITCH_MOD_RANGE / 12.0)
+                } else {
+                    freq
+                };
+                let pulse_width = params.pulse_width + pulse_width * PULSE_WIDTH_MOD_RANGE;
+                let cutoff = cutoff + cutoff_mod * CUTOFF_MOD_RANGE;
+
+                let sample = match (params.osc, wavetable) {
+                    (OscType::Wavetable, Some(table)) => voice.osc.next_wavetable(
+                        table,
+                        params.wave_pos + modulation.wave_pos,
+                        freq,
+                        self.sample_rate,
+                    ),
+                    (osc, _) => voice.osc.next(osc, freq, pulse_width, self.sample_rate),
+                };
+                let sample = voice.filter.process(
+                    sample,
+                    filter.mode,
+                    cutoff,
+                    filter.resonance,
+                    self.sample_rate,
+                ) * amp
+                    * velocity
+                    * (1.0 + modulation.volume).max(0.0)
+                    * voice.fade.next(self.sample_rate)
+                    * params.volume
+                    * 0.25;
+                let pan = modulation.pan.clamp(-1.0, 1.0);
+
+                frame[0] += sample * (1.0 - pan).min(1.0);
+                frame[1] += sample * (1.0 + pan).min(1.0);
+            }
+        }
+
+        for drum in self.drums.iter_mut() {
+            let Some(Some(inst)) = self.instruments.get(drum.instrument) else {
+                continue;
+            };
+            let params = inst.drums.get(drum.voice);
+
+            let bus = self.inserts.bus(drum.channel, drum.instrument);
+
+            for frame in bus.chunks_exact_mut(2) {
+                let sample = drum.next(params, self.sample_rate) * 0.5;
+
+                frame[0] += sample;
+                frame[1] += sample;
+            }
+        }
+
+        for voice in self.samplers.iter_mut() {
+            let Some(Some(inst)) = self.instruments.get(voice.instrument) else {
+                voice.amp_env = Envelope::default();
+                continue;
+            };
+
+            voice.bend = 2.0_f64.powf(bend(voice.channel) as f64 / 12.0);
+            let bus = self.inserts.bus(voice.channel, voice.instrument);
+
+            for frame in bus.chunks_exact_mut(2) {
+                let amp = voice.amp_env.next(&inst.amp_env, self.sample_rate);
+                let fade = voice.fade.next(self.sample_rate);
+                let sample = voice.next_sample() * amp * fade * inst.sampler.volume * 0.5;
+
+                frame[0] += sample;
+                frame[1] += sample;
+            }
+        }
+
+        for voice in self.fm_voices.iter_mut() {
+            let Some(Some(inst)) = self.instruments.get(voice.instrument) else {
+                continue;
+            };
+
+            voice.bend = bend(voice.channel);
+            let bus = self.inserts.bus(voice.channel, voice.instrument);
+
+            for frame in bus.chunks_exact_mut(2) {
+                let fade = voice.fade.next(self.sample_rate);
+                let sample = voice.next(&inst.fm, self.sample_rate) * fade * inst.fm.volume * 0.25;
+
+                frame[0] += sample;
+                frame[1] += sample;
+            }
+        }
+
+        for voice in self.gb_voices.iter_mut() {
+            let bus = self.inserts.bus(voice.channel, voice.instrument);
+
+            for frame in bus.chunks_exact_mut(2) {
+                let sample =
+                    voice.next(self.sample_rate) * voice.fade.next(self.sample_rate) * 0.25;
+
+                frame[0] += sample;
+                frame[1] += sample;
+            }
+        }
+
+        self.inserts.process(&self.instruments, &mut self.buses);
+        self.buses.mix(out, self.tempo);
+
+        self.voices
+            .retain(|voice| !voice.amp_env.is_done() && !voice.fade.is_done());
+        self.drums.retain(|drum| !drum.is_done());
+        self.samplers.retain(|voice| !voice.is_done());
+        self.gb_voices.retain(|voice| !voice.is_done());
+
+        let instruments = &self.instruments;
+        // a voice whose instrument is gone can never finish, so it's dropped too.
+        self.fm_voices.retain(|voice| {
+            instruments
+                .get(voice.instrument)
+                .and_then(Option::as_ref)
+                .is_some_and(|inst| !voice.is_done(&inst.fm))
+        });
+    }
+}
+
+/// opens the output in `config`, or the null output if it can't be opened so the song still
+/// plays in time.
+fn open_output(config: &AudioConfig) -> Box<dyn Backend> {
+    match output::open(config) {
+        Ok(backend) => {
+            info!(
+                "{} audio output opened at {} Hz",
+                config.backend.name(),
+                backend.sample_rate()
+            );
+            backend
+        }
+        Err(e) => {
+            error!(
+                "failed to open the {} audio output, the synth will be silent: {e}",
+                config.backend.name()
+            );
+            output::null(config.sample_rate.hz())
+        }
+    }
+}
+
+/// renders audio and hands it to the audio output until the app exits.
+fn audio_thread(
+    rx: Receiver<(u64, SynthCmd)>,
+    config: AudioConfig,
+    clock: AudioClock,
+    meters: Meters,
+    scope: ScopeTap,
+    spectrum: SpectrumTap,
+) {
+    let mut backend = open_output(&config);
+    let mut buf = vec![0.0; config.buffer_size.frames() * 2];
+    // the under-runs of outputs that have since been closed.
+    let mut underruns = 0;
+    clock
+        .sample_rate
+        .store(backend.sample_rate(), Ordering::Relaxed);
+
+    let mut synth = Synth::new(backend.sample_rate() as f32);
+    synth.buses.meters = meters;
+    synth.buses.scope = scope;
+    synth.buses.spectrum = spectrum;
+
+    loop {
+        loop {
+            match rx.try_recv() {
+                Ok((_, SynthCmd::SetOutput(config))) => {
+                    underruns += backend.underruns();
+                    // closed first, as the new output may be the same device.
+                    drop(backend);
+                    backend = open_output(&config);
+                    buf = vec![0.0; config.buffer_size.frames() * 2];
+
+                    let sample_rate = backend.sample_rate();
+
+                    if sample_rate as f32 != synth.sample_rate {
+                        synth = synth.resampled(sample_rate as f32);
+                    }
+
+                    clock.sample_rate.store(sample_rate, Ordering::Relaxed);
+                }
+                Ok((_, SynthCmd::Export { path, cmds, frames })) => {
+                    let mut export = synth.offline();
+                    let buffer_frames = buf.len() / 2;
+
+                    spawn(move || {
+                        let result = export.export(cmds, frames, buffer_frames, &path);
+
+                        match result {
+                            Ok(()) => info!("exported the song to {}", path.display()),
+                            Err(e) => error!("failed to export to {}: {e}", path.display()),
+                        }
+                    });
+                }
+                Ok((at, cmd)) => synth.schedule(at, cmd),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    info!("audio thread exiting");
+                    return;
+                }
+            }
+        }
+
+        synth.render(&mut buf);
+        clock.frames.store(synth.frame, Ordering::Relaxed);
+
+        if let Err(e) = backend.write(&buf) {
+            error!("writing audio failed: {e}");
+        }
+
+        clock
+            .underruns
+            .store(underruns + backend.underruns(), Ordering::Relaxed);
+    }
+}
+
+pub struct SynthPlugin;
+
+impl Plugin for SynthPlugin {
+    fn build(&self, app: &mut App) {
+        debug!("tracker_backend::synth::SynthPlugin loaded");
+
+        let (tx, rx) = unbounded();
+        let config = get_config().audio;
+        let audio_config = config.clone();
+        let clock = AudioClock::default();
+        let audio_clock = clock.clone();
+        let meters = Meters::default();
+        let audio_meters = meters.clone();
+        let scope = ScopeTap::default();
+        let audio_scope = scope.clone();
+        let spectrum = SpectrumTap::default();
+        let audio_spectrum = spectrum.clone();
+        spawn(move || {
+            audio_thread(
+                rx,
+                audio_config,
+                audio_clock,
+                audio_meters,
+                audio_scope,
+                audio_spectrum,
+            )
+        });
+
+        let (load_tx, load_rx) = unbounded();
+        let synth = SynthHandle(tx.clone());
+        spawn(move || sampler::loader_thread(load_rx, synth));
+
+        app.insert_resource(SynthHandle(tx))
+            .insert_resource(clock)
+            .insert_resource(config)
+            .insert_resource(SampleLoader(load_tx))
+            .insert_resource(meters)
+            .insert_resource(scope)
+            .insert_resource(spectrum)
+            .add_event::<export::ExportSong>()
+            .add_systems(Update, sync_instruments)
+            .add_systems(Update, sync_tempo)
+            .add_systems(Update, sync_mixer)
+            .add_systems(Update, sync_wavetables)
+            .add_systems(Update, (sync_output, save_output))
+            .add_systems(Update, sampler::request_samples)
+            .add_systems(Update, play_notes.after(sync_instruments))
+            .add_systems(Update, export::export_song);
+    }
+}
+
+/// keeps the audio threads copy of the instruments up to date. only the instruments that
+/// changed since they were last sent are sent.
+fn sync_instruments(
+    instruments: Res<AllInstruments>,
+    synth: Res<SynthHandle>,
+    mut sent: Local<Instruments>,
+) {
+    if !instruments.is_changed() {
+        return;
+    }
+
+    sent.resize(instruments.0.len(), None);
+
+    for (i, (instrument, sent)) in instruments.0.iter().zip(sent.iter_mut()).enumerate() {
+        if let Some(instrument) = instrument
+            && sent.as_ref() != Some(instrument)
+        {
+            synth.send(SynthCmd::SetInstrument(i, Box::new(instrument.clone())));
+            *sent = Some(instrument.clone());
+        }
+    }
+}
+
+/// reopens the audio output when it is changed on the settings screen.
+fn sync_output(config: Res<AudioConfig>, synth: Res<SynthHandle>) {
+    if config.is_changed() && !config.is_added() {
+        synth.send(SynthCmd::SetOutput(config.clone()));
+    }
+}
+
+/// keeps the audio output picked on the settings screen for the next run.
+fn save_output(config: Res<AudioConfig>) {
+    if !config.is_changed() || config.is_added() {
+        return;
+    }
+
+    let path = AudioConfig::path();
+
+    if let Err(e) = config.save(&path) {
+        error!("failed to save the audio config to {}: {e}", path.display());
+    }
+}
+
+/// keeps the audio threads copy of the wavetables up to date.
+fn sync_wavetables(wavetables: Res<AllWavetables>, synth: Res<SynthHandle>) {
+    if !wavetables.is_changed() {
+        return;
+    }
+
+    for (i, wavetable) in wavetables.0.iter().enumerate() {
+        if let Some(wavetable) = wavetable {
+            synth.send(SynthCmd::SetWavetable(i, wavetable.clone()));
+        }
+    }
+}
+
+/// keeps the audio threads mixer, effects, and dynamics settings up to date.
+fn sync_mixer(song: Res<Song>, synth: Res<SynthHandle>) {
+    if song.is_changed() {
+        synth.send(SynthCmd::SetMixer(song.mixer));
+        synth.send(SynthCmd::SetEffects(song.effects));
+        synth.send(SynthCmd::SetDynamics(song.dynamics));
+    }
+}
+
+/// keeps the audio threads tempo up to date.
+fn sync_tempo(tempo: Res<Tempo>, external: Res<ExternalTempo>, synth: Res<SynthHandle>) {
+    if tempo.is_changed() || external.is_changed() {
+        synth.send(SynthCmd::SetTempo(external.bpm(&tempo)));
+    }
+}
+
+/// passes notes from the sequencer to the synth, to land on the frame they were scheduled for.
+fn play_notes(mut note_events: EventReader<ScheduledNote>, synth: Res<SynthHandle>) {
+    for ScheduledNote { at, event } in note_events.read() {
+        if let Some(cmd) = note_cmd(event.clone()) {
+            synth.send_at(*at, cmd);
+        }
+    }
+}
+
+/// what the synth does for `event`, `None` for events only sent over MIDI.
+fn note_cmd(event: NoteEvent) -> Option<SynthCmd> {
+    let cmd = match event {
+        NoteEvent::NoteOn {
+            channel,
+            instrument,
+            notes,
+            velocity,
+        } => SynthCmd::NoteOn {
+            channel,
+            instrument,
+            notes,
+            velocity,
+        },
+        NoteEvent::NoteOff { channel } => SynthCmd::NoteOff { channel },
+        NoteEvent::Cutoff { channel, cutoff } => SynthCmd::SetCutoff { channel, cutoff },
+        NoteEvent::Volume { channel, volume } => SynthCmd::SetChannelVolume { channel, volume },
+        NoteEvent::Send {
+            channel,
+            bus,
+            amount,
+        } => SynthCmd::SetSend {
+            channel,
+            bus,
+            amount,
+        },
+        // only sent over MIDI.
+        NoteEvent::ControlChange { .. }
+        | NoteEvent::PitchBend { .. }
+        | NoteEvent::Program { .. } => return None,
+    };
+
+    Some(cmd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mod_matrix::{ModDest, ModMatrix, ModSlot};
+
+    /// a synth with a single instrument, a saw wave.
+    fn synth(mode: VoiceMode) -> Synth {
+        let mut synth = Synth::new(SAMPLE_RATE as f32);
+        let mut instrument = Instrument::new(0);
+        instrument.polyphony.mode = mode;
+        synth.handle(SynthCmd::SetInstrument(0, Box::new(instrument)));
+
+        synth
+    }
+
+    fn note_on(notes: Vec<Note>) -> SynthCmd {
+        SynthCmd::NoteOn {
+            channel: 0,
+            instrument: 0,
+            notes,
+            velocity: 1.0,
+        }
+    }
+
+    /// the first frame of a buffer with sound in it, for a note scheduled `delay` frames into
+    /// the second buffer.
+    fn first_sound(delay: u64) -> Option<usize> {
+        let mut synth = synth(VoiceMode::Poly);
+        let mut out = vec![0.0; BUFFER_FRAMES * 2];
+
+        synth.render(&mut out);
+        synth.schedule(BUFFER_FRAMES as u64 + delay, note_on(vec![60]));
+        synth.render(&mut out);
+
+        out.iter().position(|s| *s != 0.0).map(|i| i / 2)
+    }
+
+    #[test]
+    fn notes_land_on_their_frame() {
+        let start = first_sound(0).unwrap();
+
+        assert_eq!(first_sound(100), Some(start + 100));
+        assert_eq!(first_sound(BUFFER_FRAMES as u64), None);
+    }
+
+    #[test]
+    fn legato_notes_dont_restart_the_envelope() {
+        let mut synth = synth(VoiceMode::Legato);
+        let mut out = vec![0.0; BUFFER_FRAMES * 2];
+
+        synth.handle(note_on(vec![60, 64, 67]));
+
+        // past the attack, so a retrigger would be heard.
+        while synth.voices[0].amp_env.stage() == envelope::Stage::Attack {
+            synth.render(&mut out);
+        }
+
+        let stage = synth.voices[0].amp_env.stage();
+        let level = synth.voices[0].amp_env.level();
+        synth.handle(note_on(vec![62]));
+
+        assert_eq!(synth.voices.len(), 1);
+        assert_eq!(synth.voices[0].note, 62);
+        assert_eq!(synth.voices[0].amp_env.stage(), stage);
+        assert_eq!(synth.voices[0].amp_env.level(), level);
+
+        synth.handle(SynthCmd::NoteOff { channel: 0 });
+        synth.handle(note_on(vec![64]));
+
+        // released, so the next note starts a voice of its own in place of the old one, which
+        // fades out rather than stopping dead.
+        assert_eq!(synth.voices.len(), 2);
+        assert!(synth.voices[0].is_stolen());
+        assert_eq!(synth.voices[1].note, 64);
+
+        synth.render(&mut out);
+        assert_eq!(synth.voices.len(), 1);
+    }
+
+    #[test]
+    fn only_changed_instruments_are_sent() {
+        let (tx, rx) = unbounded();
+        let mut world = World::new();
+        world.insert_resource(AllInstruments::default());
+        world.insert_resource(SynthHandle(tx));
+        let mut system = IntoSystem::into_system(sync_instruments);
+        system.initialize(&mut world);
+        let mut sent = |world: &mut World| {
+            system.run((), world);
+            rx.try_iter()
+                .map(|(_, cmd)| match cmd {
+                    SynthCmd::SetInstrument(i, _) => i,
+                    cmd => panic!("sent {cmd:?}"),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(sent(&mut world), [0, 1, 2]);
+
+        let mut instruments = world.resource_mut::<AllInstruments>();
+        instruments.0[1].as_mut().unwrap().filter.cutoff = 40.0;
+        assert_eq!(sent(&mut world), [1]);
+
+        // marked as changed, but the same as was sent.
+        world.resource_mut::<AllInstruments>().set_changed();
+        assert!(sent(&mut world).is_empty());
+    }
+
+    #[test]
+    fn cutoff_commands_carry_on_to_the_next_notes() {
+        let mut synth = synth(VoiceMode::Poly);
+        let cutoff = |cutoff| SynthCmd::SetCutoff { channel: 0, cutoff };
+
+        synth.handle(note_on(vec![60]));
+        synth.handle(cutoff(Some(40.0)));
+        assert_eq!(synth.voices[0].cutoff, Some(40.0));
+
+        synth.handle(note_on(vec![62]));
+        assert_eq!(synth.voices.last().unwrap().cutoff, Some(40.0));
+
+        // once playback stops, notes go back to the cutoff of their instrument.
+        synth.handle(cutoff(None));
+        synth.handle(note_on(vec![64]));
+        assert_eq!(synth.voices.last().unwrap().cutoff, None);
+
+        // only on the channel of the command.
+        synth.handle(cutoff(Some(40.0)));
+        synth.handle(SynthCmd::NoteOn {
+            channel: 1,
+            instrument: 0,
+            notes: vec![60],
+            velocity: 1.0,
+        });
+        assert_eq!(synth.voices.last().unwrap().cutoff, None);
+    }
+
+    fn live_note_on(note: Note) -> SynthCmd {
+        SynthCmd::LiveNoteOn {
+            channel: 0,
+            instrument: 0,
+            note,
+            velocity: 1.0,
+        }
+    }
+
+    #[test]
+    fn live_notes_play_over_the_song() {
+        let mut synth = synth(VoiceMode::Poly);
+
+        synth.handle(live_note_on(60));
+        synth.handle(live_note_on(64));
+        synth.handle(note_on(vec![48]));
+        // the song only releases its own notes.
+        synth.handle(SynthCmd::NoteOff { channel: 0 });
+        synth.handle(SynthCmd::LiveNoteOff {
+            channel: 0,
+            note: 60,
+        });
+
+        let held: Vec<_> = synth
+            .voices
+            .iter()
+            .filter(|voice| voice.amp_env.is_held())
+            .map(|voice| voice.note)
+            .collect();
+        assert_eq!(held, [64]);
+
+        // heard on the mixer channel they were played on.
+        let mut out = vec![0.0; BUFFER_FRAMES * 2];
+        synth.render(&mut out);
+        assert!(out.iter().any(|s| *s != 0.0));
+    }
+
+    #[test]
+    fn velocity_routed_to_volume_changes_what_is_heard() {
+        let mut instrument = Instrument::new(0);
+        instrument.mod_matrix = ModMatrix::default();
+        // full velocity notes are turned all the way down, softer ones less so.
+        instrument.mod_matrix.slots[0] = ModSlot {
+            source: ModSource::Velocity,
+            dest: ModDest::Volume,
+            amount: -1.0,
+        };
+        let peak = |instrument: &Instrument, velocity| {
+            let mut synth = Synth::new(SAMPLE_RATE as f32);
+            synth.handle(SynthCmd::SetInstrument(0, Box::new(instrument.clone())));
+            synth.handle(SynthCmd::NoteOn {
+                channel: 0,
+                instrument: 0,
+                notes: vec![60],
+                velocity,
+            });
+
+            let mut out = vec![0.0; BUFFER_FRAMES * 8];
+            synth.render(&mut out);
+            out.iter().fold(0.0_f32, |peak, s| peak.max(s.abs()))
+        };
+
+        assert_eq!(peak(&instrument, 1.0), 0.0);
+        assert!(peak(&instrument, 0.5) > 0.0);
+        assert!(peak(&Instrument::new(0), 1.0) > 0.0);
+    }
+
+    #[test]
+    fn legato_live_notes_glide() {
+        let mut synth = synth(VoiceMode::Legato);
+
+        synth.handle(live_note_on(60));
+        synth.handle(live_note_on(62));
+        // the first note was glided away from, so there is nothing to release.
+        synth.handle(SynthCmd::LiveNoteOff {
+            channel: 0,
+            note: 60,
+        });
+
+        assert_eq!(synth.voices.len(), 1);
+        assert_eq!(synth.voices[0].note, 62);
+        assert!(synth.voices[0].amp_env.is_held());
+    }
+}
@@ -0,0 +1,251 @@
+use crate::params::{Choice, Param, Range};
+use pyo3::pyclass;
+use serde::{Deserialize, Serialize};
+
+/// the number of routings in an instruments modulation matrix.
+pub const N_SLOTS: usize = 4;
+
+/// the names of each slots source, destination, and amount parameters.
+const SLOT_NAMES: [[&str; 3]; N_SLOTS] = [
+    ["MOD1 SRC", "MOD1 DEST", "MOD1 AMT"],
+    ["MOD2 SRC", "MOD2 DEST", "MOD2 AMT"],
+    ["MOD3 SRC", "MOD3 DEST", "MOD3 AMT"],
+    ["MOD4 SRC", "MOD4 DEST", "MOD4 AMT"],
+];
+
+/// where a modulation comes from.
+#[pyclass(module = "tracker_backend", eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum ModSource {
+    /// the slot is not used.
+    #[default]
+    Off,
+    Lfo1,
+    Lfo2,
+    /// the modulation envelope.
+    ModEnv,
+    /// the amplitude envelope.
+    AmpEnv,
+    /// the volume the note was played at.
+    Velocity,
+    /// the note, -1.0 five octaves below middle C and 1.0 five octaves above.
+    Note,
+    /// the mod wheel of the MIDI input, for live notes.
+    ModWheel,
+}
+
+impl Choice for ModSource {
+    const ALL: &'static [Self] = &[
+        Self::Off,
+        Self::Lfo1,
+        Self::Lfo2,
+        Self::ModEnv,
+        Self::AmpEnv,
+        Self::Velocity,
+        Self::Note,
+        Self::ModWheel,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Off => "OFF",
+            Self::Lfo1 => "LFO 1",
+            Self::Lfo2 => "LFO 2",
+            Self::ModEnv => "MOD ENV",
+            Self::AmpEnv => "AMP ENV",
+            Self::Velocity => "VELOCITY",
+            Self::Note => "NOTE",
+            Self::ModWheel => "MOD WHEEL",
+        }
+    }
+}
+
+/// what a modulation changes.
+#[pyclass(module = "tracker_backend", eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum ModDest {
+    #[default]
+    Pitch,
+    Cutoff,
+    Volume,
+    Pan,
+    PulseWidth,
+    /// the position in the wavetable.
+    WavePos,
+}
+
+impl Choice for ModDest {
+    const ALL: &'static [Self] = &[
+        Self::Pitch,
+        Self::Cutoff,
+        Self::Volume,
+        Self::Pan,
+        Self::PulseWidth,
+        Self::WavePos,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Pitch => "PITCH",
+            Self::Cutoff => "CUTOFF",
+            Self::Volume => "VOLUME",
+            Self::Pan => "PAN",
+            Self::PulseWidth => "PULSE W",
+            Self::WavePos => "WAVE POS",
+        }
+    }
+}
+
+/// routes one source to one destination.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModSlot {
+    pub source: ModSource,
+    pub dest: ModDest,
+    /// from -1.0 to 1.0.
+    pub amount: f32,
+}
+
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModMatrix {
+    pub slots: [ModSlot; N_SLOTS],
+}
+
+impl ModMatrix {
+    pub fn params(&mut self) -> Vec<Param<'_>> {
+        self.slots
+            .iter_mut()
+            .zip(SLOT_NAMES)
+            .flat_map(|(slot, names)| {
+                [
+                    Param::choice(names[0], &mut slot.source),
+                    Param::choice(names[1], &mut slot.dest),
+                    Param::new(names[2], &mut slot.amount, Range::new(-1.0, 1.0, 0.05)),
+                ]
+            })
+            .collect()
+    }
+
+    /// sums the modulation going to each destination. `source` gives the current value of each
+    /// source.
+    pub fn apply(&self, source: impl Fn(ModSource) -> f32) -> Modulation {
+        let mut modulation = Modulation::default();
+
+        for slot in self
+            .slots
+            .iter()
+            .filter(|slot| slot.source != ModSource::Off)
+        {
+            let value = source(slot.source) * slot.amount;
+
+            match slot.dest {
+                ModDest::Pitch => modulation.pitch += value,
+                ModDest::Cutoff => modulation.cutoff += value,
+                ModDest::Volume => modulation.volume += value,
+                ModDest::Pan => modulation.pan += value,
+                ModDest::PulseWidth => modulation.pulse_width += value,
+                ModDest::WavePos => modulation.wave_pos += value,
+            }
+        }
+
+        modulation
+    }
+}
+
+/// the total modulation of each destination, before it is scaled to the destinations range.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Modulation {
+    pub pitch: f32,
+    pub cutoff: f32,
+    pub volume: f32,
+    pub pan: f32,
+    pub pulse_width: f32,
+    pub wave_pos: f32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slot(source: ModSource, dest: ModDest, amount: f32) -> ModSlot {
+        ModSlot {
+            source,
+            dest,
+            amount,
+        }
+    }
+
+    /// every source at 0.5, the velocity at 1.0.
+    fn sources(source: ModSource) -> f32 {
+        match source {
+            ModSource::Velocity => 1.0,
+            _ => 0.5,
+        }
+    }
+
+    #[test]
+    fn slots_that_are_off_do_nothing() {
+        let matrix = ModMatrix {
+            slots: [
+                slot(ModSource::Off, ModDest::Pitch, 1.0),
+                slot(ModSource::Off, ModDest::Cutoff, -1.0),
+                ModSlot::default(),
+                ModSlot::default(),
+            ],
+        };
+
+        assert_eq!(
+            matrix.apply(|source| {
+                assert_ne!(source, ModSource::Off);
+                1.0
+            }),
+            Modulation::default()
+        );
+    }
+
+    #[test]
+    fn slots_to_the_same_destination_add_up() {
+        let matrix = ModMatrix {
+            slots: [
+                slot(ModSource::Lfo1, ModDest::Cutoff, 1.0),
+                slot(ModSource::Velocity, ModDest::Cutoff, 0.25),
+                slot(ModSource::ModEnv, ModDest::Pan, 0.5),
+                slot(ModSource::Off, ModDest::Cutoff, 1.0),
+            ],
+        };
+
+        assert_eq!(
+            matrix.apply(sources),
+            Modulation {
+                cutoff: 0.75,
+                pan: 0.25,
+                ..Modulation::default()
+            }
+        );
+    }
+
+    #[test]
+    fn negative_amounts_turn_the_source_upside_down() {
+        let matrix = ModMatrix {
+            slots: [
+                slot(ModSource::Lfo2, ModDest::Pitch, -1.0),
+                slot(ModSource::Velocity, ModDest::Volume, -0.5),
+                // cancels out the first slot.
+                slot(ModSource::Lfo2, ModDest::Pitch, 1.0),
+                slot(ModSource::Note, ModDest::WavePos, -0.5),
+            ],
+        };
+
+        assert_eq!(
+            matrix.apply(sources),
+            Modulation {
+                volume: -0.5,
+                wave_pos: -0.25,
+                ..Modulation::default()
+            }
+        );
+    }
+}
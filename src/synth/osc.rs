@@ -0,0 +1,122 @@
+use super::wavetable::Wavetable;
+use crate::params::Choice;
+use pyo3::pyclass;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+/// the wave shape of an oscillator.
+#[pyclass(module = "tracker_backend", eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum OscType {
+    #[default]
+    Saw,
+    /// a pulse wave, its width is set by the instruments pulse width. (0.5 is a square wave)
+    Pulse,
+    Triangle,
+    Sine,
+    Noise,
+    /// plays the instruments wavetable, see `Oscillator::next_wavetable`.
+    Wavetable,
+}
+
+impl Choice for OscType {
+    const ALL: &'static [Self] = &[
+        Self::Saw,
+        Self::Pulse,
+        Self::Triangle,
+        Self::Sine,
+        Self::Noise,
+        Self::Wavetable,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Saw => "SAW",
+            Self::Pulse => "PULSE",
+            Self::Triangle => "TRI",
+            Self::Sine => "SINE",
+            Self::Noise => "NOISE",
+            Self::Wavetable => "WAVE",
+        }
+    }
+}
+
+/// smooths out the discontinuity of a wave at phase 0.0 to reduce aliasing.
+fn poly_blep(t: f32, dt: f32) -> f32 {
+    if t < dt {
+        let t = t / dt;
+
+        t + t - t * t - 1.0
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt;
+
+        t * t + t + t + 1.0
+    } else {
+        0.0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Oscillator {
+    /// where in the wave cycle the oscillator is, from 0.0 to 1.0.
+    phase: f32,
+    /// state of the noise generator.
+    seed: u32,
+}
+
+impl Default for Oscillator {
+    fn default() -> Self {
+        Self {
+            phase: 0.0,
+            seed: 0x1234_5678,
+        }
+    }
+}
+
+impl Oscillator {
+    /// generates the next sample of the wave, between -1.0 and 1.0.
+    pub fn next(&mut self, osc: OscType, freq: f32, pulse_width: f32, sample_rate: f32) -> f32 {
+        let dt = (freq / sample_rate).clamp(0.0, 0.5);
+        let t = self.phase;
+
+        let sample = match osc {
+            OscType::Saw => 2.0 * t - 1.0 - poly_blep(t, dt),
+            OscType::Pulse => {
+                let width = pulse_width.clamp(0.01, 0.99);
+                let naive = if t < width { 1.0 } else { -1.0 };
+
+                naive + poly_blep(t, dt) - poly_blep((t + 1.0 - width) % 1.0, dt)
+            }
+            OscType::Triangle => 1.0 - 4.0 * (t - 0.5).abs(),
+            OscType::Sine => (t * TAU).sin(),
+            OscType::Noise => {
+                // xorshift
+                self.seed ^= self.seed << 13;
+                self.seed ^= self.seed >> 17;
+                self.seed ^= self.seed << 5;
+
+                (self.seed as f32 / u32::MAX as f32) * 2.0 - 1.0
+            }
+            // wavetables need a table to play, this is silent.
+            OscType::Wavetable => 0.0,
+        };
+
+        self.phase = (self.phase + dt) % 1.0;
+
+        sample
+    }
+
+    /// generates the next sample of a wavetable, `position` picks the frame (0.0 to 1.0).
+    pub fn next_wavetable(
+        &mut self,
+        table: &Wavetable,
+        position: f32,
+        freq: f32,
+        sample_rate: f32,
+    ) -> f32 {
+        let sample = table.sample(self.phase, position);
+        self.phase = (self.phase + (freq / sample_rate).clamp(0.0, 0.5)) % 1.0;
+
+        sample
+    }
+}
@@ -0,0 +1,339 @@
+use crate::{
+    config::audio::{AudioBackend, AudioConfig, AudioDevice},
+    params::{Range, Value},
+};
+use alsa::{
+    device_name::HintIter,
+    pcm::{Access, Format, HwParams, PCM},
+    Direction, ValueOr,
+};
+use anyhow::Result;
+use hound::{SampleFormat, WavSpec, WavWriter};
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    path::Path,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+/// somewhere rendered audio goes. written to by the audio thread, which it holds up till the
+/// samples are taken, so the synth runs in time with it.
+pub trait Backend {
+    /// the rate the backend actually runs at, which may not be the one asked for.
+    fn sample_rate(&self) -> u32;
+
+    /// writes interleaved stereo samples.
+    fn write(&mut self, samples: &[f32]) -> Result<()>;
+
+    /// the times the backend has run dry since it was opened.
+    fn underruns(&self) -> u64;
+}
+
+/// opens the backend picked in `config`.
+pub fn open(config: &AudioConfig) -> Result<Box<dyn Backend>> {
+    let sample_rate = config.sample_rate.hz();
+    let buffer_frames = config.buffer_size.frames();
+
+    Ok(match config.backend {
+        AudioBackend::Alsa => {
+            Box::new(Alsa::open(&config.device.name, sample_rate, buffer_frames)?)
+        }
+        AudioBackend::Null => null(sample_rate),
+        AudioBackend::Wav => Box::new(Wav {
+            writer: wav_writer(&config.wav_path, sample_rate)?,
+            pacer: Pacer::new(sample_rate),
+            unflushed: 0,
+        }),
+    })
+}
+
+/// a backend that throws the audio away, which can't fail to open.
+pub fn null(sample_rate: u32) -> Box<dyn Backend> {
+    Box::new(Null {
+        pacer: Pacer::new(sample_rate),
+    })
+}
+
+/// a 16 bit stereo WAV file, written with `to_i16`.
+pub fn wav_writer(path: &Path, sample_rate: u32) -> Result<WavWriter<BufWriter<File>>> {
+    Ok(WavWriter::create(
+        path,
+        WavSpec {
+            channels: 2,
+            sample_rate,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        },
+    )?)
+}
+
+pub fn to_i16(sample: f32) -> i16 {
+    (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16
+}
+
+/// an ALSA playback device, for interleaved stereo.
+struct Alsa {
+    pcm: PCM,
+    sample_rate: u32,
+    underruns: u64,
+    /// the samples converted for the device, kept so the audio thread doesn't allocate a buffer
+    /// each write. only grows when the buffer size does.
+    buf: Vec<i16>,
+}
+
+impl Alsa {
+    fn open(device: &str, sample_rate: u32, buffer_frames: usize) -> Result<Self> {
+        let pcm = PCM::new(device, Direction::Playback, false)?;
+
+        let rate = {
+            let hwp = HwParams::any(&pcm)?;
+            hwp.set_channels(2)?;
+            hwp.set_rate(sample_rate, ValueOr::Nearest)?;
+            hwp.set_format(Format::s16())?;
+            hwp.set_access(Access::RWInterleaved)?;
+            hwp.set_period_size_near(buffer_frames as _, ValueOr::Nearest)?;
+            hwp.set_buffer_size_near((buffer_frames * 4) as _)?;
+            pcm.hw_params(&hwp)?;
+
+            hwp.get_rate()?
+        };
+
+        pcm.prepare()?;
+
+        Ok(Self {
+            pcm,
+            sample_rate: rate,
+            underruns: 0,
+            buf: Vec::with_capacity(buffer_frames * 2),
+        })
+    }
+}
+
+impl Backend for Alsa {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// writes every frame, carrying on from where a short write stopped. recovers from under-runs,
+    /// counting them, and writes again what they kept from being written.
+    fn write(&mut self, samples: &[f32]) -> Result<()> {
+        self.buf.resize(samples.len(), 0);
+
+        for (out, sample) in self.buf.iter_mut().zip(samples) {
+            *out = to_i16(*sample);
+        }
+
+        let io = self.pcm.io_i16()?;
+        let mut written = 0;
+
+        while written < self.buf.len() {
+            match io.writei(&self.buf[written..]) {
+                Ok(frames) => written += frames * 2,
+                Err(e) => {
+                    if is_underrun(&e) {
+                        self.underruns += 1;
+                    }
+
+                    // fails if it can't be recovered from, like the device going away.
+                    self.pcm.try_recover(e, true)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn underruns(&self) -> u64 {
+        self.underruns
+    }
+}
+
+/// true if `e` is ALSA saying the device ran dry, rather than it being suspended or some other
+/// error.
+fn is_underrun(e: &alsa::Error) -> bool {
+    io::Error::from_raw_os_error(e.errno()).kind() == io::ErrorKind::BrokenPipe
+}
+
+/// holds the audio thread up so samples are taken at `sample_rate`, like a sound card would.
+struct Pacer {
+    sample_rate: u32,
+    start: Instant,
+    frames: u64,
+}
+
+impl Pacer {
+    fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            start: Instant::now(),
+            frames: 0,
+        }
+    }
+
+    fn wait(&mut self, frames: usize) {
+        self.frames += frames as u64;
+
+        let due =
+            self.start + Duration::from_secs_f64(self.frames as f64 / self.sample_rate as f64);
+        let now = Instant::now();
+
+        match due.checked_duration_since(now) {
+            Some(ahead) => sleep(ahead),
+            // fallen behind, start again from now rather than rushing to catch up.
+            None => {
+                self.start = now;
+                self.frames = 0;
+            }
+        }
+    }
+}
+
+/// throws the audio away. for running without a sound card.
+struct Null {
+    pacer: Pacer,
+}
+
+impl Backend for Null {
+    fn sample_rate(&self) -> u32 {
+        self.pacer.sample_rate
+    }
+
+    fn write(&mut self, samples: &[f32]) -> Result<()> {
+        self.pacer.wait(samples.len() / 2);
+
+        Ok(())
+    }
+
+    fn underruns(&self) -> u64 {
+        0
+    }
+}
+
+/// writes the audio to a 16 bit stereo WAV file.
+struct Wav {
+    writer: WavWriter<BufWriter<File>>,
+    pacer: Pacer,
+    /// frames written since the header was last brought up to date.
+    unflushed: u32,
+}
+
+impl Backend for Wav {
+    fn sample_rate(&self) -> u32 {
+        self.pacer.sample_rate
+    }
+
+    fn write(&mut self, samples: &[f32]) -> Result<()> {
+        for sample in samples {
+            self.writer.write_sample(to_i16(*sample))?;
+        }
+
+        // keeps the file playable, even if the app is killed before the writer is dropped.
+        self.unflushed += (samples.len() / 2) as u32;
+
+        if self.unflushed >= self.pacer.sample_rate {
+            self.writer.flush()?;
+            self.unflushed = 0;
+        }
+
+        self.pacer.wait(samples.len() / 2);
+
+        Ok(())
+    }
+
+    fn underruns(&self) -> u64 {
+        0
+    }
+}
+
+/// the names of the ALSA devices that can play audio.
+fn devices() -> Vec<String> {
+    let Ok(hints) = HintIter::new_str(None, "pcm") else {
+        return Vec::new();
+    };
+
+    hints
+        .filter(|hint| hint.direction.is_none_or(|dir| dir == Direction::Playback))
+        .filter_map(|hint| hint.name)
+        .collect()
+}
+
+impl Value for AudioDevice {
+    fn shift(&mut self, delta: i32, _range: &Range) {
+        let mut devices = devices();
+
+        if !devices.contains(&self.name) {
+            devices.insert(0, self.name.clone());
+        }
+
+        let i = devices
+            .iter()
+            .position(|name| *name == self.name)
+            .unwrap_or(0) as i32;
+        self.name = devices[(i + delta.signum()).rem_euclid(devices.len() as i32) as usize].clone();
+    }
+
+    fn display(&self, _range: &Range) -> String {
+        self.name.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::audio::SampleRate, synth::wav::read_mono};
+
+    #[test]
+    fn wav_sink_writes_what_it_is_given() {
+        let path = std::env::temp_dir().join(format!("wav-sink-{}.wav", std::process::id()));
+        let config = AudioConfig {
+            backend: AudioBackend::Wav,
+            sample_rate: SampleRate::Hz44100,
+            wav_path: path.clone(),
+            ..AudioConfig::default()
+        };
+
+        {
+            let mut backend = open(&config).unwrap();
+            assert_eq!(backend.sample_rate(), 44_100);
+            backend.write(&[0.5, 0.5, -0.5, -0.5, 2.0, 2.0]).unwrap();
+        }
+
+        let (samples, rate) = read_mono(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(rate, 44_100);
+        assert_eq!(samples.len(), 3);
+        assert!((samples[0] - 0.5).abs() < 0.001);
+        assert!((samples[1] + 0.5).abs() < 0.001);
+        // clipped to full scale.
+        assert!((samples[2] - 1.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn only_a_broken_pipe_is_an_underrun() {
+        let error = |errno| alsa::Error::new("snd_pcm_writei", errno);
+
+        // EPIPE, ESTRPIPE, and EAGAIN.
+        assert!(is_underrun(&error(32)));
+        assert!(!is_underrun(&error(86)));
+        assert!(!is_underrun(&error(11)));
+    }
+
+    #[test]
+    fn null_sink_keeps_time() {
+        let config = AudioConfig {
+            backend: AudioBackend::Null,
+            ..AudioConfig::default()
+        };
+        let mut backend = open(&config).unwrap();
+        let start = Instant::now();
+
+        // a tenth of a second.
+        for _ in 0..10 {
+            backend.write(&[0.0; 960]).unwrap();
+        }
+
+        assert!(start.elapsed() >= Duration::from_millis(95));
+    }
+}
@@ -0,0 +1,509 @@
+use super::{
+    envelope::Envelope,
+    voices::{Allocated, StealFade},
+    wav, SynthCmd, SynthHandle,
+};
+use crate::{
+    params::{Choice, Param, Range, Value},
+    pygame_coms::{Index, InstrumentOutput, Note},
+    tracker_state::AllInstruments,
+};
+use anyhow::Result;
+use bevy::{log::*, prelude::*};
+use crossbeam::channel::{Receiver, Sender};
+use pyo3::pyclass;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// how the notes played by a sampler instrument pick what it plays.
+#[pyclass(module = "tracker_backend", eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum SamplerMode {
+    /// the sample is pitched by the note, it plays at its own pitch on the root note.
+    #[default]
+    Pitched,
+    /// every note from the root note up plays the next WAV file in the samples folder.
+    Kit,
+    /// the sample is cut into equal slices, every note from the root note up plays the next one.
+    Slice,
+}
+
+impl Choice for SamplerMode {
+    const ALL: &'static [Self] = &[Self::Pitched, Self::Kit, Self::Slice];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Pitched => "PITCHED",
+            Self::Kit => "KIT",
+            Self::Slice => "SLICE",
+        }
+    }
+}
+
+/// parameters of a sampler instrument. the start and loop points are fractions of the sample
+/// (or of the slice) measured in the direction it plays, so they still make sense reversed.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SamplerParams {
+    /// the WAV file played, in kit mode its folder is the kit. only the path is saved, the
+    /// audio is loaded from it.
+    pub sample: Option<PathBuf>,
+    pub mode: SamplerMode,
+    /// the note the sample plays at its own pitch, or the first note of a kit or slices.
+    pub root: Note,
+    /// how many slices the sample is cut into in slice mode.
+    pub slices: u8,
+    pub start: f32,
+    pub reverse: bool,
+    pub looped: bool,
+    pub loop_start: f32,
+    pub loop_end: f32,
+    pub volume: f32,
+}
+
+impl Default for SamplerParams {
+    fn default() -> Self {
+        Self {
+            sample: None,
+            mode: SamplerMode::default(),
+            root: 60,
+            slices: 8,
+            start: 0.0,
+            reverse: false,
+            looped: false,
+            loop_start: 0.0,
+            loop_end: 1.0,
+            volume: 0.8,
+        }
+    }
+}
+
+impl SamplerParams {
+    pub fn params(&mut self) -> Vec<Param<'_>> {
+        let fraction = Range::new(0.0, 1.0, 0.01);
+
+        vec![
+            Param::choice("SAMPLE", &mut self.sample),
+            Param::choice("MODE", &mut self.mode),
+            Param::new("ROOT", &mut self.root, Range::new(0.0, 127.0, 1.0)),
+            Param::new("SLICES", &mut self.slices, Range::new(1.0, 64.0, 1.0)),
+            Param::new("START", &mut self.start, fraction),
+            Param::choice("REVERSE", &mut self.reverse),
+            Param::choice("LOOP", &mut self.looped),
+            Param::new("LOOP START", &mut self.loop_start, fraction),
+            Param::new("LOOP END", &mut self.loop_end, fraction),
+            Param::new("VOLUME", &mut self.volume, Range::new(0.0, 1.0, 0.05)),
+        ]
+    }
+
+    /// what has to be loaded before the instrument can play.
+    pub fn load_request(&self) -> Option<LoadRequest> {
+        let sample = self.sample.clone()?;
+
+        match self.mode {
+            SamplerMode::Kit => sample
+                .parent()
+                .map(|dir| LoadRequest::Kit(dir.to_path_buf())),
+            _ => Some(LoadRequest::Sample(sample)),
+        }
+    }
+}
+
+/// a sample is picked by scrolling through every WAV file in the samples folder.
+impl Value for Option<PathBuf> {
+    fn shift(&mut self, delta: i32, _range: &Range) {
+        let library = library();
+
+        if library.is_empty() {
+            return;
+        }
+
+        let i = match self
+            .as_ref()
+            .and_then(|path| library.iter().position(|option| option == path))
+        {
+            Some(i) => (i as i32 + delta.signum()).rem_euclid(library.len() as i32) as usize,
+            None => 0,
+        };
+
+        *self = Some(library[i].clone());
+    }
+
+    fn display(&self, _range: &Range) -> String {
+        let Some(path) = self else {
+            return "---".to_string();
+        };
+
+        let path = path.with_extension("");
+        let dir = sample_dir();
+
+        path.strip_prefix(&dir)
+            .unwrap_or(&path)
+            .display()
+            .to_string()
+    }
+}
+
+/// the folder samples are loaded from.
+pub fn sample_dir() -> PathBuf {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join(".local/share/midi-tracker/samples")
+}
+
+/// every WAV file in the samples folder and the folders directly inside of it.
+fn library() -> Vec<PathBuf> {
+    let dir = sample_dir();
+    let mut library = wav::list(&dir).unwrap_or_default();
+
+    if let Ok(entries) = dir.read_dir() {
+        let mut kits: Vec<_> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_dir())
+            .collect();
+        kits.sort();
+
+        for kit in kits {
+            library.append(&mut wav::list(&kit).unwrap_or_default());
+        }
+    }
+
+    library
+}
+
+/// a mono sample loaded from a WAV file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub sample_rate: f32,
+    pub data: Vec<f32>,
+}
+
+impl Sample {
+    pub fn load(path: &Path) -> Result<Self> {
+        let (data, sample_rate) = wav::read_mono(path)?;
+
+        Ok(Self {
+            sample_rate: sample_rate as f32,
+            data,
+        })
+    }
+}
+
+/// something for the loader thread to load.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LoadRequest {
+    /// a single WAV file.
+    Sample(PathBuf),
+    /// every WAV file in a folder.
+    Kit(PathBuf),
+}
+
+/// used to ask the loader thread to load samples.
+#[derive(Debug, Clone, Resource)]
+pub struct SampleLoader(pub Sender<LoadRequest>);
+
+/// loads samples from disk so the main loop and the audio thread never wait on it. loaded
+/// samples are sent straight to the audio thread.
+pub fn loader_thread(rx: Receiver<LoadRequest>, synth: SynthHandle) {
+    let mut loaded = HashSet::new();
+
+    let mut load = |path: &Path| {
+        if loaded.contains(path) {
+            return true;
+        }
+
+        match Sample::load(path) {
+            Ok(sample) => {
+                info!("loaded sample {}", path.display());
+                loaded.insert(path.to_path_buf());
+                synth.send(SynthCmd::SetSample(path.to_path_buf(), Arc::new(sample)));
+
+                true
+            }
+            Err(e) => {
+                error!("failed to load sample {}: {e}", path.display());
+
+                false
+            }
+        }
+    };
+
+    for request in rx.iter() {
+        match request {
+            LoadRequest::Sample(path) => {
+                load(&path);
+            }
+            LoadRequest::Kit(dir) => match wav::list(&dir) {
+                Ok(paths) => {
+                    let kit = paths.into_iter().filter(|path| load(path)).collect();
+                    synth.send(SynthCmd::SetKit(dir, kit));
+                }
+                Err(e) => error!("failed to read kit {}: {e}", dir.display()),
+            },
+        }
+    }
+}
+
+/// asks the loader thread for the samples the instruments use.
+pub fn request_samples(
+    instruments: Res<AllInstruments>,
+    loader: Res<SampleLoader>,
+    mut requested: Local<HashSet<LoadRequest>>,
+) {
+    if !instruments.is_changed() {
+        return;
+    }
+
+    let requests = instruments
+        .0
+        .iter()
+        .flatten()
+        .filter(|instrument| instrument.output == InstrumentOutput::Sampler)
+        .filter_map(|instrument| instrument.sampler.load_request());
+
+    for request in requests {
+        if requested.insert(request.clone())
+            && let Err(e) = loader.0.send(request)
+        {
+            error!("failed to send a request to the sample loader: {e}");
+        }
+    }
+}
+
+/// a single note played by a sampler instrument.
+#[derive(Debug, Clone)]
+pub struct SamplerVoice {
+    pub channel: usize,
+    pub instrument: Index,
+    pub amp_env: Envelope,
+    note: Note,
+    /// false if the note picks a slice or kit sample rather than a pitch.
+    pitched: bool,
+    sample: Arc<Sample>,
+    /// the first and one past the last frame played.
+    region: (usize, usize),
+    reverse: bool,
+    /// frames into the region, in the direction it plays.
+    pos: f64,
+    /// how far `pos` moves each output sample.
+    step: f64,
+    /// multiplies `step`, set by pitch bend.
+    pub bend: f64,
+    /// where playback jumps back to, and when, if the sample loops.
+    looping: Option<(f64, f64)>,
+    velocity: f32,
+    finished: bool,
+    pub fade: StealFade,
+}
+
+impl SamplerVoice {
+    /// `None` if the note is outside the slices of the sample.
+    pub fn new(
+        channel: usize,
+        instrument: Index,
+        params: &SamplerParams,
+        sample: Arc<Sample>,
+        note: Note,
+        velocity: f32,
+        sample_rate: f32,
+    ) -> Option<Self> {
+        let len = sample.data.len();
+        let offset = note as i32 - params.root as i32;
+
+        let (region, semitones) = match params.mode {
+            SamplerMode::Pitched => ((0, len), offset),
+            SamplerMode::Kit => ((0, len), 0),
+            SamplerMode::Slice => {
+                let slices = params.slices.max(1) as usize;
+                let slice = usize::try_from(offset).ok().filter(|&i| i < slices)?;
+
+                ((len * slice / slices, len * (slice + 1) / slices), 0)
+            }
+        };
+
+        let region_len = (region.1 - region.0) as f64;
+        let ratio = 2.0_f64.powf(semitones as f64 / 12.0);
+        let loop_start = params.loop_start.min(params.loop_end) as f64 * region_len;
+        let loop_end = params.loop_start.max(params.loop_end) as f64 * region_len;
+
+        let mut amp_env = Envelope::default();
+        amp_env.gate_on();
+
+        Some(Self {
+            channel,
+            instrument,
+            amp_env,
+            note,
+            pitched: params.mode == SamplerMode::Pitched,
+            region,
+            reverse: params.reverse,
+            pos: params.start as f64 * region_len,
+            step: ratio * sample.sample_rate as f64 / sample_rate as f64,
+            bend: 1.0,
+            looping: (params.looped && loop_end - loop_start >= 1.0)
+                .then_some((loop_start, loop_end)),
+            sample,
+            velocity,
+            finished: region_len == 0.0,
+            fade: StealFade::default(),
+        })
+    }
+
+    /// true once the sample has played to its end, the release has finished, or the voice was
+    /// stolen and has faded out.
+    pub fn is_done(&self) -> bool {
+        self.finished || self.amp_env.is_done() || self.fade.is_done()
+    }
+
+    /// the sample `i` frames into the region, in the direction it plays.
+    fn frame(&self, i: usize) -> f32 {
+        let (start, end) = self.region;
+        let i = i.min(end - start - 1);
+
+        if self.reverse {
+            self.sample.data[end - 1 - i]
+        } else {
+            self.sample.data[start + i]
+        }
+    }
+
+    /// the next sample, before the amp envelope and velocity are applied.
+    pub fn next_sample(&mut self) -> f32 {
+        if self.finished {
+            return 0.0;
+        }
+
+        let i = self.pos as usize;
+        let a = self.frame(i);
+        let b = self.frame(i + 1);
+        let sample = a + (b - a) * (self.pos - i as f64) as f32;
+
+        self.pos += self.step * self.bend;
+
+        match self.looping {
+            Some((start, end)) if self.pos >= end => {
+                self.pos = start + (self.pos - end) % (end - start)
+            }
+            _ => self.finished = self.pos >= (self.region.1 - self.region.0) as f64,
+        }
+
+        sample * self.velocity
+    }
+}
+
+impl Allocated for SamplerVoice {
+    fn channel(&self) -> usize {
+        self.channel
+    }
+
+    fn instrument(&self) -> Index {
+        self.instrument
+    }
+
+    fn note(&self) -> Note {
+        self.note
+    }
+
+    fn level(&self) -> f32 {
+        self.amp_env.level() * self.velocity
+    }
+
+    fn is_held(&self) -> bool {
+        self.amp_env.is_held()
+    }
+
+    /// only pitched samples can slide, in the other modes a note picks a different sound.
+    fn set_note(&mut self, note: Note) -> bool {
+        if !self.pitched {
+            return false;
+        }
+
+        self.step *= 2.0_f64.powf((note as f64 - self.note as f64) / 12.0);
+        self.note = note;
+
+        true
+    }
+
+    fn steal(&mut self) {
+        self.fade.steal();
+    }
+
+    fn is_stolen(&self) -> bool {
+        self.fade.is_stolen()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ramp(len: usize) -> Arc<Sample> {
+        Arc::new(Sample {
+            sample_rate: 1000.0,
+            data: (0..len).map(|i| i as f32).collect(),
+        })
+    }
+
+    fn play(params: &SamplerParams, note: Note, n: usize) -> Vec<f32> {
+        let mut voice = SamplerVoice::new(0, 0, params, ramp(16), note, 1.0, 1000.0).unwrap();
+
+        (0..n).map(|_| voice.next_sample()).collect()
+    }
+
+    #[test]
+    fn pitched_by_note() {
+        let params = SamplerParams::default();
+
+        assert_eq!(play(&params, 60, 4), [0.0, 1.0, 2.0, 3.0]);
+        assert_eq!(play(&params, 72, 4), [0.0, 2.0, 4.0, 6.0]);
+        assert_eq!(play(&params, 48, 4), [0.0, 0.5, 1.0, 1.5]);
+    }
+
+    #[test]
+    fn reversed_with_offset() {
+        let params = SamplerParams {
+            reverse: true,
+            start: 0.25,
+            ..SamplerParams::default()
+        };
+
+        assert_eq!(play(&params, 60, 3), [11.0, 10.0, 9.0]);
+    }
+
+    #[test]
+    fn loops() {
+        let params = SamplerParams {
+            looped: true,
+            loop_start: 0.25,
+            loop_end: 0.5,
+            ..SamplerParams::default()
+        };
+
+        let out = play(&params, 60, 20);
+        assert_eq!(
+            &out[..10],
+            [0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 4.0, 5.0]
+        );
+        assert!(out.iter().all(|&s| s < 8.0));
+    }
+
+    #[test]
+    fn slices_and_end() {
+        let params = SamplerParams {
+            mode: SamplerMode::Slice,
+            slices: 4,
+            ..SamplerParams::default()
+        };
+
+        assert_eq!(play(&params, 62, 4), [8.0, 9.0, 10.0, 11.0]);
+        assert_eq!(play(&params, 63, 6), [12.0, 13.0, 14.0, 15.0, 0.0, 0.0]);
+        assert!(SamplerVoice::new(0, 0, &params, ramp(16), 64, 1.0, 1000.0).is_none());
+        assert!(SamplerVoice::new(0, 0, &params, ramp(16), 59, 1.0, 1000.0).is_none());
+    }
+}
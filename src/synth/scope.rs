@@ -0,0 +1,97 @@
+use super::mixer::N_METERS;
+use crate::sequencer::N_CHANNELS;
+use bevy::prelude::Resource;
+use pyo3::pyclass;
+use std::sync::{
+    atomic::{AtomicU32, AtomicUsize, Ordering},
+    Arc,
+};
+
+/// the number of points in each waveform sent to the frontend.
+pub const SCOPE_POINTS: usize = 128;
+/// one frame in this many is kept for the scope. at 48 kHz a waveform shows about 40 ms.
+pub const SCOPE_DECIMATION: usize = 16;
+
+/// the latest waveform of each channel and of the master, oldest point first.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, PartialEq, PartialOrd, Default)]
+pub struct Scope {
+    pub channels: [Vec<f32>; N_CHANNELS],
+    pub master: Vec<f32>,
+}
+
+/// a ring of decimated points for each channel and the master, written by the audio thread and
+/// read by the main loop. like the meters, the points are `f32`s stored as bits so neither side
+/// ever waits on a lock.
+#[derive(Debug, Clone, Resource)]
+pub struct ScopeTap {
+    points: Arc<[AtomicU32]>,
+    /// where the next point goes in each ring.
+    pos: Arc<AtomicUsize>,
+}
+
+impl Default for ScopeTap {
+    fn default() -> Self {
+        Self {
+            points: (0..N_METERS * SCOPE_POINTS)
+                .map(|_| AtomicU32::new(0))
+                .collect(),
+            pos: Arc::default(),
+        }
+    }
+}
+
+impl ScopeTap {
+    /// adds the points of the latest buffer, one list per channel then the master. every list
+    /// must be the same length.
+    pub fn push(&self, points: &[Vec<f32>; N_METERS]) {
+        let pos = self.pos.load(Ordering::Relaxed);
+
+        for (ring, points) in self.points.chunks_exact(SCOPE_POINTS).zip(points) {
+            for (i, point) in points.iter().enumerate() {
+                ring[(pos + i) % SCOPE_POINTS].store(point.to_bits(), Ordering::Relaxed);
+            }
+        }
+
+        self.pos
+            .store((pos + points[0].len()) % SCOPE_POINTS, Ordering::Relaxed);
+    }
+
+    /// the waveforms as they are now.
+    pub fn read(&self) -> Scope {
+        let pos = self.pos.load(Ordering::Relaxed);
+        let mut rings = self.points.chunks_exact(SCOPE_POINTS).map(|ring| {
+            (0..SCOPE_POINTS)
+                .map(|i| f32::from_bits(ring[(pos + i) % SCOPE_POINTS].load(Ordering::Relaxed)))
+                .collect::<Vec<_>>()
+        });
+
+        Scope {
+            channels: std::array::from_fn(|_| rings.next().unwrap_or_default()),
+            master: rings.next().unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oldest_point_first() {
+        let tap = ScopeTap::default();
+
+        for i in 0..SCOPE_POINTS + 3 {
+            tap.push(&std::array::from_fn(|meter| vec![(i * 10 + meter) as f32]));
+        }
+
+        let scope = tap.read();
+
+        assert_eq!(scope.channels[0][0], 30.0);
+        assert_eq!(
+            scope.channels[2][SCOPE_POINTS - 1],
+            ((SCOPE_POINTS + 2) * 10 + 2) as f32
+        );
+        assert_eq!(scope.master[0], 34.0);
+    }
+}
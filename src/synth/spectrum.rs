@@ -0,0 +1,195 @@
+use bevy::prelude::Resource;
+use std::{
+    f32::consts::PI,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+};
+
+/// the number of bands the master spectrum is shown in.
+pub const SPECTRUM_BANDS: usize = 32;
+/// the frames in each FFT. at 48 kHz a bin is about 47 Hz wide.
+const FFT_SIZE: usize = 1024;
+/// the bottom of the lowest band, in Hz.
+const LOW_HZ: f32 = 40.0;
+/// band levels are never taken below this, in dB.
+pub const SPECTRUM_FLOOR: f32 = -90.0;
+
+/// the level of each band of the master, in dB from full scale, written by the audio thread and
+/// read by the main loop.
+#[derive(Debug, Clone, Default, Resource)]
+pub struct SpectrumTap {
+    bands: Arc<[AtomicU32; SPECTRUM_BANDS]>,
+}
+
+impl SpectrumTap {
+    /// the band levels as they are now, lowest band first.
+    pub fn read(&self) -> Vec<f32> {
+        self.bands
+            .iter()
+            .map(|band| f32::from_bits(band.load(Ordering::Relaxed)))
+            .collect()
+    }
+}
+
+/// in place radix 2 FFT. `re` and `im` must be the same power of two long.
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let mut j = 0;
+
+    for i in 1..n {
+        let mut bit = n >> 1;
+
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+
+        j |= bit;
+
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+
+        len <<= 1;
+    }
+}
+
+/// gathers the master into blocks and works out the level of each band. lives on the audio
+/// thread.
+#[derive(Debug, Clone)]
+pub struct Analyzer {
+    input: Vec<f32>,
+    window: Vec<f32>,
+    re: Vec<f32>,
+    im: Vec<f32>,
+    /// the first bin of each band, then one past the last bin of the top band.
+    edges: [usize; SPECTRUM_BANDS + 1],
+}
+
+impl Analyzer {
+    pub fn new(sample_rate: f32) -> Self {
+        let bin_hz = sample_rate / FFT_SIZE as f32;
+        let high_hz = sample_rate * 0.5;
+        // bands are spaced evenly in pitch, and always at least one bin wide.
+        let mut edges = [0; SPECTRUM_BANDS + 1];
+
+        for (i, edge) in edges.iter_mut().enumerate() {
+            let hz = LOW_HZ * (high_hz / LOW_HZ).powf(i as f32 / SPECTRUM_BANDS as f32);
+            *edge = ((hz / bin_hz).round() as usize).clamp(1, FFT_SIZE / 2);
+        }
+
+        for i in 1..edges.len() {
+            edges[i] = edges[i].max(edges[i - 1] + 1);
+        }
+
+        Self {
+            input: Vec::with_capacity(FFT_SIZE),
+            window: (0..FFT_SIZE)
+                .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FFT_SIZE as f32).cos())
+                .collect(),
+            re: vec![0.0; FFT_SIZE],
+            im: vec![0.0; FFT_SIZE],
+            edges,
+        }
+    }
+
+    /// adds the interleaved stereo `out`, updating `tap` each time a block fills.
+    pub fn push(&mut self, out: &[f32], tap: &SpectrumTap) {
+        for frame in out.chunks_exact(2) {
+            self.input.push((frame[0] + frame[1]) * 0.5);
+
+            if self.input.len() == FFT_SIZE {
+                self.analyse(tap);
+                self.input.clear();
+            }
+        }
+    }
+
+    fn analyse(&mut self, tap: &SpectrumTap) {
+        for ((re, im), (input, window)) in self
+            .re
+            .iter_mut()
+            .zip(self.im.iter_mut())
+            .zip(self.input.iter().zip(&self.window))
+        {
+            *re = input * window;
+            *im = 0.0;
+        }
+
+        fft(&mut self.re, &mut self.im);
+
+        // a full scale sine comes out as a quarter of the block, as the window halves it.
+        let full_scale = FFT_SIZE as f32 * 0.25;
+
+        for (band, edges) in tap.bands.iter().zip(self.edges.windows(2)) {
+            let peak = (edges[0]..edges[1].min(FFT_SIZE / 2 + 1))
+                .map(|bin| self.re[bin].hypot(self.im[bin]))
+                .fold(0.0_f32, f32::max);
+            let db = (20.0 * (peak / full_scale).log10()).max(SPECTRUM_FLOOR);
+
+            band.store(db.to_bits(), Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48_000.0;
+
+    fn analyse(signal: impl Fn(f32) -> f32) -> Vec<f32> {
+        let mut analyzer = Analyzer::new(SAMPLE_RATE);
+        let tap = SpectrumTap::default();
+        let out: Vec<f32> = (0..FFT_SIZE)
+            .flat_map(|i| {
+                let sample = signal(i as f32 / SAMPLE_RATE);
+                [sample, sample]
+            })
+            .collect();
+
+        analyzer.push(&out, &tap);
+        tap.read()
+    }
+
+    #[test]
+    fn sine_lands_in_its_band() {
+        let bands = analyse(|t| (2.0 * PI * 1_000.0 * t).sin());
+        let loudest = (0..SPECTRUM_BANDS)
+            .max_by(|a, b| bands[*a].total_cmp(&bands[*b]))
+            .unwrap();
+        let analyzer = Analyzer::new(SAMPLE_RATE);
+        let bin = (1_000.0 / (SAMPLE_RATE / FFT_SIZE as f32)).round() as usize;
+
+        assert!((analyzer.edges[loudest]..analyzer.edges[loudest + 1]).contains(&bin));
+        assert!(bands[loudest].abs() < 1.5);
+    }
+
+    #[test]
+    fn silence_is_the_floor() {
+        assert!(analyse(|_| 0.0).iter().all(|db| *db == SPECTRUM_FLOOR));
+    }
+}
@@ -0,0 +1,409 @@
+use super::MAX_VOICES;
+use crate::{
+    params::{Choice, Param, Range},
+    pygame_coms::{Index, Note},
+};
+use pyo3::pyclass;
+use serde::{Deserialize, Serialize};
+
+/// how an instrument plays more than one note at a time.
+#[pyclass(module = "tracker_backend", eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum VoiceMode {
+    /// as many notes as the polyphony allows.
+    #[default]
+    Poly,
+    /// one note at a time, every note restarts the envelopes. chords play just their root.
+    Mono,
+    /// one note at a time, a note played while another is held changes its pitch without
+    /// restarting the envelopes.
+    Legato,
+}
+
+impl Choice for VoiceMode {
+    const ALL: &'static [Self] = &[Self::Poly, Self::Mono, Self::Legato];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Poly => "POLY",
+            Self::Mono => "MONO",
+            Self::Legato => "LEGATO",
+        }
+    }
+}
+
+/// which voice makes way for a new one once an instrument is out of voices.
+#[pyclass(module = "tracker_backend", eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum StealPolicy {
+    #[default]
+    Oldest,
+    Quietest,
+    /// a voice playing the same note, or the oldest if there isn't one.
+    SameNote,
+}
+
+impl Choice for StealPolicy {
+    const ALL: &'static [Self] = &[Self::Oldest, Self::Quietest, Self::SameNote];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Oldest => "OLDEST",
+            Self::Quietest => "QUIETEST",
+            Self::SameNote => "SAME NOTE",
+        }
+    }
+}
+
+/// how long a stolen voice takes to fade out, long enough that it doesn't click.
+pub const STEAL_SECONDS: f32 = 0.005;
+
+/// the gain of a voice, which falls to nothing over `STEAL_SECONDS` once the voice is stolen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StealFade {
+    gain: f32,
+    stolen: bool,
+}
+
+impl Default for StealFade {
+    fn default() -> Self {
+        Self {
+            gain: 1.0,
+            stolen: false,
+        }
+    }
+}
+
+impl StealFade {
+    pub fn steal(&mut self) {
+        self.stolen = true;
+    }
+
+    pub fn is_stolen(&self) -> bool {
+        self.stolen
+    }
+
+    /// the gain of the next sample.
+    pub fn next(&mut self, sample_rate: f32) -> f32 {
+        if self.stolen {
+            self.gain = (self.gain - 1.0 / (STEAL_SECONDS * sample_rate)).max(0.0);
+        }
+
+        self.gain
+    }
+
+    /// true once a stolen voice has faded out.
+    pub fn is_done(&self) -> bool {
+        self.stolen && self.gain <= 0.0
+    }
+}
+
+/// how many voices an instrument gets and how they are shared out. not used by percussion,
+/// drums are one shots.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Polyphony {
+    pub mode: VoiceMode,
+    /// the most voices, held or releasing, the instrument plays at once in poly mode.
+    pub voices: u8,
+    pub steal: StealPolicy,
+}
+
+impl Default for Polyphony {
+    fn default() -> Self {
+        Self {
+            mode: VoiceMode::Poly,
+            voices: 8,
+            steal: StealPolicy::Oldest,
+        }
+    }
+}
+
+impl Polyphony {
+    pub fn params(&mut self) -> Vec<Param<'_>> {
+        vec![
+            Param::choice("VOICE MODE", &mut self.mode),
+            Param::new(
+                "VOICES",
+                &mut self.voices,
+                Range::new(1.0, MAX_VOICES as f32, 1.0),
+            ),
+            Param::choice("STEAL", &mut self.steal),
+        ]
+    }
+
+    /// the most voices the instrument plays at once.
+    pub fn limit(&self) -> usize {
+        match self.mode {
+            VoiceMode::Poly => self.voices.max(1) as usize,
+            VoiceMode::Mono | VoiceMode::Legato => 1,
+        }
+    }
+
+    /// the notes of a row that are played, every note in poly mode, just the first otherwise.
+    pub fn notes(&self, mut notes: Vec<Note>) -> Vec<Note> {
+        if self.mode != VoiceMode::Poly {
+            notes.truncate(1);
+        }
+
+        notes
+    }
+}
+
+/// what the allocator needs to know about a voice. voices are kept oldest first, so a voices
+/// age is its place in the list.
+pub trait Allocated {
+    fn channel(&self) -> usize;
+    fn instrument(&self) -> Index;
+    fn note(&self) -> Note;
+    /// how loud the voice is now, from 0.0 to 1.0.
+    fn level(&self) -> f32;
+    /// true until the note is released.
+    fn is_held(&self) -> bool;
+    /// changes the pitch of the voice without restarting it, for legato. false if the voice
+    /// can't change pitch, in which case a new voice is started.
+    fn set_note(&mut self, note: Note) -> bool;
+    /// starts fading the voice out, to make way for another.
+    fn steal(&mut self);
+    /// true once the voice has been stolen. a stolen voice no longer counts as one of the voices
+    /// of its instrument.
+    fn is_stolen(&self) -> bool;
+}
+
+/// the voice of `instrument` that makes way for `note`, or `None` if the instrument has a voice
+/// free. ties go to the oldest voice, so the same voices are always stolen.
+fn victim<V: Allocated>(
+    voices: &[V],
+    polyphony: &Polyphony,
+    instrument: Index,
+    note: Note,
+) -> Option<usize> {
+    let mut playing = voices
+        .iter()
+        .enumerate()
+        .filter(|(_, voice)| voice.instrument() == instrument && !voice.is_stolen());
+
+    if playing.clone().count() < polyphony.limit() {
+        return None;
+    }
+
+    let oldest = playing.clone().next().map(|(i, _)| i);
+
+    match polyphony.steal {
+        StealPolicy::Oldest => oldest,
+        StealPolicy::Quietest => playing
+            .min_by(|(_, a), (_, b)| a.level().total_cmp(&b.level()))
+            .map(|(i, _)| i),
+        StealPolicy::SameNote => playing
+            .find(|(_, voice)| voice.note() == note)
+            .map(|(i, _)| i)
+            .or(oldest),
+    }
+}
+
+/// steals voices till there is room for `instrument` to play `note`, first to keep the
+/// instrument under its polyphony then to keep the synth under `MAX_VOICES`. stolen voices fade
+/// out, and are only cut short if there are as many of them again, so `voices` never grows past
+/// `MAX_VOICES * 2`.
+pub fn make_room<V: Allocated>(
+    voices: &mut Vec<V>,
+    polyphony: &Polyphony,
+    instrument: Index,
+    note: Note,
+) {
+    while let Some(i) = victim(voices, polyphony, instrument, note) {
+        voices[i].steal();
+    }
+
+    if voices.iter().filter(|voice| !voice.is_stolen()).count() >= MAX_VOICES
+        && let Some(oldest) = voices.iter_mut().find(|voice| !voice.is_stolen())
+    {
+        oldest.steal();
+    }
+
+    if voices.len() >= MAX_VOICES * 2 {
+        let oldest = voices.iter().position(V::is_stolen).unwrap_or(0);
+        voices.remove(oldest);
+    }
+}
+
+/// moves the newest held voice of `instrument` on `channel` to `note`. false if there is no such
+/// voice, or it can't change pitch.
+pub fn glide<V: Allocated>(
+    voices: &mut [V],
+    channel: usize,
+    instrument: Index,
+    note: Note,
+) -> bool {
+    voices
+        .iter_mut()
+        .rev()
+        .find(|voice| {
+            voice.channel() == channel && voice.instrument() == instrument && voice.is_held()
+        })
+        .is_some_and(|voice| voice.set_note(note))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct TestVoice {
+        instrument: Index,
+        note: Note,
+        level: f32,
+        held: bool,
+        stolen: bool,
+    }
+
+    impl Allocated for TestVoice {
+        fn channel(&self) -> usize {
+            0
+        }
+
+        fn instrument(&self) -> Index {
+            self.instrument
+        }
+
+        fn note(&self) -> Note {
+            self.note
+        }
+
+        fn level(&self) -> f32 {
+            self.level
+        }
+
+        fn is_held(&self) -> bool {
+            self.held
+        }
+
+        fn set_note(&mut self, note: Note) -> bool {
+            self.note = note;
+            true
+        }
+
+        fn steal(&mut self) {
+            self.stolen = true;
+        }
+
+        fn is_stolen(&self) -> bool {
+            self.stolen
+        }
+    }
+
+    /// the notes of the voices that haven't been stolen.
+    fn playing(voices: &[TestVoice]) -> Vec<Note> {
+        voices
+            .iter()
+            .filter(|voice| !voice.stolen)
+            .map(|voice| voice.note)
+            .collect()
+    }
+
+    /// three voices of instrument 0 and one of instrument 1, oldest first.
+    fn voices() -> Vec<TestVoice> {
+        [(0, 60, 0.5), (1, 62, 0.1), (0, 64, 0.2), (0, 67, 0.2)]
+            .map(|(instrument, note, level)| TestVoice {
+                instrument,
+                note,
+                level,
+                held: true,
+                stolen: false,
+            })
+            .to_vec()
+    }
+
+    fn play(steal: StealPolicy, note: Note) -> Vec<Note> {
+        let polyphony = Polyphony {
+            voices: 3,
+            steal,
+            ..Polyphony::default()
+        };
+        let mut voices = voices();
+
+        make_room(&mut voices, &polyphony, 0, note);
+        playing(&voices)
+    }
+
+    #[test]
+    fn steals_by_policy() {
+        assert_eq!(play(StealPolicy::Oldest, 72), [62, 64, 67]);
+        // 64 and 67 are as quiet as each other, the older goes.
+        assert_eq!(play(StealPolicy::Quietest, 72), [60, 62, 67]);
+        assert_eq!(play(StealPolicy::SameNote, 67), [60, 62, 64]);
+        assert_eq!(play(StealPolicy::SameNote, 72), [62, 64, 67]);
+    }
+
+    #[test]
+    fn only_steals_when_full() {
+        let mut voices = voices();
+
+        make_room(&mut voices, &Polyphony::default(), 0, 72);
+        assert_eq!(voices, self::voices());
+
+        let mono = Polyphony {
+            mode: VoiceMode::Mono,
+            ..Polyphony::default()
+        };
+
+        make_room(&mut voices, &mono, 0, 72);
+        assert_eq!(playing(&voices), [62]);
+        assert_eq!(mono.notes(vec![60, 64, 67]), [60]);
+
+        // the stolen voices fade out rather than stopping dead, and don't count any more.
+        assert_eq!(voices.len(), 4);
+        make_room(&mut voices, &mono, 0, 74);
+        assert_eq!(voices.len(), 4);
+    }
+
+    #[test]
+    fn stolen_voices_fade_out() {
+        let mut fade = StealFade::default();
+        assert_eq!(fade.next(48_000.0), 1.0);
+
+        fade.steal();
+        let mut samples = 0;
+
+        while !fade.is_done() {
+            fade.next(48_000.0);
+            samples += 1;
+        }
+
+        assert!((samples as f32 - STEAL_SECONDS * 48_000.0).abs() <= 1.0);
+    }
+
+    #[test]
+    fn keeps_the_synth_under_max_voices() {
+        let polyphony = Polyphony {
+            voices: MAX_VOICES as u8,
+            ..Polyphony::default()
+        };
+        let mut voices = Vec::new();
+
+        for i in 0..MAX_VOICES * 3 {
+            make_room(&mut voices, &polyphony, i % 2, 60);
+            voices.push(TestVoice {
+                instrument: i % 2,
+                note: 60,
+                level: 1.0,
+                held: true,
+                stolen: false,
+            });
+
+            assert!(playing(&voices).len() <= MAX_VOICES);
+            assert!(voices.len() <= MAX_VOICES * 2);
+        }
+    }
+
+    #[test]
+    fn legato_moves_the_held_voice() {
+        let mut voices = voices();
+        voices[3].held = false;
+
+        assert!(glide(&mut voices, 0, 0, 72));
+        assert_eq!(voices[2].note, 72);
+        assert!(!glide(&mut voices, 0, 2, 72));
+    }
+}
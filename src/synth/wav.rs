@@ -0,0 +1,49 @@
+use anyhow::{bail, Result};
+use hound::{SampleFormat, WavReader};
+use std::path::{Path, PathBuf};
+
+/// reads a WAV file, mixing all of its channels down to one. returns the samples and the
+/// files sample rate.
+pub fn read_mono(path: &Path) -> Result<(Vec<f32>, u32)> {
+    let mut reader = WavReader::open(path)?;
+    let spec = reader.spec();
+    let channels = spec.channels.max(1) as usize;
+
+    let samples: Vec<f32> = match spec.sample_format {
+        SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        SampleFormat::Int => {
+            let scale = (1_i64 << (spec.bits_per_sample - 1)) as f32;
+
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 / scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+
+    if samples.len() < channels {
+        bail!("{} has no samples", path.display());
+    }
+
+    let samples = samples
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+
+    Ok((samples, spec.sample_rate))
+}
+
+/// the WAV files in `dir`, sorted by name.
+pub fn list(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut paths: Vec<_> = dir
+        .read_dir()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
+        })
+        .collect();
+    paths.sort();
+
+    Ok(paths)
+}
@@ -0,0 +1,201 @@
+use super::wav;
+use crate::pygame_coms::Index;
+use anyhow::Result;
+use pyo3::{pyclass, pymethods};
+use serde::{Deserialize, Serialize};
+use std::{
+    f32::consts::TAU,
+    path::{Path, PathBuf},
+};
+
+/// how many points a frame drawn on the wavetable screen has.
+pub const DRAW_POINTS: usize = 32;
+/// the smallest step a drawn point can be moved by.
+pub const DRAW_STEP: f32 = 1.0 / 16.0;
+/// the length of a frame in a multi-frame WAV file. (the same as most wavetable synths use)
+pub const FILE_FRAME_LEN: usize = 2048;
+
+/// a table of single cycle waves. an oscillator plays one frame or morphs between two
+/// neighbouring frames.
+#[pyclass(module = "tracker_backend", get_all)]
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct Wavetable {
+    pub name: Index,
+    /// the WAV file the table was loaded from, `None` if it was drawn.
+    pub file: Option<PathBuf>,
+    /// every frame is the same length.
+    pub frames: Vec<Vec<f32>>,
+}
+
+impl Wavetable {
+    /// a drawn table with a single sine wave frame.
+    pub fn new(index: Index) -> Self {
+        let frame = (0..DRAW_POINTS)
+            .map(|i| {
+                let sample = (i as f32 / DRAW_POINTS as f32 * TAU).sin();
+
+                (sample / DRAW_STEP).round() * DRAW_STEP
+            })
+            .collect();
+
+        Self {
+            name: index,
+            file: None,
+            frames: vec![frame],
+        }
+    }
+
+    /// loads a WAV file. files that are a multiple of `FILE_FRAME_LEN` samples long are split
+    /// into frames, any other file is a single frame. channels are mixed down to one.
+    pub fn load(index: Index, path: &Path) -> Result<Self> {
+        let (mut samples, _) = wav::read_mono(path)?;
+
+        let peak = samples.iter().fold(0.0_f32, |peak, s| peak.max(s.abs()));
+
+        if peak > 0.0 {
+            samples.iter_mut().for_each(|s| *s /= peak);
+        }
+
+        let frames =
+            if samples.len() > FILE_FRAME_LEN && samples.len().is_multiple_of(FILE_FRAME_LEN) {
+                samples
+                    .chunks(FILE_FRAME_LEN)
+                    .map(|frame| frame.to_vec())
+                    .collect()
+            } else {
+                vec![samples]
+            };
+
+        Ok(Self {
+            name: index,
+            file: Some(path.to_path_buf()),
+            frames,
+        })
+    }
+
+    /// true if the table can be changed on the wavetable screen.
+    pub fn editable(&self) -> bool {
+        self.file.is_none()
+    }
+
+    /// the sample at `phase` (0.0 to 1.0) of the wave `position` (0.0 to 1.0) of the way through
+    /// the table. interpolates within and between frames.
+    pub fn sample(&self, phase: f32, position: f32) -> f32 {
+        let Some(last) = self.frames.len().checked_sub(1) else {
+            return 0.0;
+        };
+
+        let position = position.clamp(0.0, 1.0) * last as f32;
+        let frame_i = (position as usize).min(last);
+        let a = Self::frame_sample(&self.frames[frame_i], phase);
+
+        if frame_i == last {
+            return a;
+        }
+
+        let b = Self::frame_sample(&self.frames[frame_i + 1], phase);
+        let mix = position - frame_i as f32;
+
+        a + (b - a) * mix
+    }
+
+    fn frame_sample(frame: &[f32], phase: f32) -> f32 {
+        if frame.is_empty() {
+            return 0.0;
+        }
+
+        let pos = phase.rem_euclid(1.0) * frame.len() as f32;
+        let i = (pos as usize).min(frame.len() - 1);
+        let a = frame[i];
+        let b = frame[(i + 1) % frame.len()];
+
+        a + (b - a) * (pos - i as f32)
+    }
+}
+
+#[pymethods]
+impl Wavetable {
+    /// the frame shown on the wavetable screen, reduced to at most `points` samples.
+    fn preview(&self, frame: usize, points: usize) -> Vec<f32> {
+        let Some(frame) = self.frames.get(frame) else {
+            return Vec::new();
+        };
+
+        if frame.len() <= points {
+            return frame.clone();
+        }
+
+        (0..points)
+            .map(|i| frame[i * frame.len() / points])
+            .collect()
+    }
+}
+
+/// the folder WAV wavetables are loaded from at start up.
+pub fn wavetable_dir() -> PathBuf {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join(".local/share/midi-tracker/wavetables")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hound::{SampleFormat, WavSpec, WavWriter};
+
+    #[test]
+    fn morphs_between_frames() {
+        let table = Wavetable {
+            name: 0,
+            file: None,
+            frames: vec![vec![0.0, 0.0], vec![1.0, 1.0], vec![-1.0, -1.0]],
+        };
+
+        assert_eq!(table.sample(0.25, 0.0), 0.0);
+        assert_eq!(table.sample(0.25, 0.25), 0.5);
+        assert_eq!(table.sample(0.25, 0.5), 1.0);
+        assert_eq!(table.sample(0.25, 0.75), 0.0);
+        assert_eq!(table.sample(0.25, 1.0), -1.0);
+    }
+
+    #[test]
+    fn interpolates_within_a_frame() {
+        let table = Wavetable {
+            name: 0,
+            file: None,
+            frames: vec![vec![0.0, 1.0, 0.0, -1.0]],
+        };
+
+        assert_eq!(table.sample(0.125, 0.0), 0.5);
+        assert_eq!(table.sample(0.875, 0.0), -0.5);
+        assert_eq!(table.sample(1.25, 0.0), 1.0);
+    }
+
+    #[test]
+    fn loads_wav() {
+        let path = std::env::temp_dir().join("tracker_backend_wavetable_test.wav");
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 48_000,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let mut writer = WavWriter::create(&path, spec).unwrap();
+
+        for i in 0..FILE_FRAME_LEN * 2 {
+            let sample = if i < FILE_FRAME_LEN { 8_000 } else { -16_000 };
+            writer.write_sample(sample as i16).unwrap();
+        }
+
+        writer.finalize().unwrap();
+
+        let table = Wavetable::load(3, &path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(table.frames.len(), 2);
+        assert!(!table.editable());
+        assert_eq!(table.frames[0][0], 0.5);
+        assert_eq!(table.frames[1][0], -1.0);
+    }
+}
@@ -0,0 +1,92 @@
+use crate::{
+    controls::LastViewed,
+    midi::{MidiMessage, MidiReceived, MOD_WHEEL, PITCH_BEND_CENTER},
+    pygame_coms::{Index, Note},
+    synth::{SynthCmd, SynthHandle},
+    tracker_state::StateUpdated,
+    ScreenState,
+};
+use bevy::{log::*, prelude::*};
+
+/// the notes played from the MIDI input on the synth screen, and what they are played with.
+#[derive(Debug, Clone, Default, Resource)]
+pub struct LiveNotes {
+    /// the mixer channel the notes are heard on.
+    pub channel: usize,
+    pub instrument: Index,
+    /// the notes held down, oldest first.
+    pub held: Vec<Note>,
+}
+
+pub struct SynthMenuPlugin;
+
+impl Plugin for SynthMenuPlugin {
+    fn build(&self, app: &mut App) {
+        debug!("tracker_backend::synth_menu::SynthMenuPlugin loaded");
+
+        app.init_resource::<LiveNotes>()
+            .add_systems(Update, play_input.run_if(in_state(ScreenState::PlaySynth)))
+            .add_systems(OnEnter(ScreenState::PlaySynth), pick_instrument)
+            .add_systems(OnExit(ScreenState::PlaySynth), release_all);
+    }
+}
+
+/// plays the instrument and channel last looked at.
+fn pick_instrument(last_viewed: Res<LastViewed>, mut live: ResMut<LiveNotes>) {
+    live.channel = last_viewed.channel;
+    live.instrument = last_viewed.instrument;
+}
+
+/// plays the notes, pitch bend, and mod wheel of the MIDI input. every MIDI channel plays the
+/// same instrument.
+fn play_input(
+    mut received: EventReader<MidiReceived>,
+    mut live: ResMut<LiveNotes>,
+    synth: Res<SynthHandle>,
+    mut state_updated: EventWriter<StateUpdated>,
+) {
+    for MidiReceived { message, .. } in received.read() {
+        match *message {
+            MidiMessage::NoteOn { note, velocity, .. } => {
+                synth.send(SynthCmd::LiveNoteOn {
+                    channel: live.channel,
+                    instrument: live.instrument,
+                    note,
+                    velocity: velocity as f32 / 127.0,
+                });
+                live.held.retain(|held| *held != note);
+                live.held.push(note);
+                state_updated.send_default();
+            }
+            MidiMessage::NoteOff { note, .. } => {
+                synth.send(SynthCmd::LiveNoteOff {
+                    channel: live.channel,
+                    note,
+                });
+                live.held.retain(|held| *held != note);
+                state_updated.send_default();
+            }
+            MidiMessage::ControlChange {
+                control: MOD_WHEEL,
+                value,
+                ..
+            } => synth.send(SynthCmd::SetModWheel(value as f32 / 127.0)),
+            MidiMessage::PitchBend { value, .. } => synth.send(SynthCmd::SetPitchBend(
+                (value as f32 - PITCH_BEND_CENTER as f32) / PITCH_BEND_CENTER as f32,
+            )),
+            _ => {}
+        }
+    }
+}
+
+/// lets go of everything still held when leaving the synth screen, and centres the wheels.
+fn release_all(mut live: ResMut<LiveNotes>, synth: Res<SynthHandle>) {
+    let channel = live.channel;
+
+    for note in live.held.drain(..) {
+        synth.send(SynthCmd::LiveNoteOff { channel, note });
+    }
+
+    synth.send(SynthCmd::SetPitchBend(0.0));
+    synth.send(SynthCmd::SetModWheel(0.0));
+}
@@ -1,13 +1,25 @@
 use crate::{
-    config::ui::Bpm,
+    config::{audio::AudioConfig, midi::MidiConfig, ui::Bpm},
     ipc::RustIPC,
+    midi::learn,
     pygame_coms::{
-        Chains, DisplayCursor, Instruments, Phrases, PlaybackCursor, PlaybackCursorWrapper, Screen,
-        ScreenData, Song, State,
+        Chains, DisplayCursor, Instrument, InstrumentOutput, Instruments, Phrases,
+        PlaybackCursorWrapper, Screen, ScreenData, Song, State, Telemetry, TELEMETRY_RATE,
     },
+    sequencer::{ExternalTempo, Sequencer},
+    synth::{
+        mixer::Meters,
+        scope::ScopeTap,
+        spectrum::SpectrumTap,
+        wav,
+        wavetable::{wavetable_dir, Wavetable},
+        AudioClock,
+    },
+    synth_menu::LiveNotes,
     ScreenState,
 };
 use bevy::{log::*, prelude::*};
+use std::sync::Arc;
 
 pub struct TrackerStatePlugin;
 
@@ -24,18 +36,25 @@ impl Plugin for TrackerStatePlugin {
             .insert_resource(Tempo(120))
             .insert_resource(Screen::Song())
             .insert_resource(AllInstruments::default())
+            .insert_resource(AllWavetables::default())
             .insert_resource(AllPhrases::default())
             .insert_resource(AllChains::default())
             .insert_resource(PlaybackCursorWrapper::default())
             .insert_resource(DisplayCursor::default())
             .insert_resource(Song::default())
+            .add_systems(Startup, load_wavetables)
             .add_systems(Update, update_state)
+            .add_systems(Update, send_telemetry)
             .add_systems(OnEnter(ScreenState::EditSong), send_state)
             .add_systems(OnEnter(ScreenState::EditChain), send_state)
             .add_systems(OnEnter(ScreenState::EditPhrase), send_state)
             .add_systems(OnEnter(ScreenState::EditInsts), send_state)
+            .add_systems(OnEnter(ScreenState::EditWavetable), send_state)
             .add_systems(OnEnter(ScreenState::PlaySynth), send_state)
-            .add_systems(OnEnter(ScreenState::Settings), send_state);
+            .add_systems(OnEnter(ScreenState::EditMixer), send_state)
+            .add_systems(OnEnter(ScreenState::EditEffects), send_state)
+            .add_systems(OnEnter(ScreenState::Settings), send_state)
+            .add_systems(OnEnter(ScreenState::Mappings), send_state);
     }
 }
 
@@ -69,15 +88,64 @@ pub struct AllInstruments(pub Instruments);
 
 impl Default for AllInstruments {
     fn default() -> Self {
-        let insts: Instruments = Vec::with_capacity(256);
+        let mut insts: Instruments = Vec::with_capacity(256);
 
-        // TODO: add default instruments
-        // insts[0] =
+        // the songs default instruments.
+        for i in 0..3 {
+            insts.push(Some(Instrument::new(i)));
+        }
+
+        // the default instrument of the percussion channel.
+        if let Some(Some(drums)) = insts.get_mut(2) {
+            drums.output = InstrumentOutput::Percusion;
+            drums.human_name = "Drums".into();
+        }
 
         Self(insts)
     }
 }
 
+/// wavetables are behind an `Arc` so sending them to the audio thread is cheap.
+#[derive(Debug, Resource)]
+pub struct AllWavetables(pub Vec<Option<Arc<Wavetable>>>);
+
+impl Default for AllWavetables {
+    fn default() -> Self {
+        let mut tables = vec![None; 256];
+        tables[0] = Some(Arc::new(Wavetable::new(0)));
+
+        Self(tables)
+    }
+}
+
+/// loads the WAV files in the wavetable folder into the free wavetable slots.
+pub(crate) fn load_wavetables(mut wavetables: ResMut<AllWavetables>) {
+    let dir = wavetable_dir();
+
+    let paths = match wav::list(&dir) {
+        Ok(paths) => paths,
+        Err(e) => {
+            info!("not loading wavetables from {}: {e}", dir.display());
+            return;
+        }
+    };
+
+    for path in paths {
+        let Some(i) = wavetables.0.iter().position(Option::is_none) else {
+            warn!("no free wavetable slots left for {}", path.display());
+            break;
+        };
+
+        match Wavetable::load(i, &path) {
+            Ok(table) => {
+                info!("loaded wavetable {i:02X} from {}", path.display());
+                wavetables.0[i] = Some(Arc::new(table));
+            }
+            Err(e) => error!("failed to load wavetable {}: {e}", path.display()),
+        }
+    }
+}
+
 // fn run_if_state_updated(updated: Res<StateUpdated>) -> bool {
 //     updated.0
 // }
@@ -88,50 +156,57 @@ fn send_state(mut state_update_events: EventWriter<StateUpdated>) {
 fn update_state(
     coms: ResMut<RustIPC>,
     mut state_update_events: EventReader<StateUpdated>,
-    tempo: Res<Tempo>,
+    (tempo, external_tempo): (Res<Tempo>, Res<ExternalTempo>),
     screen: Res<Screen>,
     instruments: Res<AllInstruments>,
+    wavetables: Res<AllWavetables>,
     phrases: Res<AllPhrases>,
     chains: Res<AllChains>,
-    playing: Res<PlaybackCursorWrapper>,
+    sequencer: Res<Sequencer>,
     display_cursor: Res<DisplayCursor>,
     song: Res<Song>,
+    meters: Res<Meters>,
+    (audio_config, midi_config): (Res<AudioConfig>, Res<MidiConfig>),
+    clock: Res<AudioClock>,
+    live: Res<LiveNotes>,
     // playing: Res<PlaybackCursor>,
 ) {
     for _ev in state_update_events.read() {
         let screen = match *screen {
-            Screen::Song() => ScreenData::Song(song.clone()),
-            Screen::Settings() => ScreenData::Settings(),
-            Screen::EditChain(i) => ScreenData::Chain(chains.0[i].unwrap()),
-            Screen::EditPhrase(i) => ScreenData::Phrase(phrases.0[i].unwrap()),
-            Screen::Instrument(i) => ScreenData::Instrument(instruments.0[i].clone().unwrap()),
-            Screen::PlaySynth() => ScreenData::PlaySynth(),
-        };
-
-        let playing = match playing.0.lock().unwrap().clone() {
-            PlaybackCursor::NotPlaying() => [None, None, None, None],
-            PlaybackCursor::NotFull {
-                from_screen: _,
-                row: _,
-            } => [None, None, None, None],
-            PlaybackCursor::FullSong {
-                lead_1: _,
-                lead_2: _,
-                bass: _,
-                perc: _,
-                row: _,
-            } => {
-                // TODO: do the thing
-                [None, None, None, None]
+            Screen::Song() => ScreenData::Song(song.clone().into()),
+            Screen::Settings() => ScreenData::Settings(audio_config.clone(), midi_config.clone()),
+            Screen::EditChain(i) => ScreenData::Chain(chains.0[i].unwrap().into()),
+            Screen::EditPhrase(i) => ScreenData::Phrase(phrases.0[i].unwrap().into()),
+            Screen::Instrument(i) => {
+                ScreenData::Instrument(instruments.0[i].clone().unwrap().into())
             }
+            Screen::Wavetable(i) => {
+                ScreenData::Wavetable(wavetables.0[i].as_deref().cloned().unwrap().into())
+            }
+            Screen::PlaySynth() => ScreenData::PlaySynth(live.instrument, live.channel),
+            Screen::Mixer() => ScreenData::Mixer(song.mixer.into(), meters.levels().into()),
+            Screen::Effects() => ScreenData::Effects(song.effects, song.dynamics),
+            Screen::Mappings() => ScreenData::Mappings(learn::rows(&song, &midi_config)),
         };
 
+        let mut playing = sequencer.playing();
+
+        // notes played from the MIDI input sound over what the song plays on their channel.
+        if let Some(note) = live.held.last()
+            && let Some(channel) = playing.get_mut(live.channel)
+        {
+            *channel = Some(*note);
+        }
+
         let state = State {
             display_cursor: display_cursor.clone(),
             screen,
             tempo: tempo.0,
+            clock_tempo: external_tempo.0,
             song: song.clone(),
             playing,
+            gain_reduction: meters.gain_reduction(),
+            underruns: clock.underruns(),
         };
 
         info!("sending state to frontend");
@@ -145,3 +220,32 @@ fn update_state(
         info!("sending complete");
     }
 }
+
+/// sends the scope, levels, and spectrum on their own, a few times a second, so the whole state
+/// isn't rebuilt for them.
+fn send_telemetry(
+    coms: Res<RustIPC>,
+    scope: Res<ScopeTap>,
+    meters: Res<Meters>,
+    spectrum: Res<SpectrumTap>,
+    time: Res<Time>,
+    mut since_sent: Local<f32>,
+) {
+    *since_sent += time.delta_seconds();
+
+    if *since_sent < 1.0 / TELEMETRY_RATE {
+        return;
+    }
+
+    *since_sent = 0.0;
+
+    let telemetry = Telemetry {
+        scope: scope.read(),
+        levels: meters.levels(),
+        spectrum: spectrum.read(),
+    };
+
+    if let Err(e) = coms.send_telemetry(telemetry) {
+        error!("sending telemetry failed with error: {e}");
+    }
+}
@@ -0,0 +1,397 @@
+use crate::{
+    controls::MyGamepad,
+    pygame_coms::{DisplayCursor, Index, Screen},
+    synth::wavetable::{Wavetable, DRAW_POINTS, DRAW_STEP},
+    tracker_state::{AllWavetables, StateUpdated},
+    ExitMenuState, ScreenState,
+};
+use bevy::{log::*, prelude::*};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Default, Resource)]
+struct WavetableIndex(Index);
+
+/// the cursor of the screen the wavetable screen was entered from.
+#[derive(Debug, Clone, Default, Resource)]
+struct ReturnCursor(DisplayCursor);
+
+pub struct WavetableMenuPlugin;
+
+impl Plugin for WavetableMenuPlugin {
+    fn build(&self, app: &mut App) {
+        debug!("tracker_backend::wavetable_menu::WavetableMenuPlugin loaded");
+
+        app.init_resource::<WavetableIndex>()
+            .init_resource::<ReturnCursor>()
+            .add_event::<EditPoint>()
+            .add_systems(
+                Update,
+                movement
+                    .run_if(in_state(ScreenState::EditWavetable))
+                    .run_if(not(in_state(ExitMenuState::Opened))),
+            )
+            .add_systems(
+                Update,
+                set_select
+                    .run_if(in_state(ScreenState::EditWavetable))
+                    .run_if(not(in_state(ExitMenuState::Opened))),
+            )
+            .add_systems(
+                Update,
+                change_point
+                    .run_if(in_state(ScreenState::EditWavetable))
+                    .run_if(not(in_state(ExitMenuState::Opened))),
+            )
+            .add_systems(
+                Update,
+                edit_point
+                    .run_if(in_state(ScreenState::EditWavetable))
+                    .run_if(not(in_state(ExitMenuState::Opened))),
+            )
+            .add_systems(
+                Update,
+                add_frame
+                    .run_if(in_state(ScreenState::EditWavetable))
+                    .run_if(not(in_state(ExitMenuState::Opened))),
+            )
+            .add_systems(
+                Update,
+                rm_frame
+                    .run_if(in_state(ScreenState::EditWavetable))
+                    .run_if(not(in_state(ExitMenuState::Opened))),
+            )
+            .add_systems(OnEnter(ScreenState::EditWavetable), set_wavetable_index)
+            .add_systems(OnEnter(ScreenState::EditWavetable), set_selected)
+            .add_systems(
+                OnEnter(ScreenState::EditWavetable),
+                (save_cursor, set_cursor).chain(),
+            )
+            .add_systems(OnExit(ScreenState::EditWavetable), restore_cursor);
+    }
+}
+
+#[derive(Event, Debug, Default)]
+struct EditPoint {
+    /// how many `DRAW_STEP`s to move the point under the cursor by.
+    delta: i32,
+}
+
+/// also makes a new drawn wavetable if there is not one at the index yet.
+fn set_wavetable_index(
+    mut wavetable_index: ResMut<WavetableIndex>,
+    mut wavetables: ResMut<AllWavetables>,
+    screen: Res<Screen>,
+) {
+    if let Screen::Wavetable(wavetable_i) = *screen {
+        wavetable_index.0 = wavetable_i;
+
+        if wavetables.0[wavetable_i].is_none() {
+            wavetables.0[wavetable_i] = Some(Arc::new(Wavetable::new(wavetable_i)));
+        }
+    }
+}
+
+fn set_selected(mut display_cursor: ResMut<DisplayCursor>) {
+    display_cursor.selected = false;
+}
+
+fn save_cursor(display_cursor: Res<DisplayCursor>, mut return_cursor: ResMut<ReturnCursor>) {
+    return_cursor.0 = display_cursor.clone();
+}
+
+fn restore_cursor(mut display_cursor: ResMut<DisplayCursor>, return_cursor: Res<ReturnCursor>) {
+    display_cursor.row = return_cursor.0.row;
+    display_cursor.col = return_cursor.0.col;
+}
+
+fn set_cursor(mut display_cursor: ResMut<DisplayCursor>) {
+    display_cursor.row = 0;
+    display_cursor.col = 0;
+}
+
+fn set_select(
+    mut display_cursor: ResMut<DisplayCursor>,
+    mut state_updated: EventWriter<StateUpdated>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    my_gamepad: Option<Res<MyGamepad>>,
+) {
+    let Some(&MyGamepad(gamepad)) = my_gamepad.as_deref() else {
+        // no gamepad is connected
+        return;
+    };
+
+    let a_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::East,
+    };
+
+    if (!buttons.pressed(a_button) && display_cursor.selected)
+        || (buttons.pressed(a_button) && !display_cursor.selected)
+    {
+        state_updated.send_default();
+        display_cursor.selected = !(!buttons.pressed(a_button) && display_cursor.selected)
+            || (buttons.pressed(a_button) && !display_cursor.selected);
+    }
+}
+
+fn change_point(
+    display_cursor: Res<DisplayCursor>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    my_gamepad: Option<Res<MyGamepad>>,
+    mut edit_point_event: EventWriter<EditPoint>,
+) {
+    let Some(&MyGamepad(gamepad)) = my_gamepad.as_deref() else {
+        // no gamepad is connected
+        return;
+    };
+
+    let up_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::DPadUp,
+    };
+    let down_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::DPadDown,
+    };
+    let left_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::DPadLeft,
+    };
+    let right_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::DPadRight,
+    };
+    let a_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::East,
+    };
+
+    if !buttons.pressed(a_button) || !display_cursor.selected {
+        return;
+    }
+
+    if buttons.just_released(up_button) {
+        edit_point_event.send(EditPoint { delta: 1 });
+    }
+
+    if buttons.just_released(down_button) {
+        edit_point_event.send(EditPoint { delta: -1 });
+    }
+
+    if buttons.just_released(right_button) {
+        edit_point_event.send(EditPoint { delta: 4 });
+    }
+
+    if buttons.just_released(left_button) {
+        edit_point_event.send(EditPoint { delta: -4 });
+    }
+}
+
+fn edit_point(
+    mut wavetables: ResMut<AllWavetables>,
+    display_cursor: Res<DisplayCursor>,
+    mut events: EventReader<EditPoint>,
+    mut state_updated: EventWriter<StateUpdated>,
+    wavetable_index: Res<WavetableIndex>,
+) {
+    let wavetable_i = wavetable_index.0;
+
+    for ev in events.read() {
+        let Some(Some(wavetable)) = wavetables.0.get_mut(wavetable_i) else {
+            error!("attempting to edit wavetable {wavetable_i}, which does not exist.");
+            continue;
+        };
+
+        if !wavetable.editable() {
+            warn!("wavetable {wavetable_i} was loaded from a file and can not be drawn on.");
+            continue;
+        }
+
+        if let Some(point) = Arc::make_mut(wavetable)
+            .frames
+            .get_mut(display_cursor.row)
+            .and_then(|frame| frame.get_mut(display_cursor.col))
+        {
+            *point = (*point + ev.delta as f32 * DRAW_STEP).clamp(-1.0, 1.0);
+            state_updated.send_default();
+        }
+    }
+}
+
+/// B + right adds a copy of the frame under the cursor after it.
+fn add_frame(
+    mut wavetables: ResMut<AllWavetables>,
+    mut display_cursor: ResMut<DisplayCursor>,
+    mut state_updated: EventWriter<StateUpdated>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    my_gamepad: Option<Res<MyGamepad>>,
+    wavetable_index: Res<WavetableIndex>,
+) {
+    let Some(&MyGamepad(gamepad)) = my_gamepad.as_deref() else {
+        // no gamepad is connected
+        return;
+    };
+
+    let right_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::DPadRight,
+    };
+    let b_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::South,
+    };
+    let a_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::East,
+    };
+
+    if !buttons.just_released(right_button)
+        || !buttons.pressed(b_button)
+        || buttons.pressed(a_button)
+    {
+        return;
+    }
+
+    if let Some(Some(wavetable)) = wavetables.0.get_mut(wavetable_index.0)
+        && wavetable.editable()
+    {
+        let frames = &mut Arc::make_mut(wavetable).frames;
+        let row = display_cursor.row.min(frames.len() - 1);
+
+        frames.insert(row + 1, frames[row].clone());
+        display_cursor.row = row + 1;
+        state_updated.send_default();
+    }
+}
+
+/// A + B removes the frame under the cursor, the last frame can't be removed.
+fn rm_frame(
+    mut wavetables: ResMut<AllWavetables>,
+    mut display_cursor: ResMut<DisplayCursor>,
+    mut state_updated: EventWriter<StateUpdated>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    my_gamepad: Option<Res<MyGamepad>>,
+    wavetable_index: Res<WavetableIndex>,
+) {
+    let Some(&MyGamepad(gamepad)) = my_gamepad.as_deref() else {
+        // no gamepad is connected
+        return;
+    };
+
+    let b_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::South,
+    };
+    let a_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::East,
+    };
+
+    if !((buttons.just_released(a_button) && buttons.pressed(b_button))
+        || (buttons.just_released(b_button) && buttons.pressed(a_button)))
+    {
+        return;
+    }
+
+    if let Some(Some(wavetable)) = wavetables.0.get_mut(wavetable_index.0)
+        && wavetable.editable()
+        && wavetable.frames.len() > 1
+    {
+        let frames = &mut Arc::make_mut(wavetable).frames;
+        frames.remove(display_cursor.row.min(frames.len() - 1));
+        display_cursor.row = display_cursor.row.min(frames.len() - 1);
+        state_updated.send_default();
+    }
+}
+
+/// left and right move between the points of a frame, up and down between frames.
+fn movement(
+    buttons: Res<ButtonInput<GamepadButton>>,
+    my_gamepad: Option<Res<MyGamepad>>,
+    mut display_cursor: ResMut<DisplayCursor>,
+    mut state_updated: EventWriter<StateUpdated>,
+    gamepads: Res<Gamepads>,
+    wavetables: Res<AllWavetables>,
+    wavetable_index: Res<WavetableIndex>,
+) {
+    if display_cursor.selected {
+        return;
+    }
+
+    let Some(&MyGamepad(gamepad)) = my_gamepad.as_deref() else {
+        // no gamepad is connected
+        return;
+    };
+
+    let up_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::DPadUp,
+    };
+    let down_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::DPadDown,
+    };
+    let left_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::DPadLeft,
+    };
+    let right_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::DPadRight,
+    };
+    let b_button = GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::South,
+    };
+
+    let start_button = if let Some(name) = gamepads.name(gamepad)
+        && name.starts_with("PS5")
+    {
+        GamepadButton {
+            gamepad,
+            button_type: GamepadButtonType::Start,
+        }
+    } else {
+        GamepadButton {
+            gamepad,
+            button_type: GamepadButtonType::Select,
+        }
+    };
+
+    if buttons.pressed(start_button) || buttons.pressed(b_button) {
+        return;
+    }
+
+    let Some(Some(wavetable)) = wavetables.0.get(wavetable_index.0) else {
+        return;
+    };
+    let n_frames = wavetable.frames.len();
+
+    if buttons.just_released(up_button) {
+        display_cursor.row = if display_cursor.row == 0 {
+            n_frames - 1
+        } else {
+            display_cursor.row - 1
+        };
+        state_updated.send_default();
+    }
+
+    if buttons.just_released(down_button) {
+        display_cursor.row = (display_cursor.row + 1) % n_frames;
+        state_updated.send_default();
+    }
+
+    if buttons.just_released(left_button) {
+        display_cursor.col = if display_cursor.col == 0 {
+            DRAW_POINTS - 1
+        } else {
+            display_cursor.col - 1
+        };
+        state_updated.send_default();
+    }
+
+    if buttons.just_released(right_button) {
+        display_cursor.col = (display_cursor.col + 1) % DRAW_POINTS;
+        state_updated.send_default();
+    }
+}